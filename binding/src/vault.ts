@@ -253,6 +253,16 @@ export class VaultUtils {
     );
   };
 
+  public custodianOnlyMusigLockingScript = (params: {
+    custodianPubkeys: Uint8Array;
+    custodianQuorum: number;
+  }) => {
+    return this.wasm.custodian_only_musig_locking_script(
+      params.custodianPubkeys,
+      params.custodianQuorum
+    );
+  };
+
   public upcLockingScript = (params: {
     userPubkey: Uint8Array;
     protocolPubkey: Uint8Array;
@@ -266,4 +276,18 @@ export class VaultUtils {
       params.custodianQuorum
     );
   };
+
+  public upcMusigLockingScript = (params: {
+    userPubkey: Uint8Array;
+    protocolPubkey: Uint8Array;
+    custodianPubkeys: Uint8Array;
+    custodianQuorum: number;
+  }) => {
+    return this.wasm.upc_musig_locking_script(
+      params.userPubkey,
+      params.protocolPubkey,
+      params.custodianPubkeys,
+      params.custodianQuorum
+    );
+  };
 }
@@ -4,10 +4,10 @@ use bitcoin::consensus::serialize;
 use bitcoin::hashes::Hash;
 use bitcoin::hex::DisplayHex;
 use bitcoin::Network;
+use bitcoin::OutPoint;
 use bitcoin::{
     transaction::Version, Address, Amount, PrivateKey, ScriptBuf, Transaction, TxIn, TxOut, Witness,
 };
-use bitcoin::{OutPoint, Psbt};
 use clap::Parser;
 use electrum_client::{Client, ElectrumApi};
 use rust_mempool::{AddressType, BitcoinWallet, MempoolClient};
@@ -222,7 +222,10 @@ impl CollectUtxosCmd {
         let custodian_quorum = test_suite.env().custodian_quorum;
         let script = match <VaultManager as CustodianOnly>::locking_script(
             &test_suite.custodian_pubkeys(),
+            None,
             custodian_quorum,
+            TaprootInternalKey::Nums,
+            None,
         ) {
             Ok(s) => s,
             Err(e) => {
@@ -298,10 +301,17 @@ impl CollectUtxosCmd {
                                 }],
                                 custodian_pubkeys: custodian_pubkeys.clone(),
                                 custodian_quorum,
+                                custodian_weights: None,
+                                internal_key: TaprootInternalKey::Nums,
+                                branch_layout: BranchLayout::Legacy,
                                 fee_rate: 2,
+                                key_origins: None,
+                                sighash_type: None,
                                 rbf: false,
                                 session_sequence: 0,
                                 custodian_group_uid: [0u8; HASH_SIZE],
+                                signers: None,
+                                subsets: None,
                             },
                         ) {
                             Ok(psbt) => psbt,
@@ -319,15 +329,15 @@ impl CollectUtxosCmd {
                         )
                         .unwrap();
                     }
-                    <Psbt as SignByKeyMap<bitcoin::secp256k1::All>>::finalize(&mut unstaked_psbt);
-                    let finalized_tx = match unstaked_psbt.extract_tx() {
-                        Ok(tx) => tx,
+                    let tx_hex = match <VaultManager as Signing>::finalize_psbt_and_extract_tx(
+                        &mut unstaked_psbt,
+                    ) {
+                        Ok(tx_hex) => tx_hex,
                         Err(e) => {
                             eprintln!("Failed to extract tx for batch {}: {}", i + 1, e);
                             return;
                         }
                     };
-                    let tx_hex = bitcoin::consensus::serialize(&finalized_tx);
                     let result = mempool_client
                         .broadcast_transaction(tx_hex.to_lower_hex_string().as_str())
                         .await;
@@ -18,6 +18,12 @@ ByteBuffer aggregate_tap_script_sigs(
     size_t tap_script_sigs_map_len
 );
 
+ByteBuffer combine_psbts(
+    const uint8_t* psbts_bytes,
+    const size_t* psbt_lens,
+    size_t psbts_len
+);
+
 */
 import "C"
 import (
@@ -50,7 +56,37 @@ func AggregateTapScriptSigs(psbtBytes []byte, tapScriptSigsMap go_utils.TapScrip
 	defer C.free_byte_buffer(result)
 
 	if result.data == nil || result.len == 0 {
-		return nil, ErrFailedToAggregateTapScriptSigs
+		return nil, lastError(ErrFailedToAggregateTapScriptSigs)
+	}
+
+	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
+}
+
+// CombinePsbts merges partially signed copies of the same PSBT, as emitted by any BIP-174 signer
+func CombinePsbts(psbts [][]byte) ([]byte, error) {
+	if len(psbts) == 0 {
+		return nil, ErrInvalidPsbt
+	}
+
+	var psbtsBytes []byte
+	psbtLens := make([]C.size_t, len(psbts))
+	for i, psbt := range psbts {
+		if len(psbt) == 0 {
+			return nil, ErrInvalidPsbt
+		}
+		psbtsBytes = append(psbtsBytes, psbt...)
+		psbtLens[i] = C.size_t(len(psbt))
+	}
+
+	result := C.combine_psbts(
+		(*C.uint8_t)(unsafe.Pointer(&psbtsBytes[0])),
+		(*C.size_t)(unsafe.Pointer(&psbtLens[0])),
+		C.size_t(len(psbts)),
+	)
+	defer C.free_byte_buffer(result)
+
+	if result.data == nil || result.len == 0 {
+		return nil, lastError(ErrFailedToCombinePsbts)
 	}
 
 	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
@@ -1,6 +1,23 @@
 package vault
 
-import "errors"
+/*
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct {
+    uint8_t* data;
+    size_t len;
+} ByteBuffer;
+
+ByteBuffer last_error_message();
+void free_byte_buffer(ByteBuffer buffer);
+*/
+import "C"
+import (
+	"errors"
+	"fmt"
+	"unsafe"
+)
 
 var (
 	ErrInvalidScript                         = errors.New("invalid script")
@@ -13,4 +30,19 @@ var (
 	ErrFailedToAggregateTapScriptSigs        = errors.New("failed to aggregate tap script sigs")
 	ErrFailedToFinalizePsbtAndExtractTx      = errors.New("failed to finalize psbt and extract tx")
 	ErrFailedToBuildCustodianOnlyUnlockingTx = errors.New("failed to build custodian only unlocking tx")
+	ErrFailedToBuildTimeGatedLockingScript   = errors.New("failed to build time gated locking script")
+	ErrFailedToCombinePsbts                  = errors.New("failed to combine psbts")
+	ErrFailedToInspectPsbt                   = errors.New("failed to inspect psbt")
 )
+
+// lastError wraps err with the message of the last failed library call, if any
+func lastError(err error) error {
+	result := C.last_error_message()
+	defer C.free_byte_buffer(result)
+
+	if result.data == nil || result.len == 0 {
+		return err
+	}
+
+	return fmt.Errorf("%w: %s", err, C.GoStringN((*C.char)(unsafe.Pointer(result.data)), C.int(result.len)))
+}
@@ -0,0 +1,69 @@
+package vault
+
+/*
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef struct {
+    uint8_t* data;
+    size_t len;
+} ByteBuffer;
+
+ByteBuffer inspect_psbt_signing_status(
+    const uint8_t* psbt_bytes,
+    size_t psbt_len
+);
+
+void free_byte_buffer(ByteBuffer buffer);
+*/
+import "C"
+import (
+	"encoding/json"
+	"unsafe"
+)
+
+// InputSigningStatus is the signing progress of a psbt input, keys and leaf hash are hex encoded
+type InputSigningStatus struct {
+	Input           int      `json:"input"`
+	LeafHash        *string  `json:"leaf_hash"`
+	Signed          []string `json:"signed"`
+	Invalid         []string `json:"invalid"`
+	Missing         []string `json:"missing"`
+	Quorum          *uint8   `json:"quorum"`
+	Weight          uint16   `json:"weight"`
+	MissingPreimage *string  `json:"missing_preimage"`
+	QuorumMet       bool     `json:"quorum_met"`
+	Finalized       bool     `json:"finalized"`
+}
+
+// PsbtSigningStatus is ready once every input can be finalized
+type PsbtSigningStatus struct {
+	Inputs []InputSigningStatus `json:"inputs"`
+	Ready  bool                 `json:"ready"`
+}
+
+// InspectPsbtSigningStatus reports the valid and missing signatures of every input of the psbt
+func InspectPsbtSigningStatus(psbtBytes []byte) (*PsbtSigningStatus, error) {
+	if len(psbtBytes) == 0 {
+		return nil, ErrInvalidPsbt
+	}
+
+	result := C.inspect_psbt_signing_status(
+		(*C.uint8_t)(unsafe.Pointer(&psbtBytes[0])),
+		C.size_t(len(psbtBytes)),
+	)
+	defer C.free_byte_buffer(result)
+
+	if result.data == nil || result.len == 0 {
+		return nil, lastError(ErrFailedToInspectPsbt)
+	}
+
+	goBytes := C.GoBytes(unsafe.Pointer(result.data), C.int(result.len))
+
+	var status PsbtSigningStatus
+	if err := json.Unmarshal(goBytes, &status); err != nil {
+		return nil, err
+	}
+
+	return &status, nil
+}
@@ -16,6 +16,25 @@ ByteBuffer custodians_only_locking_script(
     uint8_t custodian_quorum
 );
 
+ByteBuffer custodians_only_musig_locking_script(
+    const uint8_t (*custodian_pub_keys_ptr)[33],
+    size_t custodian_pub_keys_len,
+    uint8_t custodian_quorum
+);
+
+typedef struct {
+    uint8_t typ;
+    uint16_t value;
+} RelativeLockTimeFFI;
+
+ByteBuffer time_gated_locking_script(
+    const uint8_t (*party_pub_key)[33],
+    const uint8_t (*custodian_pub_keys_ptr)[33],
+    size_t custodian_pub_keys_len,
+    uint8_t custodian_quorum,
+    RelativeLockTimeFFI sequence
+);
+
 void free_byte_buffer(ByteBuffer buffer);
 */
 import "C"
@@ -35,7 +54,54 @@ func CustodiansOnlyLockingScript(custodianPubKeys []types.PublicKey, custodianQu
 	defer C.free_byte_buffer(result)
 
 	if result.data == nil || result.len == 0 {
-		return nil, ErrFailedToBuildCustodianOnlyUnlockingTx
+		return nil, lastError(ErrFailedToBuildCustodianOnlyUnlockingTx)
+	}
+
+	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
+}
+
+// CustodiansOnlyMusigLockingScript locks to the MuSig2 key of all custodians on the key path,
+// the custodian quorum leaf stays available on the script path
+func CustodiansOnlyMusigLockingScript(custodianPubKeys []types.PublicKey, custodianQuorum uint8) ([]byte, error) {
+	result := C.custodians_only_musig_locking_script(
+		(*[33]C.uint8_t)(unsafe.Pointer(&custodianPubKeys[0])),
+		C.size_t(len(custodianPubKeys)),
+		C.uint8_t(custodianQuorum),
+	)
+
+	defer C.free_byte_buffer(result)
+
+	if result.data == nil || result.len == 0 {
+		return nil, lastError(ErrFailedToBuildCustodianOnlyUnlockingTx)
+	}
+
+	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
+}
+
+// RelativeLockTimeType is the BIP-68 unit of a relative lock time
+type RelativeLockTimeType uint8
+
+const (
+	RelativeLockTimeBlocks  RelativeLockTimeType = 0
+	RelativeLockTimeSeconds RelativeLockTimeType = 1 // 512-second intervals
+)
+
+func TimeGatedLockingScript(partyPubKey types.PublicKey, custodianPubKeys []types.PublicKey, custodianQuorum uint8, sequenceType RelativeLockTimeType, sequence uint16) ([]byte, error) {
+	result := C.time_gated_locking_script(
+		(*[33]C.uint8_t)(unsafe.Pointer(&partyPubKey[0])),
+		(*[33]C.uint8_t)(unsafe.Pointer(&custodianPubKeys[0])),
+		C.size_t(len(custodianPubKeys)),
+		C.uint8_t(custodianQuorum),
+		C.RelativeLockTimeFFI{
+			typ:   C.uint8_t(sequenceType),
+			value: C.uint16_t(sequence),
+		},
+	)
+
+	defer C.free_byte_buffer(result)
+
+	if result.data == nil || result.len == 0 {
+		return nil, lastError(ErrFailedToBuildTimeGatedLockingScript)
 	}
 
 	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
@@ -14,3 +14,20 @@ func TestLockingScript(t *testing.T) {
 
 	t.Logf("Taproot script: %x", scripts)
 }
+
+func TestMusigLockingScript(t *testing.T) {
+	scripts, err := vault.CustodiansOnlyMusigLockingScript(custodianPubKeys, 3)
+	if err != nil {
+		t.Fatalf("Failed to build taproot script: %v", err)
+	}
+
+	nums, err := vault.CustodiansOnlyLockingScript(custodianPubKeys, 3)
+	if err != nil {
+		t.Fatalf("Failed to build taproot script: %v", err)
+	}
+	if string(scripts) == string(nums) {
+		t.Fatalf("MuSig2 internal key must change the output key")
+	}
+
+	t.Logf("Taproot script: %x", scripts)
+}
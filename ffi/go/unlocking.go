@@ -52,6 +52,25 @@ ByteBuffer build_custodian_only(
   uint64_t fee_rate
 );
 
+ByteBuffer build_custodian_only_with_sighash(
+  const uint8_t* tag,
+  size_t tag_len,
+  const uint8_t* service_tag,
+  size_t service_tag_len,
+  uint8_t version,
+  uint8_t network_kind,
+  const PreviousOutpointFFI* inputs_ptr,
+  size_t inputs_len,
+  const TxOutFFI* outputs_ptr,
+  size_t outputs_len,
+  const uint8_t (*custodian_pub_keys_ptr)[33],
+  size_t custodian_pub_keys_len,
+  uint8_t custodian_quorum,
+  bool rbf,
+  uint64_t fee_rate,
+  uint8_t sighash_type
+);
+
 ByteBuffer build_pooling_redeem_tx(
   const uint8_t* buffer,
   size_t len
@@ -112,6 +131,11 @@ func convertOutputsToFFI(outputs []types.UnlockingOutput) ([]C.TxOutFFI, []unsaf
 }
 
 func BuildCustodianOnlyUnstakingTx(tag []byte, serviceTag []byte, version uint8, network types.NetworkKind, inputs []types.PreviousOutpoint, outputs []types.UnlockingOutput, custodianPubKeys []types.PublicKey, custodianQuorum uint8, rbf bool, feeRate uint64) ([]byte, error) {
+	return BuildCustodianOnlyUnstakingTxWithSighash(tag, serviceTag, version, network, inputs, outputs, custodianPubKeys, custodianQuorum, rbf, feeRate, 0)
+}
+
+// BuildCustodianOnlyUnstakingTxWithSighash signs the inputs with sighashType, 0 is SIGHASH_DEFAULT.
+func BuildCustodianOnlyUnstakingTxWithSighash(tag []byte, serviceTag []byte, version uint8, network types.NetworkKind, inputs []types.PreviousOutpoint, outputs []types.UnlockingOutput, custodianPubKeys []types.PublicKey, custodianQuorum uint8, rbf bool, feeRate uint64, sighashType uint8) ([]byte, error) {
 	if !network.Valid() {
 		return nil, ErrInvalidNetwork
 	}
@@ -129,7 +153,7 @@ func BuildCustodianOnlyUnstakingTx(tag []byte, serviceTag []byte, version uint8,
 		}
 	}()
 
-	result := C.build_custodian_only(
+	result := C.build_custodian_only_with_sighash(
 		(*C.uint8_t)(unsafe.Pointer(&tag[0])),
 		C.size_t(len(tag)),
 		(*C.uint8_t)(unsafe.Pointer(&serviceTag[0])),
@@ -145,11 +169,12 @@ func BuildCustodianOnlyUnstakingTx(tag []byte, serviceTag []byte, version uint8,
 		C.uint8_t(custodianQuorum),
 		C.bool(rbf),
 		C.uint64_t(feeRate),
+		C.uint8_t(sighashType),
 	)
 	defer C.free_byte_buffer(result)
 
 	if result.data == nil || result.len == 0 {
-		return nil, ErrFailedToBuildCustodianOnlyUnlockingTx
+		return nil, lastError(ErrFailedToBuildCustodianOnlyUnlockingTx)
 	}
 
 	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
@@ -282,7 +307,7 @@ func BuildPoolingRedeemTx(tag []byte,
 	defer C.free_byte_buffer(result)
 
 	if result.data == nil || result.len == 0 {
-		return nil, ErrFailedToBuildCustodianOnlyUnlockingTx
+		return nil, lastError(ErrFailedToBuildCustodianOnlyUnlockingTx)
 	}
 
 	return C.GoBytes(unsafe.Pointer(result.data), C.int(result.len)), nil
@@ -1,9 +1,52 @@
+use std::{cell::RefCell, fmt::Display};
+
 use thiserror::Error;
 
+use crate::{create_null_buffer, ByteBuffer};
+
 #[derive(Error, Debug)]
 pub enum FFIError {
     #[error("Invalid Txid")]
     InvalidTxid,
     #[error("Failed to parse xonly public keys")]
     FailedToBuildScript,
+    #[error("Invalid relative lock time type: {0}")]
+    InvalidRelativeLockTimeType(u8),
+    #[error("Invalid public key")]
+    InvalidPublicKey,
+    #[error("Invalid custodian group uid")]
+    InvalidCustodianGroupUid,
+    #[error("Invalid unlocking type: {0}")]
+    InvalidUnlockingType(u8),
+    #[error("Invalid sighash type: {0}")]
+    InvalidSighashType(u8),
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Records `err` for [`last_error_message`] and returns the null buffer of a failed call.
+pub(crate) fn error_buffer(err: impl Display) -> ByteBuffer {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(err.to_string()));
+    create_null_buffer()
+}
+
+/// Returns the message of the last error on this thread, a null buffer if there is none.
+/// The message is cleared once read, the buffer is released with `free_byte_buffer`.
+#[no_mangle]
+pub extern "C" fn last_error_message() -> ByteBuffer {
+    match LAST_ERROR.with(|last| last.borrow_mut().take()) {
+        Some(message) => {
+            let mut output = Vec::with_capacity(message.len());
+            output.extend_from_slice(message.as_bytes());
+            let buffer = ByteBuffer {
+                data: output.as_mut_ptr(),
+                len: output.len(),
+            };
+            std::mem::forget(output); // Prevent deallocation
+            buffer
+        }
+        None => create_null_buffer(),
+    }
 }
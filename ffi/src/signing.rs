@@ -1,9 +1,10 @@
 use bitcoin::Psbt;
 use std::slice;
 use vault::TapScriptSigsMap;
-use vault::{Signing, VaultManager};
+use vault::{inspect_signing_status, Signing, VaultManager};
 
 use crate::create_null_buffer;
+use crate::error_buffer;
 use crate::network_from_byte;
 use crate::ByteBuffer;
 
@@ -171,14 +172,59 @@ pub unsafe extern "C" fn aggregate_tap_script_sigs(
     let tap_script_sigs_map: TapScriptSigsMap =
         serde_json::from_slice(tap_script_sigs_map_slice).unwrap_or_default();
 
-    // Aggregate signatures
-    if VaultManager::aggregate_tap_script_sigs(&mut psbt, &tap_script_sigs_map).is_err() {
+    // Aggregate signatures, rejected ones are reported through `last_error_message`
+    let psbt_hex = match VaultManager::aggregate_tap_script_sigs(&mut psbt, &tap_script_sigs_map) {
+        Ok(psbt_hex) => psbt_hex,
+        Err(err) => return error_buffer(err),
+    };
+
+    // Allocate and copy the result
+    let mut output = Vec::with_capacity(psbt_hex.len());
+    output.extend_from_slice(&psbt_hex);
+    let buffer = ByteBuffer {
+        data: output.as_mut_ptr(),
+        len: output.len(),
+    };
+    std::mem::forget(output); // Prevent deallocation
+    buffer
+}
+
+/// Combines partially signed copies of the same PSBT.
+///
+/// `psbts_bytes` holds the `psbts_len` PSBTs back to back, `psbt_lens` the length of each of them.
+/// Mismatching or conflicting PSBTs are reported through `last_error_message`.
+///
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+#[no_mangle]
+pub unsafe extern "C" fn combine_psbts(
+    psbts_bytes: *const u8,
+    psbt_lens: *const usize,
+    psbts_len: usize,
+) -> ByteBuffer {
+    if psbts_bytes.is_null() || psbt_lens.is_null() || psbts_len == 0 {
         return create_null_buffer();
     }
 
-    let psbt_hex = match VaultManager::aggregate_tap_script_sigs(&mut psbt, &tap_script_sigs_map) {
+    let psbt_lens = slice::from_raw_parts(psbt_lens, psbts_len);
+    let psbts_slice = slice::from_raw_parts(psbts_bytes, psbt_lens.iter().sum());
+
+    let mut offset = 0;
+    let mut psbts = Vec::with_capacity(psbts_len);
+    for len in psbt_lens {
+        match Psbt::deserialize(&psbts_slice[offset..offset + len]) {
+            Ok(psbt) => psbts.push(psbt),
+            Err(err) => return error_buffer(err),
+        }
+        offset += len;
+    }
+
+    let mut psbt = psbts.remove(0);
+    let psbt_hex = match VaultManager::combine_psbts(&mut psbt, &psbts) {
         Ok(psbt_hex) => psbt_hex,
-        Err(_) => return create_null_buffer(),
+        Err(err) => return error_buffer(err),
     };
 
     // Allocate and copy the result
@@ -192,6 +238,44 @@ pub unsafe extern "C" fn aggregate_tap_script_sigs(
     buffer
 }
 
+/// Reports the valid and missing signatures of every input, as json, so a coordinator knows
+/// when the psbt can be finalized
+///
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+#[no_mangle]
+pub unsafe extern "C" fn inspect_psbt_signing_status(
+    psbt_bytes: *const u8,
+    psbt_len: usize,
+) -> ByteBuffer {
+    if psbt_bytes.is_null() {
+        return create_null_buffer();
+    }
+
+    let psbt_slice = slice::from_raw_parts(psbt_bytes, psbt_len);
+
+    let psbt = match Psbt::deserialize(psbt_slice) {
+        Ok(psbt) => psbt,
+        Err(err) => return error_buffer(err),
+    };
+
+    let json = match serde_json::to_vec(&inspect_signing_status(&psbt)) {
+        Ok(json) => json,
+        Err(err) => return error_buffer(err),
+    };
+
+    let mut output = Vec::with_capacity(json.len());
+    output.extend_from_slice(&json);
+    let buffer = ByteBuffer {
+        data: output.as_mut_ptr(),
+        len: output.len(),
+    };
+    std::mem::forget(output); // Prevent deallocation
+    buffer
+}
+
 /// # Safety
 ///
 /// This function is unsafe because it uses raw pointers and assumes that the caller has
@@ -1,9 +1,11 @@
 use std::slice;
 
-use bitcoin::PublicKey;
-use vault::VaultManager;
+use bitcoin::{relative, PublicKey};
+use vault::{TaprootInternalKey, VaultManager};
 
-use crate::{create_null_buffer, ByteBuffer, PublicKeyFFI};
+use crate::{
+    create_null_buffer, error_buffer, ByteBuffer, FFIError, PublicKeyFFI, RelativeLockTimeFFI,
+};
 
 /// # Safety
 ///
@@ -14,6 +16,41 @@ pub unsafe extern "C" fn custodians_only_locking_script(
     custodian_pub_keys_ptr: *const PublicKeyFFI,
     custodian_pub_keys_len: usize,
     custodian_quorum: u8,
+) -> ByteBuffer {
+    custodians_only_locking_script_with_internal_key(
+        custodian_pub_keys_ptr,
+        custodian_pub_keys_len,
+        custodian_quorum,
+        TaprootInternalKey::Nums,
+    )
+}
+
+/// Same as [`custodians_only_locking_script`] with the MuSig2 aggregated key of the custodians as
+/// internal key, so that all of them together can spend through the key path.
+///
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+#[no_mangle]
+pub unsafe extern "C" fn custodians_only_musig_locking_script(
+    custodian_pub_keys_ptr: *const PublicKeyFFI,
+    custodian_pub_keys_len: usize,
+    custodian_quorum: u8,
+) -> ByteBuffer {
+    custodians_only_locking_script_with_internal_key(
+        custodian_pub_keys_ptr,
+        custodian_pub_keys_len,
+        custodian_quorum,
+        TaprootInternalKey::CustodianMusig2,
+    )
+}
+
+unsafe fn custodians_only_locking_script_with_internal_key(
+    custodian_pub_keys_ptr: *const PublicKeyFFI,
+    custodian_pub_keys_len: usize,
+    custodian_quorum: u8,
+    internal_key: TaprootInternalKey,
 ) -> ByteBuffer {
     // Safety checks for null pointers
     if custodian_pub_keys_ptr.is_null() {
@@ -22,15 +59,21 @@ pub unsafe extern "C" fn custodians_only_locking_script(
 
     let custodian_pub_keys = slice::from_raw_parts(custodian_pub_keys_ptr, custodian_pub_keys_len);
 
-    let custodian_pub_keys: Vec<PublicKey> = custodian_pub_keys
+    let Ok(custodian_pub_keys) = custodian_pub_keys
         .iter()
-        .map(|key| PublicKey::from_slice(key.as_slice()).unwrap())
-        .collect();
+        .map(|key| PublicKey::from_slice(key.as_slice()))
+        .collect::<Result<Vec<PublicKey>, _>>()
+    else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
 
     // Create parameters for the unstaking function
     let result = <VaultManager as vault::CustodianOnly>::locking_script(
         &custodian_pub_keys,
+        None,
         custodian_quorum,
+        internal_key,
+        None,
     );
     // Call the build_custodian_only function
     match result {
@@ -45,6 +88,64 @@ pub unsafe extern "C" fn custodians_only_locking_script(
             std::mem::forget(output); // Prevent deallocation
             buffer
         }
-        Err(_) => create_null_buffer(),
+        Err(err) => error_buffer(err),
+    }
+}
+
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+#[no_mangle]
+pub unsafe extern "C" fn time_gated_locking_script(
+    party_pub_key: *const PublicKeyFFI,
+    custodian_pub_keys_ptr: *const PublicKeyFFI,
+    custodian_pub_keys_len: usize,
+    custodian_quorum: u8,
+    sequence: RelativeLockTimeFFI,
+) -> ByteBuffer {
+    // Safety checks for null pointers
+    if party_pub_key.is_null() || custodian_pub_keys_ptr.is_null() {
+        return create_null_buffer();
+    }
+
+    let Ok(party_pub_key) = PublicKey::from_slice((*party_pub_key).as_slice()) else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
+    let sequence = match relative::LockTime::try_from(&sequence) {
+        Ok(sequence) => sequence,
+        Err(err) => return error_buffer(err),
+    };
+
+    let custodian_pub_keys = slice::from_raw_parts(custodian_pub_keys_ptr, custodian_pub_keys_len);
+
+    let Ok(custodian_pub_keys) = custodian_pub_keys
+        .iter()
+        .map(|key| PublicKey::from_slice(key.as_slice()))
+        .collect::<Result<Vec<PublicKey>, _>>()
+    else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
+
+    let result = <VaultManager as vault::TimeGated>::locking_script(
+        &party_pub_key,
+        &custodian_pub_keys,
+        None,
+        custodian_quorum,
+        sequence,
+    );
+    match result {
+        Ok(script) => {
+            let script_bytes = script.to_bytes();
+            let mut output = Vec::with_capacity(script_bytes.len());
+            output.extend_from_slice(&script_bytes);
+            let buffer = ByteBuffer {
+                data: output.as_mut_ptr(),
+                len: output.len(),
+            };
+            std::mem::forget(output); // Prevent deallocation
+            buffer
+        }
+        Err(err) => error_buffer(err),
     }
 }
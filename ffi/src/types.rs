@@ -1,4 +1,4 @@
-use bitcoin::{hashes::Hash, Amount, OutPoint, ScriptBuf, TxOut, Txid};
+use bitcoin::{hashes::Hash, relative, Amount, OutPoint, ScriptBuf, TxOut, Txid};
 use std::slice;
 use vault::PreviousOutpoint;
 
@@ -64,6 +64,25 @@ impl From<&TxOutFFI> for TxOut {
     }
 }
 
+/// BIP-68 relative lock time, `typ` is 0 for blocks and 1 for 512-second intervals.
+#[repr(C)]
+pub struct RelativeLockTimeFFI {
+    pub typ: u8,
+    pub value: u16,
+}
+
+impl TryFrom<&RelativeLockTimeFFI> for relative::LockTime {
+    type Error = FFIError;
+
+    fn try_from(ffi: &RelativeLockTimeFFI) -> Result<Self, Self::Error> {
+        match ffi.typ {
+            0 => Ok(relative::LockTime::from_height(ffi.value)),
+            1 => Ok(relative::LockTime::from_512_second_intervals(ffi.value)),
+            typ => Err(FFIError::InvalidRelativeLockTimeType(typ)),
+        }
+    }
+}
+
 impl ScriptBufFFI {
     pub fn to_vec(&self) -> Vec<u8> {
         unsafe { slice::from_raw_parts(self.data, self.len).to_vec() }
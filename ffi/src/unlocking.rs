@@ -1,13 +1,15 @@
 use std::slice;
 
-use bitcoin::{PublicKey, TxOut};
+use bitcoin::{relative, PublicKey, ScriptBuf, TapSighashType, TxOut};
 use vault::{
-    CustodianOnly, CustodianOnlyUnlockingParams, PreviousOutpoint, VaultManager, HASH_SIZE,
+    BranchLayout, CustodianOnly, CustodianOnlyUnlockingParams, PreviousOutpoint,
+    TaprootInternalKey, TimeGated, TimeGatedInput, TimeGatedUnlockingParams,
+    TimeGatedUnlockingType, VaultManager, HASH_SIZE,
 };
 
 use crate::{
-    create_null_buffer, ByteBuffer, PoolingRedeemParams, PreviousOutpointFFI, PublicKeyFFI,
-    TxOutFFI,
+    create_null_buffer, error_buffer, ByteBuffer, FFIError, PoolingRedeemParams,
+    PreviousOutpointFFI, PublicKeyFFI, RelativeLockTimeFFI, TxOutFFI,
 };
 
 /// # Safety
@@ -32,12 +34,63 @@ pub unsafe extern "C" fn build_custodian_only(
     custodian_quorum: u8,
     rbf: bool,
     fee_rate: u64,
+) -> ByteBuffer {
+    build_custodian_only_with_sighash(
+        tag,
+        tag_len,
+        service_tag,
+        service_tag_len,
+        version,
+        network_kind,
+        inputs_ptr,
+        inputs_len,
+        outputs_ptr,
+        outputs_len,
+        custodian_pubkeys_ptr,
+        custodian_pubkeys_len,
+        custodian_quorum,
+        rbf,
+        fee_rate,
+        TapSighashType::Default as u8,
+    )
+}
+
+/// Same as [`build_custodian_only`], the inputs are signed with `sighash_type`. Kept as a
+/// separate symbol so that existing callers of `build_custodian_only` are not broken.
+///
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+#[no_mangle]
+pub unsafe extern "C" fn build_custodian_only_with_sighash(
+    tag: *const u8,
+    tag_len: usize,
+    service_tag: *const u8,
+    service_tag_len: usize,
+    version: u8,
+    network_kind: u8,
+
+    inputs_ptr: *const PreviousOutpointFFI,
+    inputs_len: usize,
+    outputs_ptr: *const TxOutFFI,
+    outputs_len: usize,
+    custodian_pubkeys_ptr: *const PublicKeyFFI,
+    custodian_pubkeys_len: usize,
+    custodian_quorum: u8,
+    rbf: bool,
+    fee_rate: u64,
+    sighash_type: u8,
 ) -> ByteBuffer {
     // Safety checks for null pointers
     if inputs_ptr.is_null() || outputs_ptr.is_null() || custodian_pubkeys_ptr.is_null() {
         return create_null_buffer();
     }
 
+    let Ok(sighash_type) = TapSighashType::from_consensus_u8(sighash_type) else {
+        return error_buffer(FFIError::InvalidSighashType(sighash_type));
+    };
+
     // Convert raw pointers to slices
     let tag = slice::from_raw_parts(tag, tag_len);
     let service_tag = slice::from_raw_parts(service_tag, service_tag_len);
@@ -46,17 +99,24 @@ pub unsafe extern "C" fn build_custodian_only(
     let outputs = slice::from_raw_parts(outputs_ptr, outputs_len);
     let custodian_pubkeys = slice::from_raw_parts(custodian_pubkeys_ptr, custodian_pubkeys_len);
 
-    let inputs: Vec<PreviousOutpoint> = inputs
+    let inputs = match inputs
         .iter()
-        .map(|input| input.try_into().unwrap())
-        .collect();
+        .map(|input| input.try_into())
+        .collect::<Result<Vec<PreviousOutpoint>, FFIError>>()
+    {
+        Ok(inputs) => inputs,
+        Err(err) => return error_buffer(err),
+    };
 
     let outputs: Vec<TxOut> = outputs.iter().map(|output| output.into()).collect();
 
-    let custodian_pubkeys: Vec<PublicKey> = custodian_pubkeys
+    let Ok(custodian_pubkeys) = custodian_pubkeys
         .iter()
-        .map(|key| PublicKey::from_slice(key.as_slice()).unwrap())
-        .collect();
+        .map(|key| PublicKey::from_slice(key.as_slice()))
+        .collect::<Result<Vec<PublicKey>, _>>()
+    else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
 
     // Create parameters for the unstaking function
     let params = CustodianOnlyUnlockingParams {
@@ -64,10 +124,17 @@ pub unsafe extern "C" fn build_custodian_only(
         outputs: outputs.to_vec(),
         custodian_pubkeys: custodian_pubkeys.to_vec(),
         custodian_quorum,
+        custodian_weights: None,
+        internal_key: TaprootInternalKey::Nums,
+        branch_layout: BranchLayout::Legacy,
         rbf,
         fee_rate,
+        key_origins: None,
+        sighash_type: Some(sighash_type),
         session_sequence: 0,
         custodian_group_uid: [0u8; HASH_SIZE],
+        signers: None,
+        subsets: None,
     };
 
     // Create a VaultManager instance
@@ -88,7 +155,7 @@ pub unsafe extern "C" fn build_custodian_only(
             std::mem::forget(output); // Prevent deallocation
             buffer
         }
-        Err(_) => create_null_buffer(),
+        Err(err) => error_buffer(err),
     }
 }
 
@@ -125,16 +192,27 @@ pub unsafe extern "C" fn build_pooling_redeem_tx(buffer: *const u8, len: usize)
             let vault_manager =
                 VaultManager::new(tag.to_vec(), service_tag.to_vec(), version, network_id);
 
+            let Ok(custodian_group_uid) = custodian_group_uid.try_into() else {
+                return error_buffer(FFIError::InvalidCustodianGroupUid);
+            };
+
             // Create parameters for the unstaking function
             let params = CustodianOnlyUnlockingParams {
                 inputs,
                 outputs,
                 custodian_pubkeys,
                 custodian_quorum,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
                 rbf,
                 fee_rate,
+                key_origins: None,
+                sighash_type: None,
                 session_sequence,
-                custodian_group_uid: custodian_group_uid.try_into().unwrap(),
+                custodian_group_uid,
+                signers: None,
+                subsets: None,
             };
             // Call the build_custodian_only function
             match <VaultManager as CustodianOnly>::build_unlocking_psbt(&vault_manager, &params) {
@@ -150,9 +228,133 @@ pub unsafe extern "C" fn build_pooling_redeem_tx(buffer: *const u8, len: usize)
                     std::mem::forget(output); // Prevent deallocation
                     buffer
                 }
-                Err(_) => create_null_buffer(),
+                Err(err) => error_buffer(err),
             }
         }
-        Err(_) => create_null_buffer(),
+        Err(err) => error_buffer(err),
+    }
+}
+
+/// # Safety
+///
+/// This function is unsafe because it uses raw pointers and assumes that the caller has
+/// provided valid pointers and lengths for the inputs and outputs.
+/// `sequences_ptr` holds the relative lock time of each input, `inputs_len` items.
+/// `unlocking_type` is 0 for the custodian only branch and 1 for the party time gated branch.
+#[no_mangle]
+pub unsafe extern "C" fn build_time_gated_unlocking(
+    tag: *const u8,
+    tag_len: usize,
+    service_tag: *const u8,
+    service_tag_len: usize,
+    version: u8,
+    network_kind: u8,
+
+    inputs_ptr: *const PreviousOutpointFFI,
+    sequences_ptr: *const RelativeLockTimeFFI,
+    inputs_len: usize,
+    outputs_ptr: *const TxOutFFI,
+    outputs_len: usize,
+    change_script_pubkey_ptr: *const u8,
+    change_script_pubkey_len: usize,
+    party_pubkey_ptr: *const PublicKeyFFI,
+    custodian_pubkeys_ptr: *const PublicKeyFFI,
+    custodian_pubkeys_len: usize,
+    custodian_quorum: u8,
+    fee_rate: u64,
+    unlocking_type: u8,
+) -> ByteBuffer {
+    // Safety checks for null pointers
+    if inputs_ptr.is_null()
+        || sequences_ptr.is_null()
+        || (outputs_ptr.is_null() && outputs_len > 0)
+        || change_script_pubkey_ptr.is_null()
+        || party_pubkey_ptr.is_null()
+        || custodian_pubkeys_ptr.is_null()
+    {
+        return create_null_buffer();
+    }
+
+    let typ = match unlocking_type {
+        0 => TimeGatedUnlockingType::CustodianOnly,
+        1 => TimeGatedUnlockingType::PartyTimeGated,
+        _ => return error_buffer(FFIError::InvalidUnlockingType(unlocking_type)),
+    };
+
+    // Convert raw pointers to slices
+    let tag = slice::from_raw_parts(tag, tag_len);
+    let service_tag = slice::from_raw_parts(service_tag, service_tag_len);
+    let inputs = slice::from_raw_parts(inputs_ptr, inputs_len);
+    let sequences = slice::from_raw_parts(sequences_ptr, inputs_len);
+    let outputs = if outputs_len > 0 {
+        slice::from_raw_parts(outputs_ptr, outputs_len)
+    } else {
+        &[]
+    };
+    let change_script_pubkey =
+        slice::from_raw_parts(change_script_pubkey_ptr, change_script_pubkey_len);
+    let custodian_pubkeys = slice::from_raw_parts(custodian_pubkeys_ptr, custodian_pubkeys_len);
+
+    let Ok(party_pubkey) = PublicKey::from_slice((*party_pubkey_ptr).as_slice()) else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
+
+    let inputs: Result<Vec<TimeGatedInput>, FFIError> = inputs
+        .iter()
+        .zip(sequences)
+        .map(|(input, sequence)| {
+            Ok(TimeGatedInput {
+                input: input.try_into()?,
+                sequence: relative::LockTime::try_from(sequence)?,
+            })
+        })
+        .collect();
+    let inputs = match inputs {
+        Ok(inputs) => inputs,
+        Err(err) => return error_buffer(err),
+    };
+
+    let outputs: Vec<TxOut> = outputs.iter().map(|output| output.into()).collect();
+
+    let Ok(custodian_pubkeys) = custodian_pubkeys
+        .iter()
+        .map(|key| PublicKey::from_slice(key.as_slice()))
+        .collect::<Result<Vec<PublicKey>, _>>()
+    else {
+        return error_buffer(FFIError::InvalidPublicKey);
+    };
+
+    let params = TimeGatedUnlockingParams {
+        inputs,
+        outputs,
+        change_script_pubkey: ScriptBuf::from_bytes(change_script_pubkey.to_vec()),
+        party_pubkey,
+        custodian_pubkeys,
+        custodian_quorum,
+        custodian_weights: None,
+        branch_layout: BranchLayout::Legacy,
+        fee_rate,
+        key_origins: None,
+        sighash_type: None,
+        typ,
+    };
+
+    let vault_manager =
+        VaultManager::new(tag.to_vec(), service_tag.to_vec(), version, network_kind);
+
+    match <VaultManager as TimeGated>::build_unlocking_psbt(&vault_manager, &params) {
+        Ok(psbt) => {
+            // Serialize the PSBT and return it as a ByteBuffer
+            let psbt_bytes = psbt.serialize();
+            let mut output = Vec::with_capacity(psbt_bytes.len());
+            output.extend_from_slice(&psbt_bytes);
+            let buffer = ByteBuffer {
+                data: output.as_mut_ptr(),
+                len: output.len(),
+            };
+            std::mem::forget(output); // Prevent deallocation
+            buffer
+        }
+        Err(err) => error_buffer(err),
     }
 }
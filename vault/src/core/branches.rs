@@ -1,20 +1,65 @@
 use bitcoin::{
-    opcodes::all::{
-        OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CSV, OP_DROP, OP_GREATERTHANOREQUAL,
+    absolute,
+    hashes::{sha256, Hash},
+    opcodes::{
+        all::{
+            OP_ADD, OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_CLTV, OP_CSV, OP_DROP,
+            OP_ELSE, OP_ENDIF, OP_EQUALVERIFY, OP_GREATERTHANOREQUAL, OP_IF, OP_NUMEQUAL,
+            OP_SHA256, OP_SIZE, OP_SWAP, OP_VERIFY,
+        },
+        Opcode,
     },
-    script, ScriptBuf, XOnlyPublicKey,
+    script::{self, Instruction},
+    Script, ScriptBuf, XOnlyPublicKey,
 };
 
 use super::{
-    BuildCustodianAndPartyBranch, BuildCustodianOnlyBranch, BuildPartyWithSequenceVerification,
-    BuildTwoPartyBranch, CoreError,
+    aggregate_custodian_pubkeys, validate_custodians, validate_tapscript_witness,
+    BuildCustodianAndPartyBranch, BuildCustodianOnlyBranch, BuildCustodianSubsetBranch,
+    BuildCustodianWithLockTimeBranch, BuildHashlockBranch, BuildPartyWithLockTimeVerification,
+    BuildPartyWithSequenceVerification, BuildTwoPartyBranch, CoreError, MAX_SCHNORR_SIGNATURE_SIZE,
+    PREIMAGE_SIZE,
 };
 
 pub type TwoPartyBranch = ScriptBuf;
 pub type CustodianAndPartyBranch = ScriptBuf;
 pub type CustodianOnlyBranch = ScriptBuf;
+pub type CustodianWithLockTimeBranch = ScriptBuf;
 pub type CustodianScript = ScriptBuf;
 pub type PartyWithSequenceVerification = ScriptBuf;
+pub type PartyWithLockTimeVerification = ScriptBuf;
+pub type HashlockBranch = ScriptBuf;
+pub type CustodianSubsetBranch = ScriptBuf;
+
+/// Encoding of the custodian and sequence leaves.
+///
+/// The legacy leaves check the custodian quorum with `OP_GREATERTHANOREQUAL` and drop the lock
+/// times. The miniscript leaves are `multi_a`, ending with `OP_NUMEQUAL`, and `v:after` /
+/// `v:older`, so the vault has a `tr()` descriptor. A miniscript custodian leaf takes exactly
+/// `custodian_quorum` signatures. Weighted custodian leaves only have the legacy layout.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BranchLayout {
+    #[default]
+    Legacy,
+    Miniscript,
+}
+
+impl BranchLayout {
+    fn quorum_opcode(&self) -> Opcode {
+        match self {
+            BranchLayout::Legacy => OP_GREATERTHANOREQUAL,
+            BranchLayout::Miniscript => OP_NUMEQUAL,
+        }
+    }
+
+    fn lock_opcode(&self) -> Opcode {
+        match self {
+            BranchLayout::Legacy => OP_DROP,
+            BranchLayout::Miniscript => OP_VERIFY,
+        }
+    }
+}
+
 pub struct CustodianScriptBuilder;
 
 impl CustodianScriptBuilder {
@@ -23,6 +68,24 @@ impl CustodianScriptBuilder {
         custodian_quorum: u8,
         initial_key: Option<&XOnlyPublicKey>,
     ) -> Result<CustodianScript, CoreError> {
+        Self::build_with_layout(
+            custodian_pub_keys,
+            custodian_quorum,
+            initial_key,
+            BranchLayout::Legacy,
+        )
+    }
+
+    /// Same as [`Self::build`], the quorum check follows `layout`: `multi_a(q,K...)`, or
+    /// `and_v(v:pk(P),multi_a(q,K...))` with an initial key, for [`BranchLayout::Miniscript`].
+    pub fn build_with_layout(
+        custodian_pub_keys: &[XOnlyPublicKey],
+        custodian_quorum: u8,
+        initial_key: Option<&XOnlyPublicKey>,
+        layout: BranchLayout,
+    ) -> Result<CustodianScript, CoreError> {
+        validate_custodians(custodian_pub_keys, custodian_quorum, None)?;
+
         let mut builder = script::Builder::new();
 
         // Initial key check
@@ -36,13 +99,6 @@ impl CustodianScriptBuilder {
         let mut sorted_pks = custodian_pub_keys.to_owned();
         sorted_pks.sort();
 
-        // Check for duplicates
-        for i in 0..sorted_pks.len() - 1 {
-            if sorted_pks[i] == sorted_pks[i + 1] {
-                return Err(CoreError::DuplicateCustodianKeys);
-            }
-        }
-
         // Add custodian keys to the script
         builder = builder.push_x_only_key(&sorted_pks[0]);
         builder = builder.push_opcode(OP_CHECKSIG);
@@ -52,15 +108,109 @@ impl CustodianScriptBuilder {
             builder = builder.push_opcode(OP_CHECKSIGADD);
         }
 
+        // Add quorum check
+        builder = builder
+            .push_int(custodian_quorum as i64)
+            .push_opcode(layout.quorum_opcode());
+
+        validated_custodian_script(
+            builder.into_script(),
+            custodian_pub_keys.len() + initial_key.is_some() as usize,
+        )
+    }
+
+    /// Same as [`Self::build`], but each custodian signature counts for the custodian's weight
+    /// and `custodian_quorum` is the total weight required.
+    ///
+    /// ```text
+    /// <k_0> OP_CHECKSIG OP_IF <w_0> OP_ELSE OP_0 OP_ENDIF
+    /// OP_SWAP <k_1> OP_CHECKSIG OP_IF <w_1> OP_ADD OP_ENDIF
+    /// ...
+    /// <quorum> OP_GREATERTHANOREQUAL
+    /// ```
+    pub fn build_weighted(
+        custodian_pub_keys: &[XOnlyPublicKey],
+        custodian_weights: &[u8],
+        custodian_quorum: u8,
+        initial_key: Option<&XOnlyPublicKey>,
+    ) -> Result<CustodianScript, CoreError> {
+        validate_custodians(
+            custodian_pub_keys,
+            custodian_quorum,
+            Some(custodian_weights),
+        )?;
+
+        let mut builder = script::Builder::new();
+
+        // Initial key check
+        if let Some(initial_key) = initial_key {
+            builder = builder
+                .push_x_only_key(initial_key)
+                .push_opcode(OP_CHECKSIGVERIFY);
+        }
+
+        // Sort custodian public keys, keeping each weight with its key
+        let mut sorted_pks: Vec<(XOnlyPublicKey, u8)> = custodian_pub_keys
+            .iter()
+            .copied()
+            .zip(custodian_weights.iter().copied())
+            .collect();
+        sorted_pks.sort();
+
+        // The first signature starts the sum
+        let (first_pk, first_weight) = sorted_pks[0];
+        builder = builder
+            .push_x_only_key(&first_pk)
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_IF)
+            .push_int(first_weight as i64)
+            .push_opcode(OP_ELSE)
+            .push_int(0)
+            .push_opcode(OP_ENDIF);
+
+        // Each next signature is below the sum
+        for (pk, weight) in sorted_pks.iter().skip(1) {
+            builder = builder
+                .push_opcode(OP_SWAP)
+                .push_x_only_key(pk)
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_IF)
+                .push_int(*weight as i64)
+                .push_opcode(OP_ADD)
+                .push_opcode(OP_ENDIF);
+        }
+
         // Add quorum check
         builder = builder
             .push_int(custodian_quorum as i64)
             .push_opcode(OP_GREATERTHANOREQUAL);
 
-        Ok(builder.into_script())
+        validated_custodian_script(
+            builder.into_script(),
+            custodian_pub_keys.len() + initial_key.is_some() as usize,
+        )
     }
 }
 
+/// `OP_VERIFY` consumes the lock time, which therefore cannot be 0.
+fn validate_verified_lock(lock: impl Into<i64>) -> Result<(), CoreError> {
+    if lock.into() == 0 {
+        return Err(CoreError::InvalidLockTime(
+            "lock time must be greater than 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// One signature per custodian, plus the initial key signature, must fit a standard witness.
+fn validated_custodian_script(
+    script: CustodianScript,
+    n_signatures: usize,
+) -> Result<CustodianScript, CoreError> {
+    validate_tapscript_witness(&vec![MAX_SCHNORR_SIGNATURE_SIZE; n_signatures], &script)?;
+    Ok(script)
+}
+
 impl BuildTwoPartyBranch for TwoPartyBranch {
     fn build(x: &XOnlyPublicKey, y: &XOnlyPublicKey) -> Result<Self, CoreError> {
         Ok(script::Builder::new()
@@ -77,8 +227,14 @@ impl BuildCustodianAndPartyBranch for CustodianAndPartyBranch {
         x: &XOnlyPublicKey,
         custodian_pub_keys: &[XOnlyPublicKey],
         custodian_quorum: u8,
+        layout: BranchLayout,
     ) -> Result<Self, CoreError> {
-        let script = CustodianScriptBuilder::build(custodian_pub_keys, custodian_quorum, Some(x))?;
+        let script = CustodianScriptBuilder::build_with_layout(
+            custodian_pub_keys,
+            custodian_quorum,
+            Some(x),
+            layout,
+        )?;
         Ok(script)
     }
 }
@@ -87,20 +243,518 @@ impl BuildCustodianOnlyBranch for CustodianOnlyBranch {
     fn build(
         custodian_pub_keys: &[XOnlyPublicKey],
         custodian_quorum: u8,
+        layout: BranchLayout,
     ) -> Result<Self, CoreError> {
-        let script = CustodianScriptBuilder::build(custodian_pub_keys, custodian_quorum, None)?;
+        let script = CustodianScriptBuilder::build_with_layout(
+            custodian_pub_keys,
+            custodian_quorum,
+            None,
+            layout,
+        )?;
         Ok(script)
     }
 }
 
+impl BuildCustodianWithLockTimeBranch for CustodianWithLockTimeBranch {
+    /// `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_DROP` followed by the custodian quorum check,
+    /// `OP_VERIFY` instead of `OP_DROP` for [`BranchLayout::Miniscript`].
+    fn build(
+        custodian_pub_keys: &[XOnlyPublicKey],
+        custodian_quorum: u8,
+        lock_time: absolute::LockTime,
+        layout: BranchLayout,
+    ) -> Result<Self, CoreError> {
+        if layout == BranchLayout::Miniscript {
+            validate_verified_lock(lock_time.to_consensus_u32())?;
+        }
+
+        let custodian_script = CustodianScriptBuilder::build_with_layout(
+            custodian_pub_keys,
+            custodian_quorum,
+            None,
+            layout,
+        )?;
+
+        let mut script = script::Builder::new()
+            .push_lock_time(lock_time)
+            .push_opcode(OP_CLTV)
+            .push_opcode(layout.lock_opcode())
+            .into_script()
+            .into_bytes();
+        script.extend_from_slice(custodian_script.as_bytes());
+
+        Ok(ScriptBuf::from_bytes(script))
+    }
+}
+
+impl BuildCustodianSubsetBranch for CustodianSubsetBranch {
+    /// Every custodian of the subset signs:
+    /// `<k_0> OP_CHECKSIGVERIFY ... <k_n-1> OP_CHECKSIG` with the keys sorted,
+    /// or `<musig(k_0, ..., k_n-1)> OP_CHECKSIG` when aggregated.
+    fn build(custodian_pub_keys: &[XOnlyPublicKey], aggregate: bool) -> Result<Self, CoreError> {
+        if aggregate {
+            let aggregated_key = aggregate_custodian_pubkeys(custodian_pub_keys)?;
+            return Ok(script::Builder::new()
+                .push_x_only_key(&aggregated_key)
+                .push_opcode(OP_CHECKSIG)
+                .into_script());
+        }
+
+        let mut sorted_pks = custodian_pub_keys.to_owned();
+        sorted_pks.sort();
+        sorted_pks.dedup();
+        if sorted_pks.len() != custodian_pub_keys.len() {
+            return Err(CoreError::DuplicateCustodianKeys);
+        }
+
+        let (last, rest) = sorted_pks
+            .split_last()
+            .ok_or(CoreError::InvalidCustodianSubset)?;
+
+        let mut builder = script::Builder::new();
+        for pk in rest {
+            builder = builder.push_x_only_key(pk).push_opcode(OP_CHECKSIGVERIFY);
+        }
+
+        Ok(builder
+            .push_x_only_key(last)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+}
+
 impl BuildPartyWithSequenceVerification for PartyWithSequenceVerification {
-    fn build(x: &XOnlyPublicKey, sequence: i64) -> Result<Self, CoreError> {
+    fn build(x: &XOnlyPublicKey, sequence: i64, layout: BranchLayout) -> Result<Self, CoreError> {
+        if layout == BranchLayout::Miniscript {
+            validate_verified_lock(sequence)?;
+        }
+
         Ok(script::Builder::new()
             .push_int(sequence)
             .push_opcode(OP_CSV)
-            .push_opcode(OP_DROP)
+            .push_opcode(layout.lock_opcode())
+            .push_x_only_key(x)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+}
+
+impl BuildPartyWithLockTimeVerification for PartyWithLockTimeVerification {
+    fn build(x: &XOnlyPublicKey, lock_time: absolute::LockTime) -> Result<Self, CoreError> {
+        validate_verified_lock(lock_time.to_consensus_u32())?;
+        Ok(script::Builder::new()
+            .push_lock_time(lock_time)
+            .push_opcode(OP_CLTV)
+            .push_opcode(OP_VERIFY)
+            .push_x_only_key(x)
+            .push_opcode(OP_CHECKSIG)
+            .into_script())
+    }
+}
+
+impl BuildHashlockBranch for HashlockBranch {
+    fn build(payment_hash: &sha256::Hash, x: &XOnlyPublicKey) -> Result<Self, CoreError> {
+        // The preimage size is fixed so that it cannot be made too large for the other chain
+        Ok(script::Builder::new()
+            .push_opcode(OP_SIZE)
+            .push_int(PREIMAGE_SIZE as i64)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_opcode(OP_SHA256)
+            .push_slice(payment_hash.as_byte_array())
+            .push_opcode(OP_EQUALVERIFY)
             .push_x_only_key(x)
             .push_opcode(OP_CHECKSIG)
             .into_script())
     }
 }
+
+/// A tapscript leaf recognized as one of the vault branch templates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultBranch {
+    TwoParty {
+        x: XOnlyPublicKey,
+        y: XOnlyPublicKey,
+    },
+    CustodianAndParty {
+        party: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        layout: BranchLayout,
+    },
+    CustodianOnly {
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        layout: BranchLayout,
+    },
+    CustodianWithLockTime {
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        lock_time: absolute::LockTime,
+        layout: BranchLayout,
+    },
+    WeightedCustodianAndParty {
+        party: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_weights: Vec<u8>,
+        custodian_quorum: u8,
+    },
+    WeightedCustodianOnly {
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_weights: Vec<u8>,
+        custodian_quorum: u8,
+    },
+    PartyWithSequenceVerification {
+        party: XOnlyPublicKey,
+        sequence: i64,
+        layout: BranchLayout,
+    },
+    PartyWithLockTimeVerification {
+        party: XOnlyPublicKey,
+        lock_time: absolute::LockTime,
+    },
+    Hashlock {
+        payment_hash: sha256::Hash,
+        party: XOnlyPublicKey,
+    },
+}
+
+impl VaultBranch {
+    /// Recognizes a leaf built by [`CustodianScriptBuilder`] or one of the `Build*Branch` impls.
+    ///
+    /// Only the canonical encoding is accepted, i.e. the script must be byte for byte what the
+    /// builder produces for the extracted keys, quorum and lock.
+    pub fn from_script(script: &Script) -> Result<Self, CoreError> {
+        let instructions = script
+            .instructions()
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| CoreError::UnrecognizedBranchScript)?;
+
+        let branch = match instructions.as_slice() {
+            [x, checksigverify, y, checksig]
+                if is_op(checksigverify, OP_CHECKSIGVERIFY) && is_op(checksig, OP_CHECKSIG) =>
+            {
+                VaultBranch::TwoParty {
+                    x: read_key(x)?,
+                    y: read_key(y)?,
+                }
+            }
+            [sequence, csv, lock_op, party, checksig]
+                if is_op(csv, OP_CSV) && is_op(checksig, OP_CHECKSIG) =>
+            {
+                VaultBranch::PartyWithSequenceVerification {
+                    party: read_key(party)?,
+                    sequence: read_num(sequence)?,
+                    layout: read_lock_layout(lock_op)?,
+                }
+            }
+            [lock_time, cltv, verify, party, checksig]
+                if is_op(cltv, OP_CLTV)
+                    && is_op(verify, OP_VERIFY)
+                    && is_op(checksig, OP_CHECKSIG) =>
+            {
+                VaultBranch::PartyWithLockTimeVerification {
+                    party: read_key(party)?,
+                    lock_time: read_lock_time(lock_time)?,
+                }
+            }
+            [size, preimage_size, size_equalverify, sha256, payment_hash, equalverify, party, checksig]
+                if is_op(size, OP_SIZE)
+                    && read_num(preimage_size)? == PREIMAGE_SIZE as i64
+                    && is_op(size_equalverify, OP_EQUALVERIFY)
+                    && is_op(sha256, OP_SHA256)
+                    && is_op(equalverify, OP_EQUALVERIFY)
+                    && is_op(checksig, OP_CHECKSIG) =>
+            {
+                let payment_hash = payment_hash
+                    .push_bytes()
+                    .and_then(|bytes| sha256::Hash::from_slice(bytes.as_bytes()).ok())
+                    .ok_or(CoreError::UnrecognizedBranchScript)?;
+                VaultBranch::Hashlock {
+                    payment_hash,
+                    party: read_key(party)?,
+                }
+            }
+            [lock_time, cltv, lock_op, custodians @ ..] if is_op(cltv, OP_CLTV) => {
+                let (custodian_pubkeys, custodian_quorum, layout) = read_custodians(custodians)?;
+                if read_lock_layout(lock_op)? != layout {
+                    return Err(CoreError::UnrecognizedBranchScript);
+                }
+                VaultBranch::CustodianWithLockTime {
+                    custodian_pubkeys,
+                    custodian_quorum,
+                    lock_time: read_lock_time(lock_time)?,
+                    layout,
+                }
+            }
+            [party, checksigverify, custodians @ ..]
+                if is_op(checksigverify, OP_CHECKSIGVERIFY) =>
+            {
+                let party = read_key(party)?;
+                match read_weighted_custodians(custodians) {
+                    Some((custodian_pubkeys, custodian_weights, custodian_quorum)) => {
+                        VaultBranch::WeightedCustodianAndParty {
+                            party,
+                            custodian_pubkeys,
+                            custodian_weights,
+                            custodian_quorum,
+                        }
+                    }
+                    None => {
+                        let (custodian_pubkeys, custodian_quorum, layout) =
+                            read_custodians(custodians)?;
+                        VaultBranch::CustodianAndParty {
+                            party,
+                            custodian_pubkeys,
+                            custodian_quorum,
+                            layout,
+                        }
+                    }
+                }
+            }
+            custodians => match read_weighted_custodians(custodians) {
+                Some((custodian_pubkeys, custodian_weights, custodian_quorum)) => {
+                    VaultBranch::WeightedCustodianOnly {
+                        custodian_pubkeys,
+                        custodian_weights,
+                        custodian_quorum,
+                    }
+                }
+                None => {
+                    let (custodian_pubkeys, custodian_quorum, layout) =
+                        read_custodians(custodians)?;
+                    VaultBranch::CustodianOnly {
+                        custodian_pubkeys,
+                        custodian_quorum,
+                        layout,
+                    }
+                }
+            },
+        };
+
+        if branch.build_script()?.as_script() != script {
+            return Err(CoreError::NonCanonicalBranchScript);
+        }
+
+        Ok(branch)
+    }
+
+    pub fn build_script(&self) -> Result<ScriptBuf, CoreError> {
+        match self {
+            VaultBranch::TwoParty { x, y } => <TwoPartyBranch as BuildTwoPartyBranch>::build(x, y),
+            VaultBranch::CustodianAndParty {
+                party,
+                custodian_pubkeys,
+                custodian_quorum,
+                layout,
+            } => <CustodianAndPartyBranch as BuildCustodianAndPartyBranch>::build(
+                party,
+                custodian_pubkeys,
+                *custodian_quorum,
+                *layout,
+            ),
+            VaultBranch::CustodianOnly {
+                custodian_pubkeys,
+                custodian_quorum,
+                layout,
+            } => <CustodianOnlyBranch as BuildCustodianOnlyBranch>::build(
+                custodian_pubkeys,
+                *custodian_quorum,
+                *layout,
+            ),
+            VaultBranch::CustodianWithLockTime {
+                custodian_pubkeys,
+                custodian_quorum,
+                lock_time,
+                layout,
+            } => <CustodianWithLockTimeBranch as BuildCustodianWithLockTimeBranch>::build(
+                custodian_pubkeys,
+                *custodian_quorum,
+                *lock_time,
+                *layout,
+            ),
+            VaultBranch::WeightedCustodianAndParty {
+                party,
+                custodian_pubkeys,
+                custodian_weights,
+                custodian_quorum,
+            } => CustodianScriptBuilder::build_weighted(
+                custodian_pubkeys,
+                custodian_weights,
+                *custodian_quorum,
+                Some(party),
+            ),
+            VaultBranch::WeightedCustodianOnly {
+                custodian_pubkeys,
+                custodian_weights,
+                custodian_quorum,
+            } => CustodianScriptBuilder::build_weighted(
+                custodian_pubkeys,
+                custodian_weights,
+                *custodian_quorum,
+                None,
+            ),
+            VaultBranch::PartyWithSequenceVerification {
+                party,
+                sequence,
+                layout,
+            } => <PartyWithSequenceVerification as BuildPartyWithSequenceVerification>::build(
+                party, *sequence, *layout,
+            ),
+            VaultBranch::PartyWithLockTimeVerification { party, lock_time } => {
+                <PartyWithLockTimeVerification as BuildPartyWithLockTimeVerification>::build(
+                    party, *lock_time,
+                )
+            }
+            VaultBranch::Hashlock {
+                payment_hash,
+                party,
+            } => <HashlockBranch as BuildHashlockBranch>::build(payment_hash, party),
+        }
+    }
+
+    /// The custodians and quorum of a [`BranchLayout::Miniscript`] custodian leaf, which is only
+    /// satisfied by exactly `custodian_quorum` custodian signatures.
+    pub fn exact_custodian_quorum(&self) -> Option<(&[XOnlyPublicKey], u8)> {
+        match self {
+            VaultBranch::CustodianAndParty {
+                custodian_pubkeys,
+                custodian_quorum,
+                layout: BranchLayout::Miniscript,
+                ..
+            }
+            | VaultBranch::CustodianOnly {
+                custodian_pubkeys,
+                custodian_quorum,
+                layout: BranchLayout::Miniscript,
+            }
+            | VaultBranch::CustodianWithLockTime {
+                custodian_pubkeys,
+                custodian_quorum,
+                layout: BranchLayout::Miniscript,
+                ..
+            } => Some((custodian_pubkeys, *custodian_quorum)),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&Script> for VaultBranch {
+    type Error = CoreError;
+
+    fn try_from(script: &Script) -> Result<Self, Self::Error> {
+        Self::from_script(script)
+    }
+}
+
+fn is_op(instruction: &Instruction, opcode: Opcode) -> bool {
+    instruction.opcode() == Some(opcode)
+}
+
+fn read_key(instruction: &Instruction) -> Result<XOnlyPublicKey, CoreError> {
+    instruction
+        .push_bytes()
+        .and_then(|bytes| XOnlyPublicKey::from_slice(bytes.as_bytes()).ok())
+        .ok_or(CoreError::UnrecognizedBranchScript)
+}
+
+fn read_num(instruction: &Instruction) -> Result<i64, CoreError> {
+    instruction
+        .script_num()
+        .ok_or(CoreError::UnrecognizedBranchScript)
+}
+
+fn read_lock_time(instruction: &Instruction) -> Result<absolute::LockTime, CoreError> {
+    let lock_time =
+        u32::try_from(read_num(instruction)?).map_err(|_| CoreError::UnrecognizedBranchScript)?;
+    Ok(absolute::LockTime::from_consensus(lock_time))
+}
+
+/// The layout of the opcode following `OP_CHECKSEQUENCEVERIFY` or `OP_CHECKLOCKTIMEVERIFY`.
+fn read_lock_layout(instruction: &Instruction) -> Result<BranchLayout, CoreError> {
+    match instruction.opcode() {
+        Some(OP_DROP) => Ok(BranchLayout::Legacy),
+        Some(OP_VERIFY) => Ok(BranchLayout::Miniscript),
+        _ => Err(CoreError::UnrecognizedBranchScript),
+    }
+}
+
+/// Reads `<k_0> OP_CHECKSIG (<k_i> OP_CHECKSIGADD)* <quorum> OP_GREATERTHANOREQUAL`, or
+/// `OP_NUMEQUAL` for [`BranchLayout::Miniscript`].
+fn read_custodians(
+    instructions: &[Instruction],
+) -> Result<(Vec<XOnlyPublicKey>, u8, BranchLayout), CoreError> {
+    let (first, rest) = match instructions {
+        [first, checksig, rest @ ..] if is_op(checksig, OP_CHECKSIG) => (first, rest),
+        _ => return Err(CoreError::UnrecognizedBranchScript),
+    };
+    let (keys, quorum, layout) = match rest {
+        [keys @ .., quorum, gte] if is_op(gte, OP_GREATERTHANOREQUAL) => {
+            (keys, quorum, BranchLayout::Legacy)
+        }
+        [keys @ .., quorum, numequal] if is_op(numequal, OP_NUMEQUAL) => {
+            (keys, quorum, BranchLayout::Miniscript)
+        }
+        _ => return Err(CoreError::UnrecognizedBranchScript),
+    };
+
+    let mut custodian_pubkeys = vec![read_key(first)?];
+    for pair in keys.chunks(2) {
+        match pair {
+            [key, checksigadd] if is_op(checksigadd, OP_CHECKSIGADD) => {
+                custodian_pubkeys.push(read_key(key)?)
+            }
+            _ => return Err(CoreError::UnrecognizedBranchScript),
+        }
+    }
+
+    let quorum =
+        u8::try_from(read_num(quorum)?).map_err(|_| CoreError::UnrecognizedBranchScript)?;
+
+    Ok((custodian_pubkeys, quorum, layout))
+}
+
+/// Reads the layout of [`CustodianScriptBuilder::build_weighted`].
+fn read_weighted_custodians(
+    instructions: &[Instruction],
+) -> Option<(Vec<XOnlyPublicKey>, Vec<u8>, u8)> {
+    let (first, rest) = match instructions {
+        [key, checksig, op_if, weight, op_else, zero, op_endif, rest @ ..]
+            if is_op(checksig, OP_CHECKSIG)
+                && is_op(op_if, OP_IF)
+                && is_op(op_else, OP_ELSE)
+                && zero.script_num() == Some(0)
+                && is_op(op_endif, OP_ENDIF) =>
+        {
+            ((key, weight), rest)
+        }
+        _ => return None,
+    };
+    let (next, quorum) = match rest {
+        [next @ .., quorum, gte] if is_op(gte, OP_GREATERTHANOREQUAL) => (next, quorum),
+        _ => return None,
+    };
+
+    let read_weight = |weight: &Instruction| u8::try_from(weight.script_num()?).ok();
+
+    let mut custodian_pubkeys = vec![read_key(first.0).ok()?];
+    let mut custodian_weights = vec![read_weight(first.1)?];
+    for chunk in next.chunks(7) {
+        match chunk {
+            [swap, key, checksig, op_if, weight, add, op_endif]
+                if is_op(swap, OP_SWAP)
+                    && is_op(checksig, OP_CHECKSIG)
+                    && is_op(op_if, OP_IF)
+                    && is_op(add, OP_ADD)
+                    && is_op(op_endif, OP_ENDIF) =>
+            {
+                custodian_pubkeys.push(read_key(key).ok()?);
+                custodian_weights.push(read_weight(weight)?);
+            }
+            _ => return None,
+        }
+    }
+
+    let quorum = u8::try_from(quorum.script_num()?).ok()?;
+
+    Some((custodian_pubkeys, custodian_weights, quorum))
+}
@@ -0,0 +1,142 @@
+use super::MAX_BNB_TRIES;
+
+/// Branch-and-bound search for a set of coins whose effective values land in
+/// `[target, target + cost_of_change]`, so the transaction needs no change output.
+///
+/// Among the matches found within [`MAX_BNB_TRIES`] branches the one with the least excess
+/// is returned, as indices into `effective_values`.
+pub fn select_coins_bnb(
+    effective_values: &[u64],
+    target: u64,
+    cost_of_change: u64,
+) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..effective_values.len()).collect();
+    order.sort_by(|a, b| effective_values[*b].cmp(&effective_values[*a]));
+    let values: Vec<u64> = order.iter().map(|i| effective_values[*i]).collect();
+
+    // remaining[i] is the sum of the values from i on, the most a branch can still add
+    let mut remaining = vec![0u64; values.len() + 1];
+    for i in (0..values.len()).rev() {
+        remaining[i] = remaining[i + 1] + values[i];
+    }
+    if remaining[0] < target {
+        return None;
+    }
+
+    let mut search = BnbSearch {
+        values: &values,
+        remaining: &remaining,
+        target,
+        upper_bound: target.saturating_add(cost_of_change),
+        tries: 0,
+        selected: vec![],
+        best: None,
+    };
+    search.explore(0, 0);
+
+    search
+        .best
+        .map(|(_, selected)| selected.into_iter().map(|i| order[i]).collect())
+}
+
+struct BnbSearch<'a> {
+    /// Sorted in descending order
+    values: &'a [u64],
+    remaining: &'a [u64],
+    target: u64,
+    upper_bound: u64,
+    tries: usize,
+    selected: Vec<usize>,
+    /// Excess and selection of the best match so far
+    best: Option<(u64, Vec<usize>)>,
+}
+
+impl BnbSearch<'_> {
+    fn explore(&mut self, index: usize, total: u64) {
+        self.tries += 1;
+        if self.tries > MAX_BNB_TRIES || self.best.as_ref().is_some_and(|(excess, _)| *excess == 0)
+        {
+            return;
+        }
+
+        if total > self.upper_bound {
+            return;
+        }
+        if total >= self.target {
+            // Adding coins only increases the excess
+            let excess = total - self.target;
+            if self.best.as_ref().is_none_or(|(best, _)| excess < *best) {
+                self.best = Some((excess, self.selected.clone()));
+            }
+            return;
+        }
+        if index == self.values.len() || total + self.remaining[index] < self.target {
+            return;
+        }
+
+        self.selected.push(index);
+        self.explore(index + 1, total + self.values[index]);
+        self.selected.pop();
+
+        // Omitting a coin then including an equal one leads to the same totals
+        let mut next = index + 1;
+        while next < self.values.len() && self.values[next] == self.values[index] {
+            next += 1;
+        }
+        self.explore(next, total);
+    }
+}
+
+/// Knapsack fallback, a deterministic take on the selection of Bitcoin Core: an exact match,
+/// else the closest subset of the coins smaller than `target`, unless the smallest coin larger
+/// than `target` overshoots less.
+///
+/// Returns indices into `values`.
+pub fn select_coins_knapsack(values: &[u64], target: u64) -> Option<Vec<usize>> {
+    if let Some(index) = values.iter().position(|value| *value == target) {
+        return Some(vec![index]);
+    }
+
+    let mut smaller: Vec<usize> = (0..values.len())
+        .filter(|i| values[*i] < target && values[*i] > 0)
+        .collect();
+    smaller.sort_by(|a, b| values[*b].cmp(&values[*a]));
+    let lowest_larger = (0..values.len())
+        .filter(|i| values[*i] > target)
+        .min_by_key(|i| values[*i]);
+
+    let total_smaller: u64 = smaller.iter().map(|i| values[*i]).sum();
+    if total_smaller == target {
+        return Some(smaller);
+    }
+    if total_smaller < target {
+        return lowest_larger.map(|index| vec![index]);
+    }
+
+    // Walk the coins from each starting point, keeping the smallest total reaching the target
+    let mut best: Option<(u64, Vec<usize>)> = None;
+    for start in 0..smaller.len() {
+        let mut total = 0;
+        let mut selected = vec![];
+        for &index in smaller[start..].iter().chain(&smaller[..start]) {
+            total += values[index];
+            selected.push(index);
+            if total >= target {
+                if best.as_ref().is_none_or(|(best, _)| total < *best) {
+                    best = Some((total, selected.clone()));
+                }
+                total -= values[index];
+                selected.pop();
+            }
+        }
+        if best.as_ref().is_some_and(|(best, _)| *best == target) {
+            break;
+        }
+    }
+
+    match (best, lowest_larger) {
+        (Some((total, _)), Some(index)) if values[index] <= total => Some(vec![index]),
+        (Some((_, selected)), _) => Some(selected),
+        (None, lowest_larger) => lowest_larger.map(|index| vec![index]),
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::BTreeMap, fmt};
+
+use bitcoin::{
+    bip32::Xpub, ecdsa, psbt::Input, secp256k1, sighash::SighashCache, taproot, Psbt, PublicKey,
+    TapLeafHash, Transaction, XOnlyPublicKey,
+};
+
+use super::{get_global_secp, verify_tap_script_sig, CoreError, TapScriptSig, Utils};
+
+/// A field set to different values by two PSBTs being combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsbtConflict {
+    TapScriptSig {
+        input: usize,
+        key: XOnlyPublicKey,
+        leaf_hash: TapLeafHash,
+    },
+    TapKeySig {
+        input: usize,
+    },
+    PartialSig {
+        input: usize,
+        key: PublicKey,
+    },
+    TapKeyOrigin {
+        input: usize,
+        key: XOnlyPublicKey,
+    },
+    Bip32Derivation {
+        input: usize,
+        key: secp256k1::PublicKey,
+    },
+    GlobalXpub(Xpub),
+}
+
+impl fmt::Display for PsbtConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PsbtConflict::TapScriptSig {
+                input,
+                key,
+                leaf_hash,
+            } => write!(
+                f,
+                "input {}: tap script sig of {} for leaf {}",
+                input, key, leaf_hash
+            ),
+            PsbtConflict::TapKeySig { input } => write!(f, "input {}: tap key sig", input),
+            PsbtConflict::PartialSig { input, key } => {
+                write!(f, "input {}: partial sig of {}", input, key)
+            }
+            PsbtConflict::TapKeyOrigin { input, key } => {
+                write!(f, "input {}: tap key origin of {}", input, key)
+            }
+            PsbtConflict::Bip32Derivation { input, key } => {
+                write!(f, "input {}: bip32 derivation of {}", input, key)
+            }
+            PsbtConflict::GlobalXpub(xpub) => write!(f, "global xpub {}", xpub),
+        }
+    }
+}
+
+/// BIP-174 combiner: merges the signatures and key origins of `others` into `psbt`.
+///
+/// Every PSBT must have the same unsigned tx. When two PSBTs carry different signatures for
+/// the same key, the one valid for the sighash of the input is kept. Signatures of which
+/// neither is valid and other values set differently are all reported at once, and `psbt` is
+/// left untouched.
+pub fn combine_psbts(psbt: &mut Psbt, others: &[Psbt]) -> Result<(), CoreError> {
+    let mut combined = psbt.clone();
+    let mut conflicts = Vec::new();
+
+    for (index, other) in others.iter().enumerate() {
+        if other.unsigned_tx != combined.unsigned_tx || other.inputs.len() != combined.inputs.len()
+        {
+            return Err(CoreError::PsbtUnsignedTxMismatch(index));
+        }
+
+        // Compared with everything merged so far, so two of the others can conflict too
+        let n_conflicts = conflicts.len();
+        let mut other = other.clone();
+        let merged = combined.clone();
+        let mut cache = SighashCache::new(&merged.unsigned_tx);
+        collect_conflicts(
+            &combined.xpub,
+            &other.xpub,
+            PsbtConflict::GlobalXpub,
+            &mut conflicts,
+        );
+        for (input_index, (input, other_input)) in combined
+            .inputs
+            .iter_mut()
+            .zip(other.inputs.iter_mut())
+            .enumerate()
+        {
+            resolve_signature_conflicts(
+                &merged,
+                input_index,
+                &mut cache,
+                input,
+                other_input,
+                &mut conflicts,
+            );
+            collect_input_conflicts(input_index, input, other_input, &mut conflicts);
+        }
+
+        if conflicts.len() == n_conflicts {
+            combined
+                .combine(other)
+                .map_err(|e| CoreError::InvalidParams(e.to_string()))?;
+        }
+    }
+
+    if !conflicts.is_empty() {
+        return Err(CoreError::ConflictingPsbts(conflicts));
+    }
+
+    *psbt = combined;
+    Ok(())
+}
+
+/// Keeps the valid one of two different signatures for the same key in both inputs.
+fn resolve_signature_conflicts(
+    psbt: &Psbt,
+    input: usize,
+    cache: &mut SighashCache<&Transaction>,
+    ours: &mut Input,
+    theirs: &mut Input,
+    conflicts: &mut Vec<PsbtConflict>,
+) {
+    resolve_conflicts(
+        &mut ours.tap_script_sigs,
+        &mut theirs.tap_script_sigs,
+        |key_and_leaf_hash, sig| {
+            let tap_script_sig = TapScriptSig::new(key_and_leaf_hash, *sig);
+            verify_tap_script_sig(psbt, input as u64, &tap_script_sig, cache).is_ok()
+        },
+        |(key, leaf_hash)| PsbtConflict::TapScriptSig {
+            input,
+            key,
+            leaf_hash,
+        },
+        conflicts,
+    );
+    resolve_conflicts(
+        &mut ours.partial_sigs,
+        &mut theirs.partial_sigs,
+        |key, sig| verify_ecdsa_sig(psbt, input, cache, &key, sig),
+        |key| PsbtConflict::PartialSig { input, key },
+        conflicts,
+    );
+    if let (Some(our_sig), Some(their_sig)) = (ours.tap_key_sig, theirs.tap_key_sig) {
+        if our_sig != their_sig {
+            if verify_tap_key_sig(psbt, input, cache, &our_sig) {
+                theirs.tap_key_sig = Some(our_sig);
+            } else if verify_tap_key_sig(psbt, input, cache, &their_sig) {
+                ours.tap_key_sig = Some(their_sig);
+            } else {
+                conflicts.push(PsbtConflict::TapKeySig { input });
+            }
+        }
+    }
+}
+
+fn collect_input_conflicts(
+    input: usize,
+    ours: &Input,
+    theirs: &Input,
+    conflicts: &mut Vec<PsbtConflict>,
+) {
+    collect_conflicts(
+        &ours.tap_key_origins,
+        &theirs.tap_key_origins,
+        |key| PsbtConflict::TapKeyOrigin { input, key },
+        conflicts,
+    );
+    collect_conflicts(
+        &ours.bip32_derivation,
+        &theirs.bip32_derivation,
+        |key| PsbtConflict::Bip32Derivation { input, key },
+        conflicts,
+    );
+}
+
+fn verify_ecdsa_sig(
+    psbt: &Psbt,
+    input: usize,
+    cache: &mut SighashCache<&Transaction>,
+    key: &PublicKey,
+    sig: &ecdsa::Signature,
+) -> bool {
+    psbt.sighash_ecdsa(input, cache)
+        .is_ok_and(|(msg, sighash_type)| {
+            sig.sighash_type == sighash_type
+                && get_global_secp()
+                    .verify_ecdsa(&msg, &sig.signature, &key.inner)
+                    .is_ok()
+        })
+}
+
+fn verify_tap_key_sig(
+    psbt: &Psbt,
+    input: usize,
+    cache: &mut SighashCache<&Transaction>,
+    sig: &taproot::Signature,
+) -> bool {
+    let output_key = psbt.inputs[input]
+        .witness_utxo
+        .as_ref()
+        .filter(|utxo| utxo.script_pubkey.is_p2tr())
+        .and_then(|utxo| XOnlyPublicKey::from_slice(&utxo.script_pubkey.as_bytes()[2..]).ok());
+    let Some(output_key) = output_key else {
+        return false;
+    };
+    psbt.sighash_taproot(input, cache, None)
+        .is_ok_and(|(msg, sighash_type)| {
+            sig.sighash_type == sighash_type
+                && get_global_secp()
+                    .verify_schnorr(&sig.signature, &msg, &output_key)
+                    .is_ok()
+        })
+}
+
+/// Values set differently in both maps are replaced by the valid one, the conflict is reported
+/// when neither is valid.
+fn resolve_conflicts<K: Ord + Copy, V: Copy + PartialEq>(
+    ours: &mut BTreeMap<K, V>,
+    theirs: &mut BTreeMap<K, V>,
+    mut is_valid: impl FnMut(K, &V) -> bool,
+    conflict: impl Fn(K) -> PsbtConflict,
+    conflicts: &mut Vec<PsbtConflict>,
+) {
+    for (key, value) in theirs.iter_mut() {
+        let Some(our_value) = ours.get_mut(key) else {
+            continue;
+        };
+        if our_value == value {
+            continue;
+        }
+        if is_valid(*key, our_value) {
+            *value = *our_value;
+        } else if is_valid(*key, value) {
+            *our_value = *value;
+        } else {
+            conflicts.push(conflict(*key));
+        }
+    }
+}
+
+fn collect_conflicts<K: Ord + Copy, V: PartialEq>(
+    ours: &BTreeMap<K, V>,
+    theirs: &BTreeMap<K, V>,
+    conflict: impl Fn(K) -> PsbtConflict,
+    conflicts: &mut Vec<PsbtConflict>,
+) {
+    for (key, value) in theirs {
+        if ours.get(key).is_some_and(|ours| ours != value) {
+            conflicts.push(conflict(*key));
+        }
+    }
+}
@@ -4,6 +4,9 @@
 /// Size of the hash size in bytes
 pub const HASH_SIZE: usize = 32;
 
+/// Size of the preimage of an HTLC payment hash in bytes
+pub const PREIMAGE_SIZE: usize = 32;
+
 pub const SEQUENCE_SIZE: usize = 8;
 /// Size of the evm address in bytes
 pub const EVM_ADDRESS_SIZE: usize = 20;
@@ -46,6 +49,22 @@ pub const EMBEDDED_DATA_SCRIPT_SIZE: usize = TAG_HASH_SIZE
     + DEST_TOKEN_ADDRESS_SIZE
     + DEST_RECIPIENT_ADDRESS_SIZE;
 
+/// Size of the BIP-68 relative lock time (consensus encoding) in bytes
+pub const RELATIVE_LOCK_TIME_SIZE: usize = 4;
+
+/// Size of an x-only public key in bytes
+pub const X_ONLY_PUBKEY_SIZE: usize = 32;
+
+/// Total size of the embedded data script for time gated locking
+pub const TIME_GATED_EMBEDDED_DATA_SCRIPT_SIZE: usize = TAG_HASH_SIZE
+    + VERSION_SIZE
+    + NETWORK_ID_SIZE
+    + FLAGS_SIZE
+    + SERVICE_TAG_HASH_SIZE
+    + CUSTODIAN_QUORUM_SIZE
+    + RELATIVE_LOCK_TIME_SIZE
+    + X_ONLY_PUBKEY_SIZE;
+
 /// Total size of the embbeded data script for unlocking
 pub const UNLOCKING_EMBEDDED_DATA_SCRIPT_SIZE: usize = TAG_HASH_SIZE
     + VERSION_SIZE
@@ -55,12 +74,54 @@ pub const UNLOCKING_EMBEDDED_DATA_SCRIPT_SIZE: usize = TAG_HASH_SIZE
     + SEQUENCE_SIZE
     + HASH_SIZE;
 
+/// Total size of the embedded data script of a custodian rotation,
+/// the old and the new custodian group uids follow the header
+pub const ROTATION_EMBEDDED_DATA_SCRIPT_SIZE: usize = TAG_HASH_SIZE
+    + VERSION_SIZE
+    + NETWORK_ID_SIZE
+    + FLAGS_SIZE
+    + SERVICE_TAG_HASH_SIZE
+    + HASH_SIZE
+    + HASH_SIZE;
+
 /*
     FEE CALCULATION
 */
 
 pub const P2TR_INPUT_SIZE: u64 = 58; // 57.5
+/// Outpoint, empty script sig and sequence of an input, without witness
+pub const TX_INPUT_BASE_SIZE: u64 = 41;
 pub const P2TR_OUTPUT_SIZE: u64 = 43;
 pub const P2TR_BUFFER_SIZE: u64 = 11; // 10.5
+pub const P2WPKH_INPUT_SIZE: u64 = 68; // 67.75
+pub const P2SH_P2WPKH_INPUT_SIZE: u64 = 91; // 90.75
+/// A 2-of-3 multisig, the witness script of a P2WSH output is not known in advance
+pub const P2WSH_INPUT_SIZE: u64 = 105; // 104.5
+pub const P2PKH_INPUT_SIZE: u64 = 148;
 pub const ESTIMATE_SIGNATURE_COST: u64 = 16;
 pub const ESTIMATE_ADDITIONAL_P2TR_SCRIPT_PATH_COST: u64 = 60;
+
+/// Dust limit of a P2TR output in sats
+pub const P2TR_DUST_LIMIT: u64 = 330;
+
+/// Maximum number of branches explored by the branch-and-bound coin selection
+pub const MAX_BNB_TRIES: usize = 100_000;
+
+/*
+    TAPSCRIPT LIMITS
+*/
+
+/// Maximum number of stack elements, witness included (BIP-342)
+pub const MAX_TAPSCRIPT_STACK_SIZE: usize = 1000;
+
+/// Maximum weight of a transaction relayed by bitcoin core
+pub const MAX_STANDARD_TX_WEIGHT: usize = 400_000;
+
+/// Size of a schnorr signature with an explicit sighash type
+pub const MAX_SCHNORR_SIGNATURE_SIZE: usize = 65;
+
+/// Maximum number of custodians of a vault, quorums and weights are single bytes
+pub const MAX_CUSTODIANS: usize = u8::MAX as usize;
+
+/// Maximum number of custodian subsets enumerated for a subset leaf tree
+pub const MAX_CUSTODIAN_SUBSET_COMBINATIONS: usize = 200_000;
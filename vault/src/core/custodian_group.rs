@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use bitcoin::{
+    hashes::{sha256t_hash_newtype, Hash, HashEngine},
+    PublicKey, TxOut,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    convert_pubkeys_to_x_only_keys, validate_custodians, BranchLayout, CoreError,
+    CustodianOnlyUnlockingParams, PreviousOutpoint, TaprootInternalKey, HASH_SIZE,
+};
+
+sha256t_hash_newtype! {
+    struct CustodianGroupTag = hash_str("ScalarVault/CustodianGroup");
+
+    /// Tagged hash committing to a custodian group.
+    #[hash_newtype(forward)]
+    struct CustodianGroupHash(_);
+}
+
+sha256t_hash_newtype! {
+    struct CustodianGroupTreeTag = hash_str("ScalarVault/CustodianGroupTree");
+
+    /// Tagged hash committing to a custodian group with weights or a key-path internal key.
+    #[hash_newtype(forward)]
+    struct CustodianGroupTreeHash(_);
+}
+
+/// A custodian set together with its on-chain uid.
+///
+/// The uid is the tagged hash `ScalarVault/CustodianGroup` of the sorted x-only custodian keys,
+/// the quorum and the big endian `u64` epoch, so the same set gets a new uid on each epoch.
+/// Groups with weights or another internal key than NUMS commit to them as well, see
+/// [`CustodianGroup::compute_weighted_uid`], they are kept to rebuild the vault tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "CustodianGroupFields")]
+pub struct CustodianGroup {
+    custodian_pubkeys: Vec<PublicKey>,
+    custodian_quorum: u8,
+    custodian_weights: Option<Vec<u8>>,
+    internal_key: TaprootInternalKey,
+    epoch: u64,
+    uid: [u8; HASH_SIZE],
+}
+
+/// Loaded groups are checked against their uid.
+#[derive(Deserialize)]
+struct CustodianGroupFields {
+    custodian_pubkeys: Vec<PublicKey>,
+    custodian_quorum: u8,
+    #[serde(default)]
+    custodian_weights: Option<Vec<u8>>,
+    #[serde(default)]
+    internal_key: TaprootInternalKey,
+    epoch: u64,
+    uid: [u8; HASH_SIZE],
+}
+
+impl TryFrom<CustodianGroupFields> for CustodianGroup {
+    type Error = CoreError;
+
+    fn try_from(fields: CustodianGroupFields) -> Result<Self, Self::Error> {
+        let group = Self::new_weighted(
+            fields.custodian_pubkeys,
+            fields.custodian_weights,
+            fields.custodian_quorum,
+            fields.internal_key,
+            fields.epoch,
+        )?;
+        if group.uid != fields.uid {
+            return Err(CoreError::CustodianGroupUidMismatch);
+        }
+        Ok(group)
+    }
+}
+
+impl CustodianGroup {
+    pub fn new(
+        custodian_pubkeys: Vec<PublicKey>,
+        custodian_quorum: u8,
+        epoch: u64,
+    ) -> Result<Self, CoreError> {
+        Self::new_weighted(
+            custodian_pubkeys,
+            None,
+            custodian_quorum,
+            TaprootInternalKey::Nums,
+            epoch,
+        )
+    }
+
+    /// Same as [`Self::new`] for a weighted quorum and the internal key of the group vaults.
+    pub fn new_weighted(
+        custodian_pubkeys: Vec<PublicKey>,
+        custodian_weights: Option<Vec<u8>>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        epoch: u64,
+    ) -> Result<Self, CoreError> {
+        validate_custodians(
+            &convert_pubkeys_to_x_only_keys(&custodian_pubkeys),
+            custodian_quorum,
+            custodian_weights.as_deref(),
+        )?;
+        let uid = Self::compute_weighted_uid(
+            &custodian_pubkeys,
+            custodian_weights.as_deref(),
+            custodian_quorum,
+            internal_key,
+            epoch,
+        );
+        Ok(Self {
+            custodian_pubkeys,
+            custodian_quorum,
+            custodian_weights,
+            internal_key,
+            epoch,
+            uid,
+        })
+    }
+
+    /// Derives the uid of a custodian group, the order of `custodian_pubkeys` does not matter.
+    pub fn compute_uid(
+        custodian_pubkeys: &[PublicKey],
+        custodian_quorum: u8,
+        epoch: u64,
+    ) -> [u8; HASH_SIZE] {
+        let mut custodians = convert_pubkeys_to_x_only_keys(custodian_pubkeys);
+        custodians.sort();
+
+        let mut engine = CustodianGroupHash::engine();
+        for custodian in custodians.iter() {
+            engine.input(&custodian.serialize());
+        }
+        engine.input(&[custodian_quorum]);
+        engine.input(&epoch.to_be_bytes());
+
+        CustodianGroupHash::from_engine(engine).to_byte_array()
+    }
+
+    /// Same as [`Self::compute_uid`], also committing to the weights and the internal key.
+    ///
+    /// Unweighted NUMS groups keep the uid of [`Self::compute_uid`]. Others are hashed with the
+    /// `ScalarVault/CustodianGroupTree` tag: the custodian count, each sorted x-only key followed
+    /// by its weight if any, the quorum, the internal key and the big endian `u64` epoch.
+    pub fn compute_weighted_uid(
+        custodian_pubkeys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        epoch: u64,
+    ) -> [u8; HASH_SIZE] {
+        if custodian_weights.is_none() && internal_key == TaprootInternalKey::Nums {
+            return Self::compute_uid(custodian_pubkeys, custodian_quorum, epoch);
+        }
+
+        let mut custodians: Vec<_> = convert_pubkeys_to_x_only_keys(custodian_pubkeys)
+            .into_iter()
+            .enumerate()
+            .map(|(i, custodian)| (custodian, custodian_weights.map(|weights| weights[i])))
+            .collect();
+        custodians.sort();
+
+        let mut engine = CustodianGroupTreeHash::engine();
+        engine.input(&[custodians.len() as u8, custodian_weights.is_some() as u8]);
+        for (custodian, weight) in custodians.iter() {
+            engine.input(&custodian.serialize());
+            if let Some(weight) = weight {
+                engine.input(&[*weight]);
+            }
+        }
+        engine.input(&[custodian_quorum]);
+        match internal_key {
+            TaprootInternalKey::Nums => engine.input(&[0]),
+            TaprootInternalKey::CustodianMusig2 => engine.input(&[1]),
+            TaprootInternalKey::FrostGroupKey(group_key) => {
+                engine.input(&[2]);
+                engine.input(&group_key.serialize());
+            }
+        }
+        engine.input(&epoch.to_be_bytes());
+
+        CustodianGroupTreeHash::from_engine(engine).to_byte_array()
+    }
+
+    pub fn custodian_pubkeys(&self) -> &[PublicKey] {
+        &self.custodian_pubkeys
+    }
+
+    pub fn custodian_quorum(&self) -> u8 {
+        self.custodian_quorum
+    }
+
+    pub fn custodian_weights(&self) -> Option<&[u8]> {
+        self.custodian_weights.as_deref()
+    }
+
+    pub fn internal_key(&self) -> TaprootInternalKey {
+        self.internal_key
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn uid(&self) -> &[u8; HASH_SIZE] {
+        &self.uid
+    }
+
+    /// Unlocking params spending a custodian only vault of this group, tagged with its uid.
+    pub fn unlocking_params(
+        &self,
+        inputs: Vec<PreviousOutpoint>,
+        outputs: Vec<TxOut>,
+        rbf: bool,
+        fee_rate: u64,
+        session_sequence: u64,
+    ) -> CustodianOnlyUnlockingParams {
+        CustodianOnlyUnlockingParams {
+            inputs,
+            outputs,
+            custodian_pubkeys: self.custodian_pubkeys.clone(),
+            custodian_quorum: self.custodian_quorum,
+            custodian_weights: self.custodian_weights.clone(),
+            internal_key: self.internal_key,
+            branch_layout: BranchLayout::Legacy,
+            subsets: None,
+            signers: None,
+            rbf,
+            fee_rate,
+            key_origins: None,
+            sighash_type: None,
+            session_sequence,
+            custodian_group_uid: self.uid,
+        }
+    }
+}
+
+/// Known custodian groups by uid, to map the uid of a parsed vault tx back to its custodians.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "Vec<CustodianGroup>", into = "Vec<CustodianGroup>")]
+pub struct CustodianGroupRegistry(BTreeMap<[u8; HASH_SIZE], CustodianGroup>);
+
+impl CustodianGroupRegistry {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Registers a group, replacing the group with the same uid if any.
+    pub fn insert(&mut self, group: CustodianGroup) -> Option<CustodianGroup> {
+        self.0.insert(group.uid, group)
+    }
+
+    pub fn get(&self, uid: &[u8; HASH_SIZE]) -> Option<&CustodianGroup> {
+        self.0.get(uid)
+    }
+
+    pub fn groups(&self) -> impl Iterator<Item = &CustodianGroup> {
+        self.0.values()
+    }
+}
+
+impl From<Vec<CustodianGroup>> for CustodianGroupRegistry {
+    fn from(groups: Vec<CustodianGroup>) -> Self {
+        Self(groups.into_iter().map(|group| (group.uid, group)).collect())
+    }
+}
+
+impl From<CustodianGroupRegistry> for Vec<CustodianGroup> {
+    fn from(registry: CustodianGroupRegistry) -> Self {
+        registry.0.into_values().collect()
+    }
+}
@@ -0,0 +1,586 @@
+//! Output descriptors for vault locking scripts.
+//!
+//! Vaults whose leaves are all miniscript are described by a standard `tr(INTERNAL,TREE)`
+//! descriptor, see [`VaultDescriptor::to_descriptor`]. This is the case of the HTLC vault, and of
+//! the UPC, custodian-only and time-gated vaults built with [`BranchLayout::Miniscript`]:
+//!
+//! | Leaf                 | Miniscript                               | Script                                                                 |
+//! |----------------------|------------------------------------------|------------------------------------------------------------------------|
+//! | Hashlock             | `and_v(v:sha256(H),pk(X))`               | `OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 H OP_EQUALVERIFY X OP_CHECKSIG`   |
+//! | Timelock             | `and_v(v:after(t),pk(X))`                | `t OP_CLTV OP_VERIFY X OP_CHECKSIG`                                    |
+//! | Two parties          | `and_v(v:pk(X),pk(Y))`                   | `X OP_CHECKSIGVERIFY Y OP_CHECKSIG`                                    |
+//! | Custodians           | `multi_a(q,K1,...,Kn)`                   | `K1 OP_CHECKSIG K2 OP_CHECKSIGADD ... Kn OP_CHECKSIGADD q OP_NUMEQUAL` |
+//! | Party and custodians | `and_v(v:pk(X),multi_a(q,K1,...,Kn))`    | `X OP_CHECKSIGVERIFY` followed by the custodians                       |
+//! | Emergency            | `and_v(v:after(t),multi_a(q,K1,...,Kn))` | `t OP_CLTV OP_VERIFY` followed by the custodians                       |
+//! | Sequence             | `and_v(v:older(n),pk(X))`                | `n OP_CSV OP_VERIFY X OP_CHECKSIG`                                     |
+//!
+//! The legacy custodian leaves end with `OP_GREATERTHANOREQUAL` where `multi_a` ends with
+//! `OP_NUMEQUAL`, and the legacy sequence and emergency leaves drop the lock time where `older`
+//! and `after` verify it, so these vaults have no `tr()` descriptor, nor have the weighted ones
+//! and the ones spendable by key path. Any vault is watched by importing its output key as
+//! `rawtr(OUTPUT_KEY)`, see [`VaultDescriptor::import_descriptors`].
+
+use std::{str::FromStr, sync::Arc};
+
+use bitcoin::{
+    absolute, hashes::sha256, key::Secp256k1, relative, secp256k1::All, taproot::TaprootSpendInfo,
+    Script, ScriptBuf, XOnlyPublicKey,
+};
+use bitcoincore_rpc::{
+    json::{ImportDescriptors, Timestamp},
+    jsonrpc::serde_json,
+};
+use miniscript::{
+    descriptor::{checksum::desc_checksum, TapTree},
+    Descriptor, Miniscript, Tap,
+};
+
+use super::{
+    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, get_global_secp, BranchLayout,
+    CoreError, CustodianOnlyLockingParams, CustodianOnlyTree, HtlcLockingParams, HtlcTree,
+    TaprootInternalKey, TaprootTree, TimeGatedLockingParams, TimeGatedTree, UPCEmergencyParams,
+    UPCLockingParams, UPCTaprootTree, VaultBranch, NUMS_BIP_341,
+};
+
+/// The BIP-380 checksum of a descriptor.
+pub fn descriptor_checksum(desc: &str) -> Result<String, CoreError> {
+    desc_checksum(desc).map_err(|e| CoreError::InvalidDescriptor(e.to_string()))
+}
+
+pub fn with_descriptor_checksum(desc: &str) -> Result<String, CoreError> {
+    Ok(format!("{}#{}", desc, descriptor_checksum(desc)?))
+}
+
+/// The parameters of a vault tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultDescriptor {
+    UPC {
+        user_pubkey: XOnlyPublicKey,
+        protocol_pubkey: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        emergency: Option<UPCEmergencyParams>,
+        branch_layout: BranchLayout,
+    },
+    CustodianOnly {
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        branch_layout: BranchLayout,
+    },
+    TimeGated {
+        party_pubkey: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        sequence: relative::LockTime,
+        branch_layout: BranchLayout,
+    },
+    Htlc {
+        recipient_pubkey: XOnlyPublicKey,
+        refund_pubkey: XOnlyPublicKey,
+        payment_hash: sha256::Hash,
+        lock_time: absolute::LockTime,
+    },
+}
+
+impl VaultDescriptor {
+    /// Builds the taproot tree described by the descriptor.
+    pub fn spend_info(&self, secp: &Secp256k1<All>) -> Result<TaprootSpendInfo, CoreError> {
+        Ok(match self {
+            VaultDescriptor::UPC {
+                user_pubkey,
+                protocol_pubkey,
+                custodian_pubkeys,
+                custodian_quorum,
+                internal_key,
+                emergency,
+                branch_layout,
+            } => {
+                upc_tree(
+                    secp,
+                    *user_pubkey,
+                    *protocol_pubkey,
+                    custodian_pubkeys,
+                    *custodian_quorum,
+                    *internal_key,
+                    *emergency,
+                    *branch_layout,
+                )?
+                .root
+            }
+            VaultDescriptor::CustodianOnly {
+                custodian_pubkeys,
+                custodian_quorum,
+                internal_key,
+                branch_layout,
+            } => {
+                TaprootTree::<CustodianOnlyTree>::new_weighted(
+                    secp,
+                    custodian_pubkeys,
+                    None,
+                    *custodian_quorum,
+                    *internal_key,
+                    *branch_layout,
+                )?
+                .root
+            }
+            VaultDescriptor::TimeGated {
+                party_pubkey,
+                custodian_pubkeys,
+                custodian_quorum,
+                sequence,
+                branch_layout,
+            } => {
+                TaprootTree::<TimeGatedTree>::new_weighted(
+                    secp,
+                    party_pubkey,
+                    custodian_pubkeys,
+                    None,
+                    *custodian_quorum,
+                    *sequence,
+                    *branch_layout,
+                )?
+                .root
+            }
+            VaultDescriptor::Htlc {
+                recipient_pubkey,
+                refund_pubkey,
+                payment_hash,
+                lock_time,
+            } => {
+                TaprootTree::<HtlcTree>::new(
+                    secp,
+                    recipient_pubkey,
+                    refund_pubkey,
+                    payment_hash,
+                    *lock_time,
+                )?
+                .root
+            }
+        })
+    }
+
+    pub fn script_pubkey(&self, secp: &Secp256k1<All>) -> Result<ScriptBuf, CoreError> {
+        let spend_info = self.spend_info(secp)?;
+        Ok(ScriptBuf::new_p2tr_tweaked(spend_info.output_key()))
+    }
+
+    /// The `tr()` miniscript descriptor of the vault, for the HTLC vault and the vaults built with
+    /// [`BranchLayout::Miniscript`] and the NUMS internal key.
+    ///
+    /// The leaves are at the depths of the vault tree, so the descriptor has the vault output key.
+    pub fn to_descriptor(
+        &self,
+        secp: &Secp256k1<All>,
+    ) -> Result<Descriptor<XOnlyPublicKey>, CoreError> {
+        let tap_tree = match self {
+            VaultDescriptor::Htlc {
+                recipient_pubkey,
+                refund_pubkey,
+                payment_hash,
+                lock_time,
+            } => {
+                let tree = TaprootTree::<HtlcTree>::new(
+                    secp,
+                    recipient_pubkey,
+                    refund_pubkey,
+                    payment_hash,
+                    *lock_time,
+                )?;
+                TapTree::combine(
+                    tap_leaf(&tree.raw.hashlock_branch)?,
+                    tap_leaf(&tree.raw.timelock_branch)?,
+                )
+            }
+            VaultDescriptor::UPC {
+                branch_layout: BranchLayout::Legacy,
+                ..
+            }
+            | VaultDescriptor::CustodianOnly {
+                branch_layout: BranchLayout::Legacy,
+                ..
+            }
+            | VaultDescriptor::TimeGated {
+                branch_layout: BranchLayout::Legacy,
+                ..
+            } => {
+                return Err(invalid(
+                    "legacy custodian leaves are not expressible in miniscript, use the miniscript branch layout or the watch-only descriptor",
+                ))
+            }
+            VaultDescriptor::UPC {
+                user_pubkey,
+                protocol_pubkey,
+                custodian_pubkeys,
+                custodian_quorum,
+                internal_key,
+                emergency,
+                branch_layout,
+            } => {
+                require_nums(internal_key)?;
+                let tree = upc_tree(
+                    secp,
+                    *user_pubkey,
+                    *protocol_pubkey,
+                    custodian_pubkeys,
+                    *custodian_quorum,
+                    *internal_key,
+                    *emergency,
+                    *branch_layout,
+                )?;
+                let standard = TapTree::combine(
+                    tap_leaf(&tree.raw.user_protocol_branch)?,
+                    TapTree::combine(
+                        tap_leaf(&tree.raw.custodian_user_branch)?,
+                        tap_leaf(&tree.raw.custodian_protocol_branch)?,
+                    ),
+                );
+                match &tree.raw.emergency_branch {
+                    Some(emergency_branch) => {
+                        TapTree::combine(tap_leaf(emergency_branch)?, standard)
+                    }
+                    None => standard,
+                }
+            }
+            VaultDescriptor::CustodianOnly {
+                custodian_pubkeys,
+                custodian_quorum,
+                internal_key,
+                branch_layout,
+            } => {
+                require_nums(internal_key)?;
+                let tree = TaprootTree::<CustodianOnlyTree>::new_weighted(
+                    secp,
+                    custodian_pubkeys,
+                    None,
+                    *custodian_quorum,
+                    *internal_key,
+                    *branch_layout,
+                )?;
+                tap_leaf(&tree.raw.custodian_only_branch)?
+            }
+            VaultDescriptor::TimeGated {
+                party_pubkey,
+                custodian_pubkeys,
+                custodian_quorum,
+                sequence,
+                branch_layout,
+            } => {
+                let tree = TaprootTree::<TimeGatedTree>::new_weighted(
+                    secp,
+                    party_pubkey,
+                    custodian_pubkeys,
+                    None,
+                    *custodian_quorum,
+                    *sequence,
+                    *branch_layout,
+                )?;
+                TapTree::combine(
+                    tap_leaf(&tree.raw.csv_party_branch)?,
+                    tap_leaf(&tree.raw.custodian_only_branch)?,
+                )
+            }
+        };
+
+        Descriptor::new_tr(*NUMS_BIP_341, Some(tap_tree))
+            .map_err(|e| CoreError::InvalidDescriptor(e.to_string()))
+    }
+
+    /// The checksummed `rawtr()` descriptor of the output key, importable by watch-only wallets.
+    pub fn watch_only_descriptor(&self, secp: &Secp256k1<All>) -> Result<String, CoreError> {
+        let output_key = self.spend_info(secp)?.output_key().to_x_only_public_key();
+        with_descriptor_checksum(&format!("rawtr({})", output_key))
+    }
+
+    /// The `importdescriptors` request of a watch-only wallet.
+    pub fn import_descriptors(
+        &self,
+        secp: &Secp256k1<All>,
+        timestamp: Timestamp,
+        label: Option<String>,
+    ) -> Result<ImportDescriptors, CoreError> {
+        Ok(ImportDescriptors {
+            descriptor: self.watch_only_descriptor(secp)?,
+            timestamp,
+            label,
+            ..Default::default()
+        })
+    }
+
+    /// The JSON payload of `bitcoin-cli importdescriptors`.
+    pub fn import_descriptors_json(
+        &self,
+        secp: &Secp256k1<All>,
+        timestamp: Timestamp,
+        label: Option<String>,
+    ) -> Result<String, CoreError> {
+        let request = self.import_descriptors(secp, timestamp, label)?;
+        serde_json::to_string(&[request]).map_err(|_| CoreError::FailedToSerialize)
+    }
+}
+
+impl FromStr for VaultDescriptor {
+    type Err = CoreError;
+
+    /// Parses a `tr()` descriptor, the checksum is verified when present.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((desc, checksum)) = s.split_once('#') {
+            if descriptor_checksum(desc)? != checksum {
+                return Err(CoreError::InvalidDescriptorChecksum);
+            }
+        }
+        let descriptor = Descriptor::<XOnlyPublicKey>::from_str(s)
+            .map_err(|e| CoreError::InvalidDescriptor(e.to_string()))?;
+        VaultDescriptor::try_from(&descriptor)
+    }
+}
+
+impl TryFrom<&Descriptor<XOnlyPublicKey>> for VaultDescriptor {
+    type Error = CoreError;
+
+    /// Recognizes the vault layout of a `tr()` descriptor from its leaf scripts.
+    fn try_from(descriptor: &Descriptor<XOnlyPublicKey>) -> Result<Self, Self::Error> {
+        let Descriptor::Tr(tr) = descriptor else {
+            return Err(invalid("only tr() descriptors describe vaults"));
+        };
+        // Vaults spendable by key path are not expressible, see the module docs
+        if *tr.internal_key() != *NUMS_BIP_341 {
+            return Err(invalid("internal key must be the NUMS point"));
+        }
+
+        let leaves = tr
+            .iter_scripts()
+            .map(|(_, ms)| {
+                VaultBranch::from_script(&ms.encode())
+                    .map_err(|_| invalid("leaf is not a vault branch"))
+            })
+            .collect::<Result<Vec<_>, CoreError>>()?;
+
+        let vault = vault_from_leaves(&leaves)
+            .ok_or_else(|| invalid("leaves do not match a vault tree"))?;
+
+        // The leaves may be at other depths than in the vault tree
+        if vault.to_descriptor(get_global_secp())?.script_pubkey() != descriptor.script_pubkey() {
+            return Err(invalid("leaves do not match a vault tree"));
+        }
+
+        Ok(vault)
+    }
+}
+
+impl TryFrom<&UPCLockingParams> for VaultDescriptor {
+    type Error = CoreError;
+
+    fn try_from(params: &UPCLockingParams) -> Result<Self, Self::Error> {
+        reject_custodian_weights(&params.custodian_weights)?;
+        Ok(VaultDescriptor::UPC {
+            user_pubkey: convert_pubkey_to_x_only_key(&params.user_pubkey),
+            protocol_pubkey: convert_pubkey_to_x_only_key(&params.protocol_pubkey),
+            custodian_pubkeys: convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys),
+            custodian_quorum: params.custodian_quorum,
+            internal_key: params.internal_key,
+            emergency: params.emergency,
+            branch_layout: params.branch_layout,
+        })
+    }
+}
+
+impl TryFrom<&CustodianOnlyLockingParams> for VaultDescriptor {
+    type Error = CoreError;
+
+    fn try_from(params: &CustodianOnlyLockingParams) -> Result<Self, Self::Error> {
+        reject_custodian_weights(&params.custodian_weights)?;
+        if params.subsets.is_some() {
+            return Err(invalid("custodian subset leaves are not supported"));
+        }
+        Ok(VaultDescriptor::CustodianOnly {
+            custodian_pubkeys: convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys),
+            custodian_quorum: params.custodian_quorum,
+            internal_key: params.internal_key,
+            branch_layout: params.branch_layout,
+        })
+    }
+}
+
+impl TryFrom<&TimeGatedLockingParams> for VaultDescriptor {
+    type Error = CoreError;
+
+    fn try_from(params: &TimeGatedLockingParams) -> Result<Self, Self::Error> {
+        reject_custodian_weights(&params.custodian_weights)?;
+        Ok(VaultDescriptor::TimeGated {
+            party_pubkey: convert_pubkey_to_x_only_key(&params.party_pubkey),
+            custodian_pubkeys: convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys),
+            custodian_quorum: params.custodian_quorum,
+            sequence: params.sequence,
+            branch_layout: params.branch_layout,
+        })
+    }
+}
+
+impl From<&HtlcLockingParams> for VaultDescriptor {
+    fn from(params: &HtlcLockingParams) -> Self {
+        VaultDescriptor::Htlc {
+            recipient_pubkey: convert_pubkey_to_x_only_key(&params.recipient_pubkey),
+            refund_pubkey: convert_pubkey_to_x_only_key(&params.refund_pubkey),
+            payment_hash: params.payment_hash,
+            lock_time: params.lock_time,
+        }
+    }
+}
+
+/// The vault parameters do not carry custodian weights.
+fn reject_custodian_weights(custodian_weights: &Option<Vec<u8>>) -> Result<(), CoreError> {
+    match custodian_weights {
+        Some(_) => Err(invalid("weighted custodian quorums are not supported")),
+        None => Ok(()),
+    }
+}
+
+/// The vault whose tree has these leaves, in any order. Their depths are not checked.
+fn vault_from_leaves(leaves: &[VaultBranch]) -> Option<VaultDescriptor> {
+    match leaves {
+        [VaultBranch::Hashlock {
+            payment_hash,
+            party: recipient_pubkey,
+        }, VaultBranch::PartyWithLockTimeVerification {
+            party: refund_pubkey,
+            lock_time,
+        }]
+        | [VaultBranch::PartyWithLockTimeVerification {
+            party: refund_pubkey,
+            lock_time,
+        }, VaultBranch::Hashlock {
+            payment_hash,
+            party: recipient_pubkey,
+        }] => Some(VaultDescriptor::Htlc {
+            recipient_pubkey: *recipient_pubkey,
+            refund_pubkey: *refund_pubkey,
+            payment_hash: *payment_hash,
+            lock_time: *lock_time,
+        }),
+        [VaultBranch::CustodianOnly {
+            custodian_pubkeys,
+            custodian_quorum,
+            layout: BranchLayout::Miniscript,
+        }] => Some(VaultDescriptor::CustodianOnly {
+            custodian_pubkeys: custodian_pubkeys.clone(),
+            custodian_quorum: *custodian_quorum,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Miniscript,
+        }),
+        [VaultBranch::PartyWithSequenceVerification {
+            party,
+            sequence,
+            layout: BranchLayout::Miniscript,
+        }, VaultBranch::CustodianOnly {
+            custodian_pubkeys,
+            custodian_quorum,
+            layout: BranchLayout::Miniscript,
+        }]
+        | [VaultBranch::CustodianOnly {
+            custodian_pubkeys,
+            custodian_quorum,
+            layout: BranchLayout::Miniscript,
+        }, VaultBranch::PartyWithSequenceVerification {
+            party,
+            sequence,
+            layout: BranchLayout::Miniscript,
+        }] => Some(VaultDescriptor::TimeGated {
+            party_pubkey: *party,
+            custodian_pubkeys: custodian_pubkeys.clone(),
+            custodian_quorum: *custodian_quorum,
+            sequence: relative::LockTime::from_consensus(u32::try_from(*sequence).ok()?).ok()?,
+            branch_layout: BranchLayout::Miniscript,
+        }),
+        _ => upc_from_leaves(leaves),
+    }
+}
+
+/// The user and protocol are the two parties of the `U + P` leaf, in this order.
+fn upc_from_leaves(leaves: &[VaultBranch]) -> Option<VaultDescriptor> {
+    let mut two_party = None;
+    let mut custodian_branches = vec![];
+    let mut emergency = None;
+
+    for leaf in leaves {
+        match leaf {
+            VaultBranch::TwoParty { x, y } if two_party.is_none() => two_party = Some((*x, *y)),
+            VaultBranch::CustodianAndParty {
+                custodian_pubkeys,
+                custodian_quorum,
+                layout: BranchLayout::Miniscript,
+                ..
+            } => custodian_branches.push((custodian_pubkeys, *custodian_quorum)),
+            VaultBranch::CustodianWithLockTime {
+                custodian_quorum,
+                lock_time,
+                layout: BranchLayout::Miniscript,
+                ..
+            } if emergency.is_none() => {
+                emergency = Some(UPCEmergencyParams {
+                    custodian_quorum: *custodian_quorum,
+                    lock_time: *lock_time,
+                })
+            }
+            _ => return None,
+        }
+    }
+
+    let (user_pubkey, protocol_pubkey) = two_party?;
+    let [(custodian_pubkeys, custodian_quorum), _] = custodian_branches.as_slice() else {
+        return None;
+    };
+
+    Some(VaultDescriptor::UPC {
+        user_pubkey,
+        protocol_pubkey,
+        custodian_pubkeys: (*custodian_pubkeys).clone(),
+        custodian_quorum: *custodian_quorum,
+        internal_key: TaprootInternalKey::Nums,
+        emergency,
+        branch_layout: BranchLayout::Miniscript,
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn upc_tree(
+    secp: &Secp256k1<All>,
+    user_pubkey: XOnlyPublicKey,
+    protocol_pubkey: XOnlyPublicKey,
+    custodian_pubkeys: &[XOnlyPublicKey],
+    custodian_quorum: u8,
+    internal_key: TaprootInternalKey,
+    emergency: Option<UPCEmergencyParams>,
+    branch_layout: BranchLayout,
+) -> Result<TaprootTree<UPCTaprootTree>, CoreError> {
+    TaprootTree::<UPCTaprootTree>::new_weighted(
+        secp,
+        user_pubkey,
+        protocol_pubkey,
+        custodian_pubkeys.to_vec(),
+        None,
+        custodian_quorum,
+        internal_key,
+        emergency,
+        branch_layout,
+    )
+}
+
+/// Vaults spendable by key path are not expressible, see the module docs.
+fn require_nums(internal_key: &TaprootInternalKey) -> Result<(), CoreError> {
+    match internal_key {
+        TaprootInternalKey::Nums => Ok(()),
+        _ => Err(invalid("internal key must be the NUMS point")),
+    }
+}
+
+fn invalid(reason: &str) -> CoreError {
+    CoreError::InvalidDescriptor(reason.to_string())
+}
+
+fn tap_leaf(script: &Script) -> Result<TapTree<XOnlyPublicKey>, CoreError> {
+    let ms = Miniscript::<XOnlyPublicKey, Tap>::parse(script)
+        .map_err(|e| CoreError::InvalidDescriptor(e.to_string()))?;
+    Ok(TapTree::Leaf(Arc::new(ms)))
+}
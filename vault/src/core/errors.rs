@@ -1,6 +1,8 @@
-use bitcoin::taproot::TaprootBuilderError;
+use bitcoin::{taproot::TaprootBuilderError, TapLeafHash, TapSighashType, XOnlyPublicKey};
 use thiserror::Error;
 
+use super::{InputSigningStatus, PsbtConflict, RejectedTapScriptSig};
+
 #[derive(Error, Debug)]
 pub enum CoreError {
     #[error("Insufficient UTXOs: required {required}, available {available}")]
@@ -49,7 +51,7 @@ pub enum CoreError {
     InsufficientFunds,
     #[error("Mismatch between number of inputs and tap script sigs")]
     MismatchBetweenNumberOfInputsAndTapScriptSigs,
-    #[error("Invalid signature size, just support Sighash Default")]
+    #[error("Invalid signature, expected 64 bytes or 65 bytes with a supported sighash type")]
     InvalidSignatureSize,
     #[error("Failed to encode leaf hash")]
     FailedToEncodeLeafHash,
@@ -63,4 +65,98 @@ pub enum CoreError {
     FailedToSerialize,
     #[error("Invalid params: {0}")]
     InvalidParams(String),
+    #[error("MuSig2 error: {0}")]
+    Musig2(String),
+    #[error(
+        "Input {0} is neither a key-path nor a leaf spend of the MuSig2 aggregated custodian key"
+    )]
+    Musig2InternalKeyMismatch(usize),
+    #[error("Mismatch between number of inputs and MuSig2 nonces")]
+    MismatchBetweenNumberOfInputsAndMusig2Nonces,
+    #[error("Invalid MuSig2 partial signature for input {0} from {1}")]
+    InvalidMusig2PartialSignature(usize, XOnlyPublicKey),
+    #[error("Invalid emergency quorum: {0}, must be higher than the custodian quorum and at most the number of custodians")]
+    InvalidEmergencyQuorum(u8),
+    #[error("Emergency branch not found")]
+    EmergencyBranchNotFound,
+    #[error("Preimage is not 32 bytes or does not match the payment hash")]
+    InvalidPreimage,
+    #[error("Duplicate policy leaf: {0}")]
+    DuplicatePolicyLeaf(String),
+    #[error("Policy leaf not found: {0}")]
+    PolicyLeafNotFound(String),
+    #[error("Invalid descriptor: {0}")]
+    InvalidDescriptor(String),
+    #[error("Invalid descriptor checksum")]
+    InvalidDescriptorChecksum,
+    #[error("Script does not match any vault branch template")]
+    UnrecognizedBranchScript,
+    #[error("Script is not the canonical encoding of its vault branch")]
+    NonCanonicalBranchScript,
+    #[error("Invalid custodian weights: one non-zero weight per custodian is required and the quorum must be reachable")]
+    InvalidCustodianWeights,
+    #[error("Invalid custodian subset")]
+    InvalidCustodianSubset,
+    #[error("Too many custodian subsets: {0}, sample them with max_leaves")]
+    TooManyCustodianSubsets(usize),
+    #[error("No custodian subset leaf matches the signers")]
+    CustodianSubsetNotFound,
+    #[error("Invalid lock time: {0}")]
+    InvalidLockTime(String),
+    #[error("Invalid relative lock time: {0}")]
+    InvalidRelativeLockTime(String),
+    #[error("Custodian set is empty")]
+    EmptyCustodianSet,
+    #[error("Too many custodians: {0}, at most {max}", max = crate::MAX_CUSTODIANS)]
+    TooManyCustodians(usize),
+    #[error(
+        "Invalid custodian quorum: {quorum}, must be between 1 and the {custodians} custodians"
+    )]
+    InvalidCustodianQuorum { quorum: u8, custodians: usize },
+    #[error("Key {0} is used by more than one party")]
+    OverlappingKeys(XOnlyPublicKey),
+    #[error("Amount must be greater than 0")]
+    ZeroAmount,
+    #[error("Amount {amount} is below the dust limit of {dust}")]
+    AmountBelowDust { amount: u64, dust: u64 },
+    #[error("Tapscript stack of {0} elements exceeds the limit of {max}", max = crate::MAX_TAPSCRIPT_STACK_SIZE)]
+    TapscriptStackLimitExceeded(usize),
+    #[error("Tapscript witness of {0} WU exceeds the standard tx weight of {max}", max = crate::MAX_STANDARD_TX_WEIGHT)]
+    TapscriptWitnessTooLarge(usize),
+    #[error("Custodian group uid does not match its custodians, quorum and epoch")]
+    CustodianGroupUidMismatch,
+    #[error("PSBT {0} does not have the same unsigned tx")]
+    PsbtUnsignedTxMismatch(usize),
+    #[error("Conflicting PSBTs: {}", .0.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(", "))]
+    ConflictingPsbts(Vec<PsbtConflict>),
+    #[error("Unsupported sighash type: {0}")]
+    UnsupportedSighashType(TapSighashType),
+    #[error("Sighash {0} needs an output for each input, got {1} outputs for {2} inputs")]
+    SighashSingleWithoutOutput(TapSighashType, usize, usize),
+    #[error("Sighash {0} pairs input {1} with an op_return output")]
+    SighashSingleWithDataOutput(TapSighashType, usize),
+    #[error("External signer error: {0}")]
+    ExternalSigner(String),
+    #[error("Invalid signature of {1} from the external signer for input {0}")]
+    InvalidExternalSignature(usize, XOnlyPublicKey),
+    #[error("No signing key for {0}")]
+    UnknownSigningKey(XOnlyPublicKey),
+    #[error("FROST error: {0}")]
+    Frost(String),
+    #[error("Invalid FROST DKG commitment from participant {0}")]
+    InvalidFrostDkgCommitment(u16),
+    #[error("Invalid FROST DKG share from participant {0}")]
+    InvalidFrostDkgShare(u16),
+    #[error("Input {0} is not a key-path spend of the FROST group key")]
+    FrostInternalKeyMismatch(usize),
+    #[error("Mismatch between number of inputs and FROST nonces")]
+    MismatchBetweenNumberOfInputsAndFrostNonces,
+    #[error("Invalid FROST signature share for input {0} from participant {1}")]
+    InvalidFrostSignatureShare(usize, u16),
+    #[error("Leaf {1} not found in input {0}")]
+    TapLeafNotFound(usize, TapLeafHash),
+    #[error("Inputs cannot be finalized: {}", .0.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", "))]
+    InputsNotFinalized(Vec<InputSigningStatus>),
+    #[error("Rejected tap script sigs: {}", .0.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(", "))]
+    RejectedTapScriptSigs(Vec<RejectedTapScriptSig>),
 }
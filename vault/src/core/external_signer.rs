@@ -0,0 +1,147 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bitcoin::{
+    bip32::KeySource,
+    secp256k1::{schnorr, Keypair, Message},
+    sighash::SighashCache,
+    taproot, Psbt, TapLeafHash, TapSighashType, XOnlyPublicKey,
+};
+
+use super::{get_global_secp, AsyncExternalSigner, CoreError, ExternalSigner, Utils};
+
+/// A taproot script path sighash to be signed by an [`ExternalSigner`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashRequest {
+    pub input: usize,
+    pub key: XOnlyPublicKey,
+    pub leaf_hash: TapLeafHash,
+    /// Appended to the signature by the vault, the signer only signs `message`.
+    pub sighash_type: TapSighashType,
+    pub message: Message,
+    /// Origin of `key` from `tap_key_origins`, for signers deriving their keys.
+    pub key_source: KeySource,
+}
+
+/// The script path sighashes `keys` have to sign, from the `tap_key_origins` of every input.
+/// Leaves the key already signed are skipped.
+pub fn sighash_requests(
+    psbt: &Psbt,
+    keys: &[XOnlyPublicKey],
+) -> Result<Vec<SighashRequest>, CoreError> {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut requests = Vec::new();
+
+    for (index, input) in psbt.inputs.iter().enumerate() {
+        for key in keys {
+            let Some((leaf_hashes, key_source)) = input.tap_key_origins.get(key) else {
+                continue;
+            };
+
+            for leaf_hash in leaf_hashes {
+                if input.tap_script_sigs.contains_key(&(*key, *leaf_hash)) {
+                    continue;
+                }
+
+                let (message, sighash_type) = psbt
+                    .sighash_taproot(index, &mut cache, Some(*leaf_hash))
+                    .map_err(|e| CoreError::SigningPSBTFailed(e.to_string()))?;
+                requests.push(SighashRequest {
+                    input: index,
+                    key: *key,
+                    leaf_hash: *leaf_hash,
+                    sighash_type,
+                    message,
+                    key_source: key_source.clone(),
+                });
+            }
+        }
+    }
+
+    Ok(requests)
+}
+
+/// Verifies the signatures returned for `requests` and adds them to `tap_script_sigs`, nothing
+/// is added if any of them is invalid. Returns the keys that signed.
+pub(crate) fn insert_external_signatures(
+    psbt: &mut Psbt,
+    requests: &[SighashRequest],
+    signatures: &[schnorr::Signature],
+) -> Result<BTreeSet<XOnlyPublicKey>, CoreError> {
+    let secp = get_global_secp();
+    for (request, signature) in requests.iter().zip(signatures) {
+        secp.verify_schnorr(signature, &request.message, &request.key)
+            .map_err(|_| CoreError::InvalidExternalSignature(request.input, request.key))?;
+    }
+
+    let mut signed_keys = BTreeSet::new();
+    for (request, signature) in requests.iter().zip(signatures) {
+        psbt.inputs[request.input].tap_script_sigs.insert(
+            (request.key, request.leaf_hash),
+            taproot::Signature {
+                signature: *signature,
+                sighash_type: request.sighash_type,
+            },
+        );
+        signed_keys.insert(request.key);
+    }
+
+    Ok(signed_keys)
+}
+
+/// Reference [`ExternalSigner`] keeping its keys in memory, for tests.
+#[derive(Debug, Clone, Default)]
+pub struct InMemorySigner {
+    keypairs: BTreeMap<XOnlyPublicKey, Keypair>,
+}
+
+impl InMemorySigner {
+    pub fn new(keypairs: impl IntoIterator<Item = Keypair>) -> Self {
+        Self {
+            keypairs: keypairs
+                .into_iter()
+                .map(|keypair| (keypair.x_only_public_key().0, keypair))
+                .collect(),
+        }
+    }
+
+    pub fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey> {
+        self.keypairs.keys().copied().collect()
+    }
+}
+
+impl ExternalSigner for InMemorySigner {
+    type Error = CoreError;
+
+    fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey> {
+        InMemorySigner::x_only_pubkeys(self)
+    }
+
+    fn sign_schnorr(&self, request: &SighashRequest) -> Result<schnorr::Signature, Self::Error> {
+        let keypair = self
+            .keypairs
+            .get(&request.key)
+            .ok_or(CoreError::UnknownSigningKey(request.key))?;
+
+        #[cfg(feature = "rand-std")]
+        let signature = get_global_secp().sign_schnorr(&request.message, keypair);
+        #[cfg(not(feature = "rand-std"))]
+        let signature = get_global_secp().sign_schnorr_no_aux_rand(&request.message, keypair);
+
+        Ok(signature)
+    }
+}
+
+impl AsyncExternalSigner for InMemorySigner {
+    type Error = CoreError;
+
+    fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey> {
+        InMemorySigner::x_only_pubkeys(self)
+    }
+
+    async fn sign_schnorr(
+        &self,
+        request: &SighashRequest,
+    ) -> Result<schnorr::Signature, Self::Error> {
+        <Self as ExternalSigner>::sign_schnorr(self, request)
+    }
+}
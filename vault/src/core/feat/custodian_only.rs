@@ -1,11 +1,14 @@
-use bitcoin::{opcodes::all::OP_RETURN, script::Builder, Psbt, PublicKey};
+use bitcoin::{
+    absolute, opcodes::all::OP_RETURN, script::Builder, Psbt, PublicKey, XOnlyPublicKey,
+};
 
 use crate::{
-    convert_pubkeys_to_x_only_keys, get_global_secp, CoreError, CustodianOnly,
-    CustodianOnlyLockingParams, CustodianOnlyTree, CustodianOnlyUnlockingParams, DataScript,
+    aggregate_custodian_pubkeys, convert_pubkeys_to_x_only_keys, custodian_signature_count,
+    get_global_secp, BranchLayout, CoreError, CustodianOnly, CustodianOnlyLockingParams,
+    CustodianOnlyTree, CustodianOnlyUnlockingParams, CustodianSubsetParams, DataScript,
     DestinationChain, DestinationRecipientAddress, DestinationTokenAddress, LockingOutput,
-    LockingScript, TaprootTree, TaprootTreeType, UnlockingParams, UnlockingTaprootTreeType,
-    VaultManager, EMBEDDED_DATA_SCRIPT_SIZE,
+    LockingScript, TaprootInternalKey, TaprootTree, TaprootTreeType, UnlockingParams,
+    UnlockingTaprootTreeType, VaultManager, EMBEDDED_DATA_SCRIPT_SIZE,
 };
 
 impl CustodianOnly for VaultManager {
@@ -15,10 +18,17 @@ impl CustodianOnly for VaultManager {
         &self,
         params: &CustodianOnlyLockingParams,
     ) -> Result<LockingOutput, Self::Error> {
-        let locking_script = <Self as CustodianOnly>::locking_script(
-            &params.custodian_pubkeys,
+        params.validate()?;
+
+        let tree = custodian_only_tree(
+            &convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys),
+            params.custodian_weights.as_deref(),
             params.custodian_quorum,
+            params.internal_key,
+            params.subsets.as_ref(),
+            params.branch_layout,
         )?;
+        let locking_script = LockingScript(tree.into_script(get_global_secp()));
 
         let data_script = <Self as CustodianOnly>::data_script(
             self,
@@ -37,12 +47,22 @@ impl CustodianOnly for VaultManager {
 
     fn locking_script(
         custodian_pubkeys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        subsets: Option<&CustodianSubsetParams>,
     ) -> Result<LockingScript, Self::Error> {
         let secp = get_global_secp();
         let keys = convert_pubkeys_to_x_only_keys(custodian_pubkeys);
 
-        let tree = TaprootTree::<CustodianOnlyTree>::new(secp, &keys, custodian_quorum)?;
+        let tree = custodian_only_tree(
+            &keys,
+            custodian_weights,
+            custodian_quorum,
+            internal_key,
+            subsets,
+            BranchLayout::Legacy,
+        )?;
 
         Ok(LockingScript(tree.into_script(secp)))
     }
@@ -90,8 +110,47 @@ impl CustodianOnly for VaultManager {
         let secp = get_global_secp();
 
         let x_only_pubkeys = convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys);
-        let tree =
-            TaprootTree::<CustodianOnlyTree>::new(secp, &x_only_pubkeys, params.custodian_quorum)?;
+        let tree = custodian_only_tree(
+            &x_only_pubkeys,
+            params.custodian_weights.as_deref(),
+            params.custodian_quorum,
+            params.internal_key,
+            params.subsets.as_ref(),
+            params.branch_layout,
+        )?;
+
+        // In subset mode the signers spend their own k-of-k leaf, the k-of-n leaf is only spent
+        // when the signers are not known
+        let signers = params
+            .signers
+            .as_deref()
+            .map(convert_pubkeys_to_x_only_keys)
+            .unwrap_or_default();
+        let subset_leaf = tree.raw.subset_leaf(&signers);
+        if params.subsets.is_some() && params.signers.is_some() && subset_leaf.is_none() {
+            return Err(CoreError::CustodianSubsetNotFound);
+        }
+
+        let (branch, keys, n_signatures) = match (subset_leaf, &params.subsets) {
+            (Some(leaf), Some(subsets)) if subsets.aggregate => (
+                leaf.branch.clone(),
+                vec![aggregate_custodian_pubkeys(&leaf.custodian_pubkeys)?],
+                1,
+            ),
+            (Some(leaf), _) => (
+                leaf.branch.clone(),
+                leaf.custodian_pubkeys.clone(),
+                leaf.custodian_pubkeys.len() as u8,
+            ),
+            (None, _) => (
+                tree.raw.custodian_only_branch.clone(),
+                x_only_pubkeys,
+                custodian_signature_count(
+                    params.custodian_weights.as_deref(),
+                    params.custodian_quorum,
+                ),
+            ),
+        };
 
         let unsigned_tx = self.build_unlocking_transaction(&UnlockingParams {
             total_input_value,
@@ -101,19 +160,60 @@ impl CustodianOnly for VaultManager {
             tree_type: UnlockingTaprootTreeType::CustodianOnlyBranch,
             script: &tree.clone().into_script(secp),
             rbf: params.rbf,
+            lock_time: absolute::LockTime::ZERO,
             fee_rate: params.fee_rate,
-            custodian_quorum: params.custodian_quorum,
+            custodian_quorum: n_signatures,
             session_sequence: params.session_sequence,
             custodian_group_uid: params.custodian_group_uid,
+            sighash_type: params.sighash_type,
         })?;
 
         let mut psbt =
             Psbt::from_unsigned_tx(unsigned_tx).map_err(|_| CoreError::FailedToCreatePSBT)?;
 
-        let (branch, keys) = (tree.raw.custodian_only_branch, x_only_pubkeys);
-
-        psbt.inputs = self.prepare_psbt_inputs(&params.inputs, &tree.root, &branch, &keys);
+        psbt.inputs = self.prepare_psbt_inputs(
+            &params.inputs,
+            &tree.root,
+            &branch,
+            &keys,
+            params.key_origins.as_ref(),
+        );
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
 
         Ok(psbt)
     }
 }
+
+fn custodian_only_tree(
+    custodian_pubkeys: &[XOnlyPublicKey],
+    custodian_weights: Option<&[u8]>,
+    custodian_quorum: u8,
+    internal_key: TaprootInternalKey,
+    subsets: Option<&CustodianSubsetParams>,
+    layout: BranchLayout,
+) -> Result<TaprootTree<CustodianOnlyTree>, CoreError> {
+    let secp = get_global_secp();
+
+    match (subsets, custodian_weights) {
+        (Some(_), Some(_)) => Err(CoreError::InvalidParams(
+            "custodian weights are not supported in subset mode".to_string(),
+        )),
+        (Some(subsets), None) => TaprootTree::<CustodianOnlyTree>::new_with_subsets(
+            secp,
+            custodian_pubkeys,
+            custodian_quorum,
+            internal_key,
+            subsets,
+            layout,
+        ),
+        (None, _) => TaprootTree::<CustodianOnlyTree>::new_weighted(
+            secp,
+            custodian_pubkeys,
+            custodian_weights,
+            custodian_quorum,
+            internal_key,
+            layout,
+        ),
+    }
+}
@@ -0,0 +1,206 @@
+use bitcoin::{psbt::Input, Amount, Psbt, Script, ScriptBuf, TxOut, XOnlyPublicKey};
+
+use crate::{
+    get_global_secp, select_coins_bnb, select_coins_knapsack, CoreError, FundedLocking,
+    FundedLockingParams, FundingUtxo, KeyOrigins, TransactionBuilder, VaultManager,
+    P2PKH_INPUT_SIZE, P2SH_P2WPKH_INPUT_SIZE, P2TR_BUFFER_SIZE, P2TR_INPUT_SIZE, P2WPKH_INPUT_SIZE,
+    P2WSH_INPUT_SIZE,
+};
+
+/// How a funding utxo is spent, the P2SH-P2WPKH variant carrying its redeem script.
+enum FundingType {
+    P2wpkh,
+    P2shP2wpkh(ScriptBuf),
+    P2tr,
+}
+
+impl FundingType {
+    fn from_utxo(utxo: &FundingUtxo) -> Result<Self, CoreError> {
+        let script_pubkey = &utxo.input.script_pubkey;
+
+        if let Ok(wpubkey_hash) = utxo.pubkey.wpubkey_hash() {
+            let p2wpkh = ScriptBuf::new_p2wpkh(&wpubkey_hash);
+            if *script_pubkey == p2wpkh {
+                return Ok(Self::P2wpkh);
+            }
+            if *script_pubkey == ScriptBuf::new_p2sh(&p2wpkh.script_hash()) {
+                return Ok(Self::P2shP2wpkh(p2wpkh));
+            }
+        }
+
+        let xonly = XOnlyPublicKey::from(utxo.pubkey);
+        if *script_pubkey == ScriptBuf::new_p2tr(get_global_secp(), xonly, None) {
+            return Ok(Self::P2tr);
+        }
+
+        Err(CoreError::InvalidParams(format!(
+            "utxo {} is not a P2WPKH, P2SH-P2WPKH or P2TR output of its key",
+            utxo.input.outpoint
+        )))
+    }
+
+    fn input_size(&self) -> u64 {
+        match self {
+            Self::P2wpkh => P2WPKH_INPUT_SIZE,
+            Self::P2shP2wpkh(_) => P2SH_P2WPKH_INPUT_SIZE,
+            Self::P2tr => P2TR_INPUT_SIZE,
+        }
+    }
+}
+
+impl FundedLocking for VaultManager {
+    type Error = CoreError;
+
+    fn build_funded_locking_psbt(&self, params: &FundedLockingParams) -> Result<Psbt, Self::Error> {
+        params.validate()?;
+
+        let funding_types = params
+            .utxos
+            .iter()
+            .map(FundingType::from_utxo)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let locking_outputs = params.locking_output.clone().into_tx_outs();
+        let locking_amount: u64 = locking_outputs.iter().map(|out| out.value.to_sat()).sum();
+        let base_size = P2TR_BUFFER_SIZE
+            + locking_outputs
+                .iter()
+                .map(|out| out.size() as u64)
+                .sum::<u64>();
+        let target = locking_amount + base_size * params.fee_rate;
+
+        // Utxos costing more to spend than they are worth are left out
+        let (candidates, effective_values): (Vec<usize>, Vec<u64>) = params
+            .utxos
+            .iter()
+            .zip(&funding_types)
+            .enumerate()
+            .filter_map(|(i, (utxo, typ))| {
+                utxo.input
+                    .amount_in_sats
+                    .to_sat()
+                    .checked_sub(typ.input_size() * params.fee_rate)
+                    .filter(|value| *value > 0)
+                    .map(|value| (i, value))
+            })
+            .unzip();
+
+        let change_output = TxOut {
+            value: Amount::ZERO,
+            script_pubkey: params.change_script_pubkey.clone(),
+        };
+        let change_fee = change_output.size() as u64 * params.fee_rate;
+        let change_dust = params.change_script_pubkey.minimal_non_dust().to_sat();
+        let cost_of_change =
+            change_fee + change_spend_size(&params.change_script_pubkey) * params.fee_rate;
+
+        let (selection, change) = match select_coins_bnb(&effective_values, target, cost_of_change)
+        {
+            Some(selection) => (selection, None),
+            None => {
+                let selection =
+                    select_coins_knapsack(&effective_values, target + change_fee + change_dust)
+                        .or_else(|| select_coins_knapsack(&effective_values, target))
+                        .ok_or(CoreError::InsufficientUTXOs {
+                            required: target,
+                            available: effective_values.iter().sum(),
+                        })?;
+                let excess = selection.iter().map(|i| effective_values[*i]).sum::<u64>() - target;
+                // Below the dust limit the excess is left to the fee
+                let change = (excess >= change_fee + change_dust).then(|| excess - change_fee);
+                (selection, change)
+            }
+        };
+
+        let mut selected: Vec<usize> = selection.into_iter().map(|i| candidates[i]).collect();
+        selected.sort();
+
+        let mut tx_builder = TransactionBuilder::new(params.rbf);
+        for &i in &selected {
+            tx_builder.add_input(params.utxos[i].input.outpoint);
+        }
+        tx_builder.add_outputs(&locking_outputs);
+        if let Some(change) = change {
+            tx_builder.add_output(
+                Amount::from_sat(change),
+                params.change_script_pubkey.clone(),
+            );
+        }
+
+        let mut psbt = Psbt::from_unsigned_tx(tx_builder.build())
+            .map_err(|_| CoreError::FailedToCreatePSBT)?;
+
+        psbt.inputs = selected
+            .iter()
+            .map(|&i| {
+                funding_psbt_input(
+                    &params.utxos[i],
+                    &funding_types[i],
+                    params.key_origins.as_ref(),
+                )
+            })
+            .collect();
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+
+        Ok(psbt)
+    }
+}
+
+/// Size of the input spending the change later on, part of the cost of making change.
+fn change_spend_size(script_pubkey: &Script) -> u64 {
+    if script_pubkey.is_p2tr() {
+        P2TR_INPUT_SIZE
+    } else if script_pubkey.is_p2wpkh() {
+        P2WPKH_INPUT_SIZE
+    } else if script_pubkey.is_p2wsh() {
+        P2WSH_INPUT_SIZE
+    } else if script_pubkey.is_p2sh() {
+        P2SH_P2WPKH_INPUT_SIZE
+    } else {
+        // P2PKH, other legacy scripts get no witness discount either
+        P2PKH_INPUT_SIZE
+    }
+}
+
+fn funding_psbt_input(
+    utxo: &FundingUtxo,
+    typ: &FundingType,
+    key_origins: Option<&KeyOrigins>,
+) -> Input {
+    let xonly = XOnlyPublicKey::from(utxo.pubkey);
+    // Keys without a known origin are left out, the signer matches them by pubkey
+    let key_source = key_origins
+        .and_then(|origins| origins.key_source(&xonly))
+        .cloned();
+
+    let mut input = Input {
+        witness_utxo: Some(TxOut {
+            value: utxo.input.amount_in_sats,
+            script_pubkey: utxo.input.script_pubkey.clone(),
+        }),
+        ..Default::default()
+    };
+
+    match typ {
+        FundingType::P2tr => {
+            input.tap_internal_key = Some(xonly);
+            if let Some(key_source) = key_source {
+                // BIP 371: the internal key has no leaf hashes
+                input.tap_key_origins.insert(xonly, (vec![], key_source));
+            }
+        }
+        FundingType::P2shP2wpkh(redeem_script) => {
+            input.redeem_script = Some(redeem_script.clone());
+            if let Some(key_source) = key_source {
+                input.bip32_derivation.insert(utxo.pubkey.inner, key_source);
+            }
+        }
+        FundingType::P2wpkh => {
+            if let Some(key_source) = key_source {
+                input.bip32_derivation.insert(utxo.pubkey.inner, key_source);
+            }
+        }
+    }
+
+    input
+}
@@ -0,0 +1,129 @@
+use bitcoin::{absolute, hashes::sha256, taproot::LeafVersion, Psbt, PublicKey, TxOut};
+
+use crate::{
+    convert_pubkey_to_x_only_key, get_global_secp, schnorr_signature_size, tap_leaf_witness_weight,
+    CoreError, Htlc, HtlcLockingParams, HtlcTree, HtlcUnlockingParams, HtlcUnlockingType,
+    LockingOutput, LockingScript, ScriptPathFeeParams, TaprootTree, TransactionBuilder,
+    VaultManager, PREIMAGE_SIZE,
+};
+
+impl Htlc for VaultManager {
+    type Error = CoreError;
+
+    fn build_locking_output(
+        &self,
+        params: &HtlcLockingParams,
+    ) -> Result<LockingOutput, Self::Error> {
+        params.validate()?;
+
+        let locking_script = <Self as Htlc>::locking_script(
+            &params.recipient_pubkey,
+            &params.refund_pubkey,
+            &params.payment_hash,
+            params.lock_time,
+        )?;
+
+        Ok(LockingOutput::new(
+            params.locking_amount,
+            locking_script,
+            None,
+        ))
+    }
+
+    fn locking_script(
+        recipient_pubkey: &PublicKey,
+        refund_pubkey: &PublicKey,
+        payment_hash: &sha256::Hash,
+        lock_time: absolute::LockTime,
+    ) -> Result<LockingScript, Self::Error> {
+        let secp = get_global_secp();
+
+        let tree = TaprootTree::<HtlcTree>::new(
+            secp,
+            &convert_pubkey_to_x_only_key(recipient_pubkey),
+            &convert_pubkey_to_x_only_key(refund_pubkey),
+            payment_hash,
+            lock_time,
+        )?;
+
+        Ok(LockingScript(tree.into_script(secp)))
+    }
+
+    fn build_unlocking_psbt(&self, params: &HtlcUnlockingParams) -> Result<Psbt, Self::Error> {
+        params.validate()?;
+
+        let secp = get_global_secp();
+        let recipient = convert_pubkey_to_x_only_key(&params.recipient_pubkey);
+        let refund = convert_pubkey_to_x_only_key(&params.refund_pubkey);
+
+        let tree = TaprootTree::<HtlcTree>::new(
+            secp,
+            &recipient,
+            &refund,
+            &params.payment_hash,
+            params.lock_time,
+        )?;
+
+        // The refund is only valid once the lock time is reached
+        let lock_time = match params.typ {
+            HtlcUnlockingType::Claim => absolute::LockTime::ZERO,
+            HtlcUnlockingType::Refund => params.lock_time,
+        };
+
+        let mut tx_builder = TransactionBuilder::new(true).with_lock_time(lock_time);
+
+        tx_builder.add_input(params.input.outpoint);
+
+        tx_builder.add_raw_output(TxOut {
+            script_pubkey: params.script_pubkey.clone(),
+            value: params.input.amount_in_sats,
+        });
+
+        let mut unsigned_tx = tx_builder.build();
+
+        let (branch, keys) = match params.typ {
+            HtlcUnlockingType::Claim => (&tree.raw.hashlock_branch, vec![recipient]),
+            HtlcUnlockingType::Refund => (&tree.raw.timelock_branch, vec![refund]),
+        };
+        let control_block = tree
+            .root
+            .control_block(&(branch.clone(), LeafVersion::TapScript))
+            .ok_or(CoreError::ControlBlockNotFound)?;
+
+        // The signature, and the preimage of a claim, are pushed before the leaf
+        let signature_size = schnorr_signature_size(params.sighash_type);
+        let stack_element_sizes = match params.typ {
+            HtlcUnlockingType::Claim => vec![signature_size, PREIMAGE_SIZE],
+            HtlcUnlockingType::Refund => vec![signature_size],
+        };
+        let fee = self.calculate_script_path_fee(ScriptPathFeeParams {
+            n_inputs: unsigned_tx.input.len() as u64,
+            outputs_size: unsigned_tx.output.iter().map(|out| out.size() as u64).sum(),
+            witness_weight: tap_leaf_witness_weight(&stack_element_sizes, branch, &control_block),
+            fee_rate: params.fee_rate,
+        });
+
+        self.distribute_fee(&mut unsigned_tx, params.input.amount_in_sats, fee)?;
+
+        let mut psbt =
+            Psbt::from_unsigned_tx(unsigned_tx).map_err(|_| CoreError::FailedToCreatePSBT)?;
+
+        psbt.inputs = self.prepare_psbt_inputs(
+            std::slice::from_ref(&params.input),
+            &tree.root,
+            branch,
+            &keys,
+            params.key_origins.as_ref(),
+        );
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
+
+        if let (HtlcUnlockingType::Claim, Some(preimage)) = (&params.typ, &params.preimage) {
+            psbt.inputs[0]
+                .sha256_preimages
+                .insert(params.payment_hash, preimage.clone());
+        }
+
+        Ok(psbt)
+    }
+}
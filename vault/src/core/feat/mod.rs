@@ -1,3 +1,7 @@
 mod custodian_only;
+mod funding;
+mod htlc;
+mod policy;
+mod rotation;
+mod time_gated;
 mod upc;
-mod time_gated;
\ No newline at end of file
@@ -0,0 +1,100 @@
+use bitcoin::{Amount, FeeRate, Psbt};
+
+use crate::{
+    get_global_secp, CoreError, LockingOutput, LockingScript, PolicyLockingParams, PolicyTree,
+    PolicyUnlockingParams, PolicyVault, TaprootTree, TransactionBuilder, UnlockingFeeParams,
+    VaultManager, VaultPolicy,
+};
+
+impl PolicyVault for VaultManager {
+    type Error = CoreError;
+
+    fn build_locking_output(
+        &self,
+        params: &PolicyLockingParams,
+    ) -> Result<LockingOutput, Self::Error> {
+        params.validate()?;
+
+        let locking_script = <Self as PolicyVault>::locking_script(&params.policy)?;
+
+        Ok(LockingOutput::new(
+            params.locking_amount,
+            locking_script,
+            None,
+        ))
+    }
+
+    fn locking_script(policy: &VaultPolicy) -> Result<LockingScript, Self::Error> {
+        let secp = get_global_secp();
+
+        let tree = TaprootTree::<PolicyTree>::new(secp, policy)?;
+
+        Ok(LockingScript(tree.into_script(secp)))
+    }
+
+    fn build_unlocking_psbt(&self, params: &PolicyUnlockingParams) -> Result<Psbt, Self::Error> {
+        let (total_input_value, total_output_value) = params.validate()?;
+        let secp = get_global_secp();
+
+        let tree = TaprootTree::<PolicyTree>::new(secp, &params.policy)?;
+
+        let leaf = tree
+            .raw
+            .leaf(&params.leaf_name)
+            .ok_or(CoreError::PolicyLeafNotFound(params.leaf_name.clone()))?;
+
+        let script = tree.clone().into_script(secp);
+
+        let mut tx_builder = TransactionBuilder::new(params.rbf);
+
+        for input in params.inputs.iter() {
+            match leaf.leaf.branch.sequence() {
+                Some(sequence) => tx_builder.add_input_with_sequence(input.outpoint, sequence),
+                None => tx_builder.add_input(input.outpoint),
+            }
+        }
+
+        // output[0->n-2]: unlocking outputs
+        // output[n-1]: change output back to the vault
+        tx_builder.add_outputs(&params.outputs);
+
+        let change = total_input_value - total_output_value;
+        let has_change = change.to_sat() > FeeRate::DUST.to_sat_per_kwu();
+        if has_change {
+            tx_builder.add_output(Amount::ZERO, script.clone());
+        }
+
+        let mut unsigned_tx = tx_builder.build();
+
+        let fee = self.calculate_unlocking_fee(UnlockingFeeParams {
+            n_inputs: unsigned_tx.input.len() as u64,
+            n_outputs: unsigned_tx.output.len() as u64,
+            fee_rate: params.fee_rate,
+            quorum: leaf.leaf.branch.required_signatures(),
+            sighash_type: params.sighash_type,
+        });
+
+        self.distribute_fee(&mut unsigned_tx, total_output_value, fee)?;
+
+        if has_change {
+            if let Some(change_output) = unsigned_tx.output.last_mut() {
+                change_output.value = change;
+            }
+        }
+
+        let mut psbt =
+            Psbt::from_unsigned_tx(unsigned_tx).map_err(|_| CoreError::FailedToCreatePSBT)?;
+
+        psbt.inputs = self.prepare_psbt_inputs(
+            &params.inputs,
+            &tree.root,
+            &leaf.script,
+            &leaf.leaf.branch.keys(),
+            params.key_origins.as_ref(),
+        );
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
+
+        Ok(psbt)
+    }
+}
@@ -0,0 +1,179 @@
+use bitcoin::{
+    opcodes::all::OP_RETURN,
+    script::{Builder, PushBytesBuf},
+    Amount, Psbt, ScriptBuf, XOnlyPublicKey,
+};
+
+use crate::{
+    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, custodian_signature_count,
+    get_global_secp, BranchLayout, CoreError, CustodianGroup, CustodianOnly, CustodianOnlyTree,
+    CustodianRotation, CustodianRotationParams, DataScript, RotationVault, TaprootTree,
+    TransactionBuilder, UPCTaprootTree, UnlockingFeeParams, UnlockingTaprootTreeType, VaultManager,
+    HASH_SIZE, ROTATION_EMBEDDED_DATA_SCRIPT_SIZE, UPC,
+};
+
+impl CustodianRotation for VaultManager {
+    type Error = CoreError;
+
+    fn build_rotation_psbt(&self, params: &CustodianRotationParams) -> Result<Psbt, Self::Error> {
+        let total_input_value = params.validate()?;
+        let secp = get_global_secp();
+
+        let old = &params.old_custodians;
+        let old_custodians = convert_pubkeys_to_x_only_keys(old.custodian_pubkeys());
+        let old_signatures =
+            custodian_signature_count(old.custodian_weights(), old.custodian_quorum());
+
+        // The old vault is spent by its custodians, together with the protocol for UPC vaults
+        let (tree, branch, keys, n_signatures) = match params.vault {
+            RotationVault::CustodianOnly => {
+                let tree = TaprootTree::<CustodianOnlyTree>::new_weighted(
+                    secp,
+                    &old_custodians,
+                    old.custodian_weights(),
+                    old.custodian_quorum(),
+                    old.internal_key(),
+                    BranchLayout::Legacy,
+                )?;
+                let branch = tree.raw.custodian_only_branch.clone();
+                (tree.root, branch, old_custodians, old_signatures)
+            }
+            RotationVault::UPC {
+                user_pubkey,
+                protocol_pubkey,
+                emergency,
+            } => {
+                let protocol = convert_pubkey_to_x_only_key(&protocol_pubkey);
+                let tree = TaprootTree::<UPCTaprootTree>::new_weighted(
+                    secp,
+                    convert_pubkey_to_x_only_key(&user_pubkey),
+                    protocol,
+                    old_custodians.clone(),
+                    old.custodian_weights(),
+                    old.custodian_quorum(),
+                    old.internal_key(),
+                    emergency,
+                    BranchLayout::Legacy,
+                )?;
+                let branch = tree.raw.custodian_protocol_branch.clone();
+                let keys: Vec<XOnlyPublicKey> =
+                    std::iter::once(protocol).chain(old_custodians).collect();
+                (tree.root, branch, keys, old_signatures + 1)
+            }
+        };
+
+        let old_script = ScriptBuf::new_p2tr_tweaked(tree.output_key());
+        if params
+            .inputs
+            .iter()
+            .any(|input| input.script_pubkey != old_script)
+        {
+            return Err(CoreError::InvalidParams(
+                "every input must be locked under the old custodian set".to_string(),
+            ));
+        }
+
+        let new_script = new_locking_script(&params.vault, &params.new_custodians)?;
+        let data_script = <Self as CustodianRotation>::rotation_data_script(
+            self,
+            old.uid(),
+            params.new_custodians.uid(),
+        )?;
+
+        let mut tx_builder = TransactionBuilder::new(params.rbf);
+        self.add_inputs_to_builder(&mut tx_builder, &params.inputs);
+
+        // output[0]: rotation output (op_return)
+        // output[1]: the new vault, paying the fee
+        let fee = self.calculate_unlocking_fee(UnlockingFeeParams {
+            n_inputs: params.inputs.len() as u64,
+            n_outputs: 2,
+            quorum: n_signatures,
+            fee_rate: params.fee_rate,
+            sighash_type: params.sighash_type,
+        });
+        let amount = total_input_value
+            .checked_sub(fee)
+            .filter(|amount| *amount >= new_script.minimal_non_dust())
+            .ok_or(CoreError::InsufficientFunds)?;
+
+        tx_builder.add_output(Amount::ZERO, data_script.into_script());
+        tx_builder.add_output(amount, new_script);
+
+        let mut psbt = Psbt::from_unsigned_tx(tx_builder.build())
+            .map_err(|_| CoreError::FailedToCreatePSBT)?;
+
+        psbt.inputs = self.prepare_psbt_inputs(
+            &params.inputs,
+            &tree,
+            &branch,
+            &keys,
+            params.key_origins.as_ref(),
+        );
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
+
+        Ok(psbt)
+    }
+
+    fn rotation_data_script(
+        &self,
+        old_custodian_group_uid: &[u8; HASH_SIZE],
+        new_custodian_group_uid: &[u8; HASH_SIZE],
+    ) -> Result<DataScript, Self::Error> {
+        let tag_hash = DataScript::compute_tag_hash(self.tag().as_slice())?;
+        let service_tag_hash = DataScript::compute_service_tag_hash(self.service_tag().as_slice())?;
+        let flags = UnlockingTaprootTreeType::CustodianRotation as u8;
+
+        let mut data = Vec::<u8>::with_capacity(ROTATION_EMBEDDED_DATA_SCRIPT_SIZE);
+        data.extend_from_slice(&tag_hash);
+        data.push(self.version());
+        data.push(self.network_id());
+        data.push(flags);
+        data.extend_from_slice(&service_tag_hash);
+        data.extend_from_slice(old_custodian_group_uid);
+        data.extend_from_slice(new_custodian_group_uid);
+
+        // Above the 76 bytes of the fixed size pushes, checked through the buffer instead
+        let data_slice = PushBytesBuf::try_from(data)
+            .ok()
+            .filter(|data| data.len() == ROTATION_EMBEDDED_DATA_SCRIPT_SIZE)
+            .ok_or(CoreError::CannotConvertOpReturnDataToSlice)?;
+
+        Ok(DataScript(
+            Builder::new()
+                .push_opcode(OP_RETURN)
+                .push_slice(data_slice)
+                .into_script(),
+        ))
+    }
+}
+
+fn new_locking_script(
+    vault: &RotationVault,
+    custodians: &CustodianGroup,
+) -> Result<ScriptBuf, CoreError> {
+    let script = match vault {
+        RotationVault::CustodianOnly => <VaultManager as CustodianOnly>::locking_script(
+            custodians.custodian_pubkeys(),
+            custodians.custodian_weights(),
+            custodians.custodian_quorum(),
+            custodians.internal_key(),
+            None,
+        )?,
+        RotationVault::UPC {
+            user_pubkey,
+            protocol_pubkey,
+            emergency,
+        } => <VaultManager as UPC>::locking_script(
+            user_pubkey,
+            protocol_pubkey,
+            custodians.custodian_pubkeys(),
+            custodians.custodian_weights(),
+            custodians.custodian_quorum(),
+            custodians.internal_key(),
+            *emergency,
+        )?,
+    };
+    Ok(script.into_script())
+}
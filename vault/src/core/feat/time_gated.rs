@@ -1,10 +1,11 @@
-use bitcoin::{Psbt, PublicKey, Sequence, TxOut};
+use bitcoin::{opcodes::all::OP_RETURN, relative, script::Builder, Amount, Psbt, PublicKey};
 
 use crate::{
-    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, get_global_secp, CoreError,
-    LockingOutput, LockingScript, TaprootTree, TimeGated, TimeGatedLockingParams, TimeGatedTree,
+    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, custodian_signature_count,
+    get_global_secp, BranchLayout, CoreError, DataScript, LockingOutput, LockingScript,
+    TaprootTree, TaprootTreeType, TimeGated, TimeGatedInput, TimeGatedLockingParams, TimeGatedTree,
     TimeGatedUnlockingParams, TimeGatedUnlockingType, TransactionBuilder, UnlockingFeeParams,
-    VaultManager,
+    VaultManager, TIME_GATED_EMBEDDED_DATA_SCRIPT_SIZE,
 };
 
 impl TimeGated for VaultManager {
@@ -14,9 +15,23 @@ impl TimeGated for VaultManager {
         &self,
         params: &TimeGatedLockingParams,
     ) -> Result<LockingOutput, Self::Error> {
-        let locking_script = <Self as TimeGated>::locking_script(
+        params.validate()?;
+
+        let secp = get_global_secp();
+        let tree = TaprootTree::<TimeGatedTree>::new_weighted(
+            secp,
+            &convert_pubkey_to_x_only_key(&params.party_pubkey),
+            &convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys),
+            params.custodian_weights.as_deref(),
+            params.custodian_quorum,
+            params.sequence,
+            params.branch_layout,
+        )?;
+        let locking_script = LockingScript(tree.into_script(secp));
+
+        let data_script = <Self as TimeGated>::data_script(
+            self,
             &params.party_pubkey,
-            &params.custodian_pubkeys,
             params.custodian_quorum,
             params.sequence,
         )?;
@@ -24,26 +39,62 @@ impl TimeGated for VaultManager {
         Ok(LockingOutput::new(
             params.locking_amount,
             locking_script,
-            None,
+            Some(data_script),
         ))
     }
 
+    fn data_script(
+        &self,
+        party: &PublicKey,
+        custodian_quorum: u8,
+        sequence: relative::LockTime,
+    ) -> Result<DataScript, Self::Error> {
+        let tag_hash = DataScript::compute_tag_hash(self.tag().as_slice())?;
+        let service_tag_hash = DataScript::compute_service_tag_hash(self.service_tag().as_slice())?;
+        let flags = TaprootTreeType::TimeGated as u8;
+
+        let mut data = Vec::<u8>::with_capacity(TIME_GATED_EMBEDDED_DATA_SCRIPT_SIZE);
+        data.extend_from_slice(&tag_hash);
+        data.push(self.version());
+        data.push(self.network_id());
+        data.push(flags);
+        data.extend_from_slice(&service_tag_hash);
+        data.push(custodian_quorum);
+        data.extend_from_slice(&sequence.to_consensus_u32().to_be_bytes());
+        data.extend_from_slice(&convert_pubkey_to_x_only_key(party).serialize());
+
+        let data_slice: &[u8; TIME_GATED_EMBEDDED_DATA_SCRIPT_SIZE] = data
+            .as_slice()
+            .try_into()
+            .map_err(|_| CoreError::CannotConvertOpReturnDataToSlice)?;
+
+        let embedded_data_script = Builder::new()
+            .push_opcode(OP_RETURN)
+            .push_slice(data_slice)
+            .into_script();
+
+        Ok(DataScript(embedded_data_script))
+    }
+
     fn locking_script(
         party: &PublicKey,
         custodian_pubkeys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
-        sequence: u16,
+        sequence: relative::LockTime,
     ) -> Result<LockingScript, Self::Error> {
         let secp = get_global_secp();
         let party_x_only_pubkey = convert_pubkey_to_x_only_key(party);
         let x_only_pubkeys = convert_pubkeys_to_x_only_keys(custodian_pubkeys);
 
-        let tree = TaprootTree::<TimeGatedTree>::new(
+        let tree = TaprootTree::<TimeGatedTree>::new_weighted(
             secp,
             &party_x_only_pubkey,
             &x_only_pubkeys,
+            custodian_weights,
             custodian_quorum,
             sequence,
+            BranchLayout::Legacy,
         )?;
         Ok(LockingScript(tree.into_script(secp)))
     }
@@ -52,54 +103,107 @@ impl TimeGated for VaultManager {
         &self,
         params: &TimeGatedUnlockingParams,
     ) -> Result<bitcoin::Psbt, Self::Error> {
+        let (total_input_value, total_output_value) = params.validate()?;
+
         let secp = get_global_secp();
         let party_x_only_pubkey = convert_pubkey_to_x_only_key(&params.party_pubkey);
         let x_only_pubkeys = convert_pubkeys_to_x_only_keys(&params.custodian_pubkeys);
 
-        let tree = TaprootTree::<TimeGatedTree>::new(
-            secp,
-            &party_x_only_pubkey,
-            &x_only_pubkeys,
-            params.custodian_quorum,
-            params.sequence,
-        )?;
+        let (keys, n_signatures) = match params.typ {
+            TimeGatedUnlockingType::CustodianOnly => (
+                x_only_pubkeys.clone(),
+                custodian_signature_count(
+                    params.custodian_weights.as_deref(),
+                    params.custodian_quorum,
+                ),
+            ),
+            TimeGatedUnlockingType::PartyTimeGated => (vec![party_x_only_pubkey], 1),
+        };
 
         let mut tx_builder = TransactionBuilder::new(true);
+        let mut psbt_inputs = Vec::with_capacity(params.inputs.len());
+
+        for TimeGatedInput { input, sequence } in params.inputs.iter() {
+            // The sequence is part of the csv leaf, so every input has its own tree
+            let tree = TaprootTree::<TimeGatedTree>::new_weighted(
+                secp,
+                &party_x_only_pubkey,
+                &x_only_pubkeys,
+                params.custodian_weights.as_deref(),
+                params.custodian_quorum,
+                *sequence,
+                params.branch_layout,
+            )?;
+
+            let branch = match params.typ {
+                TimeGatedUnlockingType::CustodianOnly => {
+                    tx_builder.add_input(input.outpoint);
+                    &tree.raw.custodian_only_branch
+                }
+                TimeGatedUnlockingType::PartyTimeGated => {
+                    tx_builder.add_input_with_sequence(input.outpoint, sequence.to_sequence());
+                    &tree.raw.csv_party_branch
+                }
+            };
+
+            psbt_inputs.extend(self.prepare_psbt_inputs(
+                std::slice::from_ref(input),
+                &tree.root,
+                branch,
+                &keys,
+                params.key_origins.as_ref(),
+            ));
+        }
+
+        tx_builder.add_outputs(&params.outputs);
+
+        let fee = |n_outputs: usize| {
+            self.calculate_unlocking_fee(UnlockingFeeParams {
+                n_inputs: params.inputs.len() as u64,
+                n_outputs: n_outputs as u64,
+                fee_rate: params.fee_rate,
+                quorum: n_signatures,
+                sighash_type: params.sighash_type,
+            })
+        };
 
-        tx_builder.add_input_with_sequence(
-            params.input.outpoint,
-            Sequence::from_height(params.sequence),
-        );
-
-        tx_builder.add_raw_output(TxOut {
-            script_pubkey: params.script_pubkey.clone(),
-            value: params.input.amount_in_sats,
-        });
-
-        let mut unsigned_tx = tx_builder.build();
-
-        let fee = self.calculate_unlocking_fee(UnlockingFeeParams {
-            n_inputs: unsigned_tx.input.len() as u64,
-            n_outputs: unsigned_tx.output.len() as u64,
-            fee_rate: params.fee_rate,
-            quorum: params.custodian_quorum,
-        });
-
-        self.distribute_fee(&mut unsigned_tx, params.input.amount_in_sats, fee)?;
+        let change = total_input_value - total_output_value;
+        let fee_with_change = fee(params.outputs.len() + 1);
+
+        let unsigned_tx =
+            if change >= fee_with_change + params.change_script_pubkey.minimal_non_dust() {
+                // The change pays the whole fee
+                tx_builder.add_output(
+                    change - fee_with_change,
+                    params.change_script_pubkey.clone(),
+                );
+                tx_builder.build()
+            } else {
+                // The change is too small to pay for itself, it goes to the fee and
+                // the outputs share the rest
+                let mut unsigned_tx = tx_builder.build();
+                let remaining_fee = fee(params.outputs.len())
+                    .checked_sub(change)
+                    .unwrap_or(Amount::ZERO);
+                if remaining_fee > Amount::ZERO {
+                    if total_output_value == Amount::ZERO {
+                        return Err(CoreError::InsufficientFunds);
+                    }
+                    self.distribute_fee(&mut unsigned_tx, total_output_value, remaining_fee)?;
+                }
+                unsigned_tx
+            };
+
+        if unsigned_tx.output.is_empty() {
+            return Err(CoreError::InsufficientFunds);
+        }
 
         let mut psbt =
             Psbt::from_unsigned_tx(unsigned_tx).map_err(|_| CoreError::FailedToCreatePSBT)?;
 
-        let (branch, keys) = match params.typ {
-            TimeGatedUnlockingType::CustodianOnly => {
-                (&tree.raw.custodian_only_branch, x_only_pubkeys)
-            }
-            TimeGatedUnlockingType::PartyTimeGated => {
-                (&tree.raw.csv_party_branch, vec![party_x_only_pubkey])
-            }
-        };
-
-        psbt.inputs = self.prepare_psbt_inputs(&[params.input.clone()], &tree.root, branch, &keys);
+        psbt.inputs = psbt_inputs;
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
 
         Ok(psbt)
     }
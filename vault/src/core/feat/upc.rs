@@ -1,11 +1,14 @@
-use bitcoin::{opcodes::all::OP_RETURN, script::Builder, Psbt, PublicKey, XOnlyPublicKey};
+use bitcoin::{
+    absolute, opcodes::all::OP_RETURN, script::Builder, Psbt, PublicKey, XOnlyPublicKey,
+};
 
 use crate::{
-    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, get_global_secp, CoreError,
-    DataScript, DestinationChain, DestinationRecipientAddress, DestinationTokenAddress,
-    LockingOutput, LockingScript, TaprootTree, TaprootTreeType, UPCLockingParams, UPCTaprootTree,
-    UPCUnlockingParams, UPCUnlockingType, UnlockingParams, UnlockingTaprootTreeType, VaultManager,
-    EMBEDDED_DATA_SCRIPT_SIZE, HASH_SIZE, UPC,
+    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, custodian_signature_count,
+    get_global_secp, BranchLayout, CoreError, DataScript, DestinationChain,
+    DestinationRecipientAddress, DestinationTokenAddress, LockingOutput, LockingScript,
+    TaprootInternalKey, TaprootTree, TaprootTreeType, UPCEmergencyParams, UPCLockingParams,
+    UPCTaprootTree, UPCUnlockingParams, UPCUnlockingType, UnlockingParams,
+    UnlockingTaprootTreeType, VaultManager, EMBEDDED_DATA_SCRIPT_SIZE, HASH_SIZE, UPC,
 };
 
 impl UPC for VaultManager {
@@ -15,12 +18,27 @@ impl UPC for VaultManager {
         &self,
         params: &UPCLockingParams,
     ) -> Result<LockingOutput, Self::Error> {
-        let locking_script = <Self as UPC>::locking_script(
+        params.validate()?;
+
+        let secp = get_global_secp();
+        let (user, protocol, custodians) = convert_upc_to_x_only_keys(
             &params.user_pubkey,
             &params.protocol_pubkey,
             &params.custodian_pubkeys,
+        );
+
+        let tree = TaprootTree::<UPCTaprootTree>::new_weighted(
+            secp,
+            user,
+            protocol,
+            custodians,
+            params.custodian_weights.as_deref(),
             params.custodian_quorum,
+            params.internal_key,
+            params.emergency,
+            params.branch_layout,
         )?;
+        let locking_script = LockingScript(tree.into_script(secp));
 
         let data_script = <Self as UPC>::data_script(
             self,
@@ -41,14 +59,26 @@ impl UPC for VaultManager {
         user_pubkey: &PublicKey,
         protocol_pubkey: &PublicKey,
         custodian_pubkeys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        emergency: Option<UPCEmergencyParams>,
     ) -> Result<LockingScript, Self::Error> {
         let secp = get_global_secp();
         let (user, protocol, custodians) =
             convert_upc_to_x_only_keys(user_pubkey, protocol_pubkey, custodian_pubkeys);
 
-        let tree =
-            TaprootTree::<UPCTaprootTree>::new(secp, user, protocol, custodians, custodian_quorum)?;
+        let tree = TaprootTree::<UPCTaprootTree>::new_weighted(
+            secp,
+            user,
+            protocol,
+            custodians,
+            custodian_weights,
+            custodian_quorum,
+            internal_key,
+            emergency,
+            BranchLayout::Legacy,
+        )?;
 
         Ok(LockingScript(tree.into_script(secp)))
     }
@@ -103,26 +133,47 @@ impl UPC for VaultManager {
             &params.custodian_pubkeys,
         );
 
-        let tree = TaprootTree::<UPCTaprootTree>::new(
+        let tree = TaprootTree::<UPCTaprootTree>::new_weighted(
             secp,
             user,
             protocol,
             custodians.clone(),
+            params.custodian_weights.as_deref(),
             params.custodian_quorum,
+            params.internal_key,
+            params.emergency,
+            params.branch_layout,
         )?;
 
+        // The emergency leaf is only spendable once its lock time is reached, with its own quorum
+        let (lock_time, custodian_quorum) = match (&params.typ, params.emergency) {
+            (UPCUnlockingType::Emergency, Some(emergency)) => {
+                (emergency.lock_time, emergency.custodian_quorum)
+            }
+            (UPCUnlockingType::Emergency, None) => return Err(CoreError::EmergencyBranchNotFound),
+            _ => (
+                absolute::LockTime::ZERO,
+                custodian_signature_count(
+                    params.custodian_weights.as_deref(),
+                    params.custodian_quorum,
+                ),
+            ),
+        };
+
         let unsigned_tx = self.build_unlocking_transaction(&UnlockingParams {
             total_input_value,
             total_output_value,
             inputs: &params.inputs,
-            outputs: &[params.output.clone()],
+            outputs: std::slice::from_ref(&params.output),
             tree_type: UnlockingTaprootTreeType::UPCBranch,
             script: &tree.clone().into_script(secp),
             rbf: params.rbf,
+            lock_time,
             fee_rate: params.fee_rate,
-            custodian_quorum: params.custodian_quorum,
+            custodian_quorum,
             session_sequence: 0,
             custodian_group_uid: [0u8; HASH_SIZE],
+            sighash_type: params.sighash_type,
         })?;
 
         let mut psbt =
@@ -142,9 +193,24 @@ impl UPC for VaultManager {
                 keys.extend_from_slice(&custodians);
                 (&tree.raw.custodian_user_branch, keys)
             }
+            UPCUnlockingType::Emergency => (
+                tree.raw
+                    .emergency_branch
+                    .as_ref()
+                    .ok_or(CoreError::EmergencyBranchNotFound)?,
+                custodians,
+            ),
         };
 
-        psbt.inputs = self.prepare_psbt_inputs(&params.inputs, &tree.root, branch, &keys);
+        psbt.inputs = self.prepare_psbt_inputs(
+            &params.inputs,
+            &tree.root,
+            branch,
+            &keys,
+            params.key_origins.as_ref(),
+        );
+        self.add_global_xpubs(&mut psbt, params.key_origins.as_ref());
+        self.set_sighash_type(&mut psbt, params.sighash_type)?;
 
         Ok(psbt)
     }
@@ -1,8 +1,8 @@
-use bitcoin::{Amount, Transaction};
+use bitcoin::{taproot::ControlBlock, Amount, Script, TapSighashType, Transaction, Witness};
 
 use super::{
-    CoreError, VaultManager, ESTIMATE_ADDITIONAL_P2TR_SCRIPT_PATH_COST, ESTIMATE_SIGNATURE_COST,
-    P2TR_BUFFER_SIZE, P2TR_INPUT_SIZE, P2TR_OUTPUT_SIZE,
+    CoreError, VaultManager, ESTIMATE_ADDITIONAL_P2TR_SCRIPT_PATH_COST, P2TR_BUFFER_SIZE,
+    P2TR_INPUT_SIZE, P2TR_OUTPUT_SIZE, TX_INPUT_BASE_SIZE,
 };
 
 #[derive(Debug)]
@@ -11,18 +11,90 @@ pub struct UnlockingFeeParams {
     pub n_outputs: u64,
     pub quorum: u8,
     pub fee_rate: u64,
+    /// Sighash type of the signatures, see [`schnorr_signature_size`]
+    pub sighash_type: Option<TapSighashType>,
+}
+
+#[derive(Debug)]
+pub struct ScriptPathFeeParams {
+    pub n_inputs: u64,
+    /// Serialized size of the outputs
+    pub outputs_size: u64,
+    /// Weight of the witness of each input, see [`tap_leaf_witness_weight`]
+    pub witness_weight: u64,
+    pub fee_rate: u64,
+}
+
+/// Size of a schnorr signature in the witness, the sighash type is appended unless it is
+/// `SIGHASH_DEFAULT`.
+pub fn schnorr_signature_size(sighash_type: Option<TapSighashType>) -> usize {
+    match sighash_type {
+        None | Some(TapSighashType::Default) => 64,
+        Some(_) => 65,
+    }
+}
+
+/// Weight of the witness spending a tapscript leaf: stack elements of the given sizes, then
+/// the script and the control block.
+pub fn tap_leaf_witness_weight(
+    stack_element_sizes: &[usize],
+    script: &Script,
+    control_block: &ControlBlock,
+) -> u64 {
+    let mut witness = Witness::new();
+    for size in stack_element_sizes {
+        witness.push(vec![0u8; *size]);
+    }
+    witness.push(script.as_bytes());
+    witness.push(control_block.serialize());
+    witness.size() as u64
+}
+
+/// The number of custodian signatures to budget for in the witness.
+///
+/// Without weights this is the quorum. With weights it is the size of the largest minimal
+/// signing set, i.e. the lightest custodians signing until the quorum weight is reached.
+pub fn custodian_signature_count(custodian_weights: Option<&[u8]>, custodian_quorum: u8) -> u8 {
+    let Some(weights) = custodian_weights else {
+        return custodian_quorum;
+    };
+
+    let mut sorted_weights = weights.to_vec();
+    sorted_weights.sort();
+
+    let mut total_weight = 0u32;
+    let mut signatures = 0u8;
+    for weight in sorted_weights {
+        if total_weight >= custodian_quorum as u32 {
+            break;
+        }
+        total_weight += weight as u32;
+        signatures += 1;
+    }
+    signatures
 }
 
 impl VaultManager {
     pub fn calculate_unlocking_fee(&self, params: UnlockingFeeParams) -> Amount {
-        let witness_cost = ESTIMATE_SIGNATURE_COST * params.quorum as u64
-            + ESTIMATE_ADDITIONAL_P2TR_SCRIPT_PATH_COST;
+        // 16 vbytes for a 64 bytes signature, 17 with the sighash type appended
+        let signature_cost = (schnorr_signature_size(params.sighash_type) as u64).div_ceil(4);
+        let witness_cost =
+            signature_cost * params.quorum as u64 + ESTIMATE_ADDITIONAL_P2TR_SCRIPT_PATH_COST;
         let inputs_cost = (P2TR_INPUT_SIZE + witness_cost) * params.n_inputs;
         let outputs_cost = P2TR_OUTPUT_SIZE * params.n_outputs;
         let fee = (P2TR_BUFFER_SIZE + inputs_cost + outputs_cost) * params.fee_rate;
         bitcoin::Amount::from_sat(fee)
     }
 
+    /// Fee of a transaction whose inputs are all spent through a script path with a witness of
+    /// `witness_weight`.
+    pub fn calculate_script_path_fee(&self, params: ScriptPathFeeParams) -> Amount {
+        let input_size = TX_INPUT_BASE_SIZE + params.witness_weight.div_ceil(4);
+        let inputs_cost = input_size * params.n_inputs;
+        let fee = (P2TR_BUFFER_SIZE + inputs_cost + params.outputs_size) * params.fee_rate;
+        Amount::from_sat(fee)
+    }
+
     pub fn distribute_fee(
         &self,
         unsigned_tx: &mut Transaction,
@@ -0,0 +1,328 @@
+use std::{cmp::Reverse, collections::BTreeMap};
+
+use bitcoin::{
+    ecdsa,
+    hashes::Hash,
+    opcodes::all::OP_CHECKMULTISIG,
+    psbt::OutputType,
+    script::{Builder, Instruction, PushBytesBuf},
+    sighash::SighashCache,
+    taproot::ControlBlock,
+    Psbt, PublicKey, Script, ScriptBuf, TapLeafHash, Transaction, Witness, XOnlyPublicKey,
+};
+
+use super::{
+    get_global_secp, leaf_signing_status, verify_tap_key_sig, AliasPushBytes, InputSigningStatus,
+    Utils, VaultBranch,
+};
+
+/// Status of taproot input `index` and its final witness when it can be finalized.
+///
+/// A valid key path signature is always the smallest witness. Otherwise the satisfied leaf
+/// with the smallest witness is picked, or `leaf_hash` only when set. When no leaf is
+/// satisfied, the status is the one of the leaf with the most valid signatures.
+pub(crate) fn plan_input_finalization(
+    psbt: &Psbt,
+    index: usize,
+    leaf_hash: Option<TapLeafHash>,
+    cache: &mut SighashCache<&Transaction>,
+) -> (InputSigningStatus, Option<Witness>) {
+    let input = &psbt.inputs[index];
+
+    if input.final_script_witness.is_some() {
+        let mut status = InputSigningStatus::new(index);
+        status.quorum_met = true;
+        status.finalized = true;
+        return (status, None);
+    }
+
+    if leaf_hash.is_none() {
+        if let Some(sig) = input
+            .tap_key_sig
+            .filter(|sig| verify_tap_key_sig(psbt, index, sig, cache))
+        {
+            let mut status = InputSigningStatus::new(index);
+            status.quorum_met = true;
+            return (status, Some(Witness::from_slice(&[sig.to_vec()])));
+        }
+    }
+
+    let candidates = input
+        .tap_scripts
+        .iter()
+        .map(|(control_block, (script, version))| {
+            (
+                control_block,
+                script,
+                TapLeafHash::from_script(script, *version),
+            )
+        })
+        .filter(|(_, _, hash)| leaf_hash.is_none_or(|leaf_hash| *hash == leaf_hash))
+        .map(|(control_block, script, hash)| {
+            let status = leaf_signing_status(psbt, index, script, hash, cache);
+            let witness = status
+                .quorum_met
+                .then(|| tap_leaf_witness(psbt, index, script, control_block, &status));
+            (control_block.size(), status, witness)
+        })
+        .collect::<Vec<_>>();
+
+    let satisfied = candidates
+        .iter()
+        .filter_map(|(size, status, witness)| witness.as_ref().map(|w| (size, status, w)))
+        .min_by_key(|(size, _, witness)| (witness.size(), **size));
+    if let Some((_, status, witness)) = satisfied {
+        return (status.clone(), Some(witness.clone()));
+    }
+
+    let status = candidates
+        .into_iter()
+        .min_by_key(|(size, status, _)| (Reverse(status.signed.len()), *size))
+        .map(|(_, status, _)| status)
+        .unwrap_or_else(|| InputSigningStatus::new(index));
+    (status, None)
+}
+
+/// The script path witness of a satisfied leaf: the valid signatures and preimages at the
+/// position of their key or hash, an empty element for the keys that did not sign, then the
+/// script and the control block.
+///
+/// A miniscript custodian leaf only gets the first `custodian_quorum` custodian signatures,
+/// `OP_NUMEQUAL` fails with more.
+fn tap_leaf_witness(
+    psbt: &Psbt,
+    index: usize,
+    script: &ScriptBuf,
+    control_block: &ControlBlock,
+    status: &InputSigningStatus,
+) -> Witness {
+    let input = &psbt.inputs[index];
+    let positions = psbt.calculate_push_bytes_positions(script);
+    let mut raw_witness = vec![Vec::new(); positions.len()];
+
+    let branch = VaultBranch::from_script(script).ok();
+    let exact_quorum = branch
+        .as_ref()
+        .and_then(VaultBranch::exact_custodian_quorum);
+    let mut n_custodian_sigs = 0;
+
+    if let Some(leaf_hash) = status.leaf_hash {
+        for key in &status.signed {
+            if let Some((custodian_pubkeys, custodian_quorum)) = exact_quorum {
+                if custodian_pubkeys.contains(key) {
+                    if n_custodian_sigs == custodian_quorum {
+                        continue;
+                    }
+                    n_custodian_sigs += 1;
+                }
+            }
+            if let (Some(pos), Some(sig)) = (
+                psbt.get_push_bytes_position(&positions, key),
+                input.tap_script_sigs.get(&(*key, leaf_hash)),
+            ) {
+                raw_witness[pos] = sig.to_vec();
+            }
+        }
+    }
+
+    for (hash, preimage) in input.sha256_preimages.iter() {
+        if let Some(pos) = positions.get(&AliasPushBytes::from(hash.to_byte_array().to_vec())) {
+            raw_witness[*pos] = preimage.clone();
+        }
+    }
+
+    let mut witness = Witness::new();
+    for element in raw_witness {
+        witness.push(element);
+    }
+    witness.push(script.as_bytes());
+    witness.push(control_block.serialize());
+    witness
+}
+
+/// Final `script_sig` and witness of a non-taproot input, either may be empty.
+pub(crate) type EcdsaSatisfaction = (ScriptBuf, Witness);
+
+/// Status of non-taproot input `index` and its final `script_sig` and witness when it can be
+/// finalized, following the script type of its utxo.
+///
+/// Single key, P2PKH and `OP_CHECKMULTISIG` scripts are supported, bare or wrapped in P2SH,
+/// P2WSH or P2SH-P2WSH, as well as P2WPKH and P2SH-P2WPKH. Only the partial signatures that
+/// verify against the sighash of the input are used.
+pub(crate) fn plan_ecdsa_finalization(
+    psbt: &Psbt,
+    index: usize,
+    cache: &mut SighashCache<&Transaction>,
+) -> (InputSigningStatus, Option<EcdsaSatisfaction>) {
+    let input = &psbt.inputs[index];
+    let mut status = InputSigningStatus::new(index);
+
+    if input.final_script_sig.is_some() || input.final_script_witness.is_some() {
+        status.quorum_met = true;
+        status.finalized = true;
+        return (status, None);
+    }
+
+    let (Ok(output_type), Ok(utxo)) = (Utils::output_type(psbt, index), psbt.spend_utxo(index))
+    else {
+        return (status, None);
+    };
+    let spk = &utxo.script_pubkey;
+
+    let sighash = psbt.sighash_ecdsa(index, cache).ok();
+    let mut sigs = BTreeMap::new();
+    for (key, sig) in input.partial_sigs.iter() {
+        let valid = sighash.is_some_and(|(msg, sighash_type)| {
+            sig.sighash_type == sighash_type
+                && get_global_secp()
+                    .verify_ecdsa(&msg, &sig.signature, &key.inner)
+                    .is_ok()
+        });
+        if valid {
+            sigs.insert(*key, *sig);
+            status.signed.push(key.inner.into());
+        } else {
+            status.invalid.push(key.inner.into());
+        }
+    }
+
+    let redeem_script = input
+        .redeem_script
+        .as_ref()
+        .filter(|redeem| ScriptBuf::new_p2sh(&redeem.script_hash()) == *spk);
+    let witness_script = input.witness_script.as_ref().filter(|witness_script| {
+        let p2wsh = ScriptBuf::new_p2wsh(&witness_script.wscript_hash());
+        p2wsh == *spk || Some(&p2wsh) == redeem_script
+    });
+
+    let satisfaction = match output_type {
+        OutputType::Bare => satisfy(psbt, index, spk, &sigs, &mut status)
+            .and_then(|stack| Some((script_sig(stack)?, Witness::new()))),
+        OutputType::Sh => redeem_script.and_then(|redeem| {
+            let mut stack = satisfy(psbt, index, redeem, &sigs, &mut status)?;
+            stack.push(redeem.to_bytes());
+            Some((script_sig(stack)?, Witness::new()))
+        }),
+        OutputType::Wpkh => spk.p2wpkh_script_code().and_then(|code| {
+            let stack = satisfy(psbt, index, &code, &sigs, &mut status)?;
+            Some((ScriptBuf::new(), Witness::from_slice(&stack)))
+        }),
+        OutputType::ShWpkh => redeem_script.and_then(|redeem| {
+            let code = redeem.p2wpkh_script_code()?;
+            let stack = satisfy(psbt, index, &code, &sigs, &mut status)?;
+            Some((
+                script_sig(vec![redeem.to_bytes()])?,
+                Witness::from_slice(&stack),
+            ))
+        }),
+        OutputType::Wsh | OutputType::ShWsh => witness_script.and_then(|witness_script| {
+            let mut stack = satisfy(psbt, index, witness_script, &sigs, &mut status)?;
+            stack.push(witness_script.to_bytes());
+            let script_sig = match redeem_script {
+                Some(redeem) if output_type == OutputType::ShWsh => {
+                    script_sig(vec![redeem.to_bytes()])?
+                }
+                _ => ScriptBuf::new(),
+            };
+            Some((script_sig, Witness::from_slice(&stack)))
+        }),
+        _ => None,
+    };
+
+    status.quorum_met = satisfaction.is_some();
+    (status, satisfaction)
+}
+
+/// The stack satisfying `script` with the signatures of `sigs`, reporting the required and
+/// missing keys in `status`.
+fn satisfy(
+    psbt: &Psbt,
+    index: usize,
+    script: &Script,
+    sigs: &BTreeMap<PublicKey, ecdsa::Signature>,
+    status: &mut InputSigningStatus,
+) -> Option<Vec<Vec<u8>>> {
+    if let Some(key) = script.p2pk_public_key() {
+        status.quorum = Some(1);
+        let Some(sig) = sigs.get(&key) else {
+            status.missing.push(key.inner.into());
+            return None;
+        };
+        status.weight = 1;
+        return Some(vec![sig.to_vec()]);
+    }
+
+    if script.is_p2pkh() {
+        status.quorum = Some(1);
+        let hash = &script.as_bytes()[3..23];
+        let Some((key, sig)) = sigs
+            .iter()
+            .find(|(key, _)| key.pubkey_hash().as_byte_array() == hash)
+        else {
+            // The key is only known through its BIP32 origin
+            status.missing.extend(
+                psbt.inputs[index]
+                    .bip32_derivation
+                    .keys()
+                    .filter(|key| PublicKey::new(**key).pubkey_hash().as_byte_array() == hash)
+                    .map(|key| XOnlyPublicKey::from(*key)),
+            );
+            return None;
+        };
+        status.weight = 1;
+        return Some(vec![sig.to_vec(), key.to_bytes()]);
+    }
+
+    let (quorum, keys) = multisig(script)?;
+    status.quorum = Some(quorum as u8);
+    status.missing = keys
+        .iter()
+        .filter(|key| !sigs.contains_key(key))
+        .map(|key| key.inner.into())
+        .collect();
+
+    // The signatures in the order of their keys, the dummy element first
+    let signatures: Vec<Vec<u8>> = keys
+        .iter()
+        .filter_map(|key| sigs.get(key))
+        .take(quorum)
+        .map(|sig| sig.to_vec())
+        .collect();
+    status.weight = signatures.len() as u16;
+    if signatures.len() < quorum {
+        return None;
+    }
+    Some(std::iter::once(vec![]).chain(signatures).collect())
+}
+
+/// Reads `<m> <key>... <n> OP_CHECKMULTISIG`.
+fn multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    if !script.is_multisig() {
+        return None;
+    }
+    let instructions: Vec<Instruction> = script.instructions().collect::<Result<_, _>>().ok()?;
+    let [m, keys @ .., _, checkmultisig] = instructions.as_slice() else {
+        return None;
+    };
+    if checkmultisig.opcode() != Some(OP_CHECKMULTISIG) {
+        return None;
+    }
+
+    let keys = keys
+        .iter()
+        .map(|instruction| {
+            instruction
+                .push_bytes()
+                .and_then(|bytes| PublicKey::from_slice(bytes.as_bytes()).ok())
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some((usize::try_from(m.script_num()?).ok()?, keys))
+}
+
+fn script_sig(stack: Vec<Vec<u8>>) -> Option<ScriptBuf> {
+    let mut builder = Builder::new();
+    for element in stack {
+        builder = builder.push_slice(PushBytesBuf::try_from(element).ok()?);
+    }
+    Some(builder.into_script())
+}
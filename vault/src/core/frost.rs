@@ -0,0 +1,591 @@
+use std::collections::BTreeMap;
+
+use bitcoin::{
+    hashes::Hash,
+    key::TapTweak,
+    secp256k1::{schnorr, Message, PublicKey},
+    sighash::SighashCache,
+    taproot, Psbt, TapSighashType, XOnlyPublicKey,
+};
+use frost_secp256k1_tr::{
+    keys::{dkg, IdentifierList, KeyPackage, PublicKeyPackage},
+    round1::{SigningCommitments, SigningNonces},
+    round2::SignatureShare,
+    Identifier, SigningPackage,
+};
+use rand_core::OsRng;
+
+use super::{get_global_secp, CoreError, FrostSigning, Utils, VaultManager};
+
+/// Index of a FROST participant, starting at 1.
+pub type FrostIdentifier = u16;
+
+fn frost_error(error: frost_secp256k1_tr::Error) -> CoreError {
+    CoreError::Frost(error.to_string())
+}
+
+fn frost_identifier(identifier: FrostIdentifier) -> Result<Identifier, CoreError> {
+    Identifier::try_from(identifier).map_err(frost_error)
+}
+
+/// The participant index of an identifier, the scalar of the index in big endian.
+fn participant(identifier: &Identifier) -> Option<FrostIdentifier> {
+    let bytes = identifier.serialize();
+    let (high, low) = bytes.split_at(bytes.len().checked_sub(2)?);
+    match (high.iter().all(|byte| *byte == 0), low) {
+        (true, [a, b]) => Some(u16::from_be_bytes([*a, *b])),
+        _ => None,
+    }
+}
+
+/// Names the participant blamed by the error, if any.
+fn blame(
+    error: frost_secp256k1_tr::Error,
+    culprit: impl Fn(FrostIdentifier) -> CoreError,
+) -> CoreError {
+    match error.culprit().as_ref().and_then(participant) {
+        Some(identifier) => culprit(identifier),
+        None => frost_error(error),
+    }
+}
+
+fn public_key(
+    serialized: Result<Vec<u8>, frost_secp256k1_tr::Error>,
+) -> Result<PublicKey, CoreError> {
+    PublicKey::from_slice(&serialized.map_err(frost_error)?)
+        .map_err(|e| CoreError::Frost(e.to_string()))
+}
+
+/// The public side of a FROST key, known to every participant and the coordinator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrostPublicKeyPackage {
+    threshold: u16,
+    group_key: PublicKey,
+    verifying_shares: BTreeMap<FrostIdentifier, PublicKey>,
+    package: PublicKeyPackage,
+}
+
+impl FrostPublicKeyPackage {
+    fn new(threshold: u16, package: PublicKeyPackage) -> Result<Self, CoreError> {
+        let verifying_shares = package
+            .verifying_shares()
+            .iter()
+            .map(|(identifier, share)| {
+                let identifier = participant(identifier)
+                    .ok_or(CoreError::Frost("unknown participant".to_string()))?;
+                Ok((identifier, public_key(share.serialize())?))
+            })
+            .collect::<Result<_, CoreError>>()?;
+
+        Ok(Self {
+            threshold,
+            group_key: public_key(package.verifying_key().serialize())?,
+            verifying_shares,
+            package,
+        })
+    }
+
+    /// Number of signature shares a signature needs.
+    pub fn threshold(&self) -> u16 {
+        self.threshold
+    }
+
+    /// The x-only group key, to be used as [`TaprootInternalKey::FrostGroupKey`](super::TaprootInternalKey::FrostGroupKey).
+    pub fn group_key(&self) -> XOnlyPublicKey {
+        self.group_key.x_only_public_key().0
+    }
+
+    /// `s_i·G` of participant `identifier`, used to verify its signature shares.
+    pub fn verifying_share(&self, identifier: FrostIdentifier) -> Option<&PublicKey> {
+        self.verifying_shares.get(&identifier)
+    }
+
+    pub fn verifying_shares(&self) -> &BTreeMap<FrostIdentifier, PublicKey> {
+        &self.verifying_shares
+    }
+
+    /// The big endian `u16` threshold followed by the `PublicKeyPackage` encoding.
+    pub fn serialize(&self) -> Result<Vec<u8>, CoreError> {
+        let package = self.package.serialize().map_err(frost_error)?;
+        Ok([&self.threshold.to_be_bytes()[..], &package].concat())
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, CoreError> {
+        let (threshold, package) = bytes
+            .split_first_chunk::<2>()
+            .ok_or(CoreError::Frost("missing threshold".to_string()))?;
+        Self::new(
+            u16::from_be_bytes(*threshold),
+            PublicKeyPackage::deserialize(package).map_err(frost_error)?,
+        )
+    }
+}
+
+/// The secret share of a participant, together with the public key package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrostKeyShare {
+    identifier: FrostIdentifier,
+    key_package: KeyPackage,
+    public_key_package: FrostPublicKeyPackage,
+}
+
+impl FrostKeyShare {
+    fn new(
+        key_package: KeyPackage,
+        public_key_package: FrostPublicKeyPackage,
+    ) -> Result<Self, CoreError> {
+        let identifier = participant(key_package.identifier())
+            .ok_or(CoreError::Frost("unknown participant".to_string()))?;
+        Ok(Self {
+            identifier,
+            key_package,
+            public_key_package,
+        })
+    }
+
+    pub fn identifier(&self) -> FrostIdentifier {
+        self.identifier
+    }
+
+    pub fn public_key_package(&self) -> &FrostPublicKeyPackage {
+        &self.public_key_package
+    }
+
+    /// The `KeyPackage` encoding, the public key package is stored on its own.
+    pub fn serialize(&self) -> Result<Vec<u8>, CoreError> {
+        self.key_package.serialize().map_err(frost_error)
+    }
+
+    /// Loads a share stored with [`Self::serialize`], it must belong to `public_key_package`.
+    pub fn deserialize(
+        bytes: &[u8],
+        public_key_package: FrostPublicKeyPackage,
+    ) -> Result<Self, CoreError> {
+        let key_package = KeyPackage::deserialize(bytes).map_err(frost_error)?;
+        let package = &public_key_package.package;
+        if key_package.verifying_key() != package.verifying_key()
+            || *key_package.min_signers() != public_key_package.threshold
+            || package.verifying_shares().get(key_package.identifier())
+                != Some(key_package.verifying_share())
+        {
+            return Err(CoreError::Frost(
+                "key share does not belong to the public key package".to_string(),
+            ));
+        }
+        Self::new(key_package, public_key_package)
+    }
+}
+
+/// Round 1 output of a DKG participant, broadcast to every other participant.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrostDkgCommitment {
+    identifier: FrostIdentifier,
+    package: dkg::round1::Package,
+}
+
+impl FrostDkgCommitment {
+    pub fn identifier(&self) -> FrostIdentifier {
+        self.identifier
+    }
+
+    pub fn serialize(&self) -> Result<Vec<u8>, CoreError> {
+        self.package.serialize().map_err(frost_error)
+    }
+
+    pub fn deserialize(identifier: FrostIdentifier, bytes: &[u8]) -> Result<Self, CoreError> {
+        Ok(Self {
+            identifier,
+            package: dkg::round1::Package::deserialize(bytes).map_err(frost_error)?,
+        })
+    }
+}
+
+/// Round 1 secret of a DKG participant, consumed by [`frost_dkg_round2`].
+pub struct FrostDkgSecret {
+    identifier: FrostIdentifier,
+    package: dkg::round1::SecretPackage,
+}
+
+/// Round 2 secret of a DKG participant, consumed by [`frost_dkg_finalize`].
+pub struct FrostDkgRound2Secret {
+    identifier: FrostIdentifier,
+    package: dkg::round2::SecretPackage,
+}
+
+/// Round 2 share of a DKG participant for another one, to be sent on a confidential channel.
+#[derive(Clone)]
+pub struct FrostDkgShare(dkg::round2::Package);
+
+impl FrostDkgShare {
+    pub fn serialize(&self) -> Result<Vec<u8>, CoreError> {
+        self.0.serialize().map_err(frost_error)
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, CoreError> {
+        dkg::round2::Package::deserialize(bytes)
+            .map(Self)
+            .map_err(frost_error)
+    }
+}
+
+/// The secret nonces of one input, consumed by [`FrostSigning::sign_frost_share`].
+pub struct FrostSecNonce(SigningNonces);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrostNonceCommitment(SigningCommitments);
+
+impl FrostNonceCommitment {
+    pub fn serialize(&self) -> Result<Vec<u8>, CoreError> {
+        self.0.serialize().map_err(frost_error)
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self, CoreError> {
+        SigningCommitments::deserialize(bytes)
+            .map(Self)
+            .map_err(frost_error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrostSignatureShare(SignatureShare);
+
+impl FrostSignatureShare {
+    pub fn serialize(&self) -> Vec<u8> {
+        self.0.serialize()
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, CoreError> {
+        SignatureShare::deserialize(bytes)
+            .map(Self)
+            .map_err(frost_error)
+    }
+}
+
+/// Splits a fresh group key into `n_participants` shares, any `threshold` of them can sign.
+///
+/// The dealer learns the group secret, prefer [`frost_dkg_round1`] when no party is trusted.
+pub fn frost_trusted_dealer_keygen(
+    threshold: u16,
+    n_participants: u16,
+) -> Result<Vec<FrostKeyShare>, CoreError> {
+    let (secret_shares, package) = frost_secp256k1_tr::keys::generate_with_dealer(
+        n_participants,
+        threshold,
+        IdentifierList::Default,
+        OsRng,
+    )
+    .map_err(frost_error)?;
+    let public_key_package = FrostPublicKeyPackage::new(threshold, package)?;
+
+    secret_shares
+        .into_values()
+        .map(|secret_share| {
+            let key_package = KeyPackage::try_from(secret_share).map_err(frost_error)?;
+            FrostKeyShare::new(key_package, public_key_package.clone())
+        })
+        .collect()
+}
+
+/// Round 1 of the Pedersen DKG: commits to a random polynomial. The commitment is broadcast,
+/// the secret is kept for round 2.
+pub fn frost_dkg_round1(
+    identifier: FrostIdentifier,
+    threshold: u16,
+    n_participants: u16,
+) -> Result<(FrostDkgSecret, FrostDkgCommitment), CoreError> {
+    if identifier == 0 || identifier > n_participants {
+        return Err(CoreError::Frost(format!(
+            "invalid identifier {}",
+            identifier
+        )));
+    }
+
+    let (secret, package) = dkg::part1(
+        frost_identifier(identifier)?,
+        n_participants,
+        threshold,
+        OsRng,
+    )
+    .map_err(frost_error)?;
+
+    Ok((
+        FrostDkgSecret {
+            identifier,
+            package: secret,
+        },
+        FrostDkgCommitment {
+            identifier,
+            package,
+        },
+    ))
+}
+
+/// The round 1 packages of every participant but `identifier`.
+fn dkg_round1_packages(
+    identifier: FrostIdentifier,
+    commitments: &[FrostDkgCommitment],
+) -> Result<BTreeMap<Identifier, dkg::round1::Package>, CoreError> {
+    commitments
+        .iter()
+        .filter(|commitment| commitment.identifier != identifier)
+        .map(|commitment| {
+            Ok((
+                frost_identifier(commitment.identifier)?,
+                commitment.package.clone(),
+            ))
+        })
+        .collect()
+}
+
+/// Round 2 of the DKG: checks the commitments of every participant, own included, and returns
+/// the share to send privately to each other participant.
+pub fn frost_dkg_round2(
+    secret: FrostDkgSecret,
+    commitments: &[FrostDkgCommitment],
+) -> Result<
+    (
+        FrostDkgRound2Secret,
+        BTreeMap<FrostIdentifier, FrostDkgShare>,
+    ),
+    CoreError,
+> {
+    let round1_packages = dkg_round1_packages(secret.identifier, commitments)?;
+    let (package, shares) = dkg::part2(secret.package, &round1_packages)
+        .map_err(|e| blame(e, CoreError::InvalidFrostDkgCommitment))?;
+
+    let shares = shares
+        .into_iter()
+        .map(|(identifier, share)| {
+            let identifier = participant(&identifier)
+                .ok_or(CoreError::Frost("unknown participant".to_string()))?;
+            Ok((identifier, FrostDkgShare(share)))
+        })
+        .collect::<Result<_, CoreError>>()?;
+
+    Ok((
+        FrostDkgRound2Secret {
+            identifier: secret.identifier,
+            package,
+        },
+        shares,
+    ))
+}
+
+/// Checks the shares received from the other participants against their commitments and
+/// derives the key share of this participant.
+pub fn frost_dkg_finalize(
+    secret: FrostDkgRound2Secret,
+    commitments: &[FrostDkgCommitment],
+    received_shares: &BTreeMap<FrostIdentifier, FrostDkgShare>,
+) -> Result<FrostKeyShare, CoreError> {
+    let round1_packages = dkg_round1_packages(secret.identifier, commitments)?;
+    let round2_packages = commitments
+        .iter()
+        .filter(|commitment| commitment.identifier != secret.identifier)
+        .map(|commitment| {
+            let share = received_shares
+                .get(&commitment.identifier)
+                .ok_or(CoreError::InvalidFrostDkgShare(commitment.identifier))?;
+            Ok((frost_identifier(commitment.identifier)?, share.0.clone()))
+        })
+        .collect::<Result<_, CoreError>>()?;
+
+    let (key_package, package) = dkg::part3(&secret.package, &round1_packages, &round2_packages)
+        .map_err(|e| blame(e, CoreError::InvalidFrostDkgShare))?;
+    let public_key_package = FrostPublicKeyPackage::new(*key_package.min_signers(), package)?;
+
+    FrostKeyShare::new(key_package, public_key_package)
+}
+
+/// The FROST signing context of a key-path spend of one input.
+struct FrostInputContext {
+    message: [u8; 32],
+    sighash_type: TapSighashType,
+    /// The group key is tweaked by the merkle root of the tree, as any taproot internal key.
+    merkle_root: Option<[u8; 32]>,
+    output_key: XOnlyPublicKey,
+}
+
+impl FrostInputContext {
+    fn merkle_root(&self) -> Option<&[u8]> {
+        self.merkle_root.as_ref().map(|root| root.as_slice())
+    }
+}
+
+fn frost_input_contexts(
+    psbt: &Psbt,
+    public_key_package: &FrostPublicKeyPackage,
+) -> Result<Vec<FrostInputContext>, CoreError> {
+    let secp = get_global_secp();
+    let group_key = public_key_package.group_key();
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            if input.tap_internal_key != Some(group_key) {
+                return Err(CoreError::FrostInternalKeyMismatch(index));
+            }
+
+            let (output_key, _) = group_key.tap_tweak(secp, input.tap_merkle_root);
+            let (msg, sighash_type) = psbt
+                .sighash_taproot(index, &mut cache, None)
+                .map_err(|e| CoreError::SigningPSBTFailed(e.to_string()))?;
+
+            Ok(FrostInputContext {
+                message: *msg.as_ref(),
+                sighash_type,
+                merkle_root: input.tap_merkle_root.map(|root| root.to_byte_array()),
+                output_key: output_key.to_x_only_public_key(),
+            })
+        })
+        .collect()
+}
+
+/// The commitments of the signers per input, checking every signer sent one per input.
+fn input_commitments(
+    commitments: &BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+    public_key_package: &FrostPublicKeyPackage,
+    n_inputs: usize,
+) -> Result<Vec<BTreeMap<Identifier, SigningCommitments>>, CoreError> {
+    if commitments.len() < public_key_package.threshold as usize {
+        return Err(CoreError::Frost(format!(
+            "{} signers, the threshold is {}",
+            commitments.len(),
+            public_key_package.threshold
+        )));
+    }
+    if let Some(identifier) = commitments
+        .keys()
+        .find(|identifier| public_key_package.verifying_share(**identifier).is_none())
+    {
+        return Err(CoreError::Frost(format!("unknown signer {}", identifier)));
+    }
+    if commitments.values().any(|c| c.len() != n_inputs) {
+        return Err(CoreError::MismatchBetweenNumberOfInputsAndFrostNonces);
+    }
+
+    (0..n_inputs)
+        .map(|index| {
+            commitments
+                .iter()
+                .map(|(identifier, c)| Ok((frost_identifier(*identifier)?, c[index].0)))
+                .collect()
+        })
+        .collect()
+}
+
+impl FrostSigning for VaultManager {
+    fn generate_frost_nonces(
+        psbt: &Psbt,
+        key_share: &FrostKeyShare,
+    ) -> Result<(Vec<FrostSecNonce>, Vec<FrostNonceCommitment>), CoreError> {
+        let contexts = frost_input_contexts(psbt, &key_share.public_key_package)?;
+
+        Ok(contexts
+            .iter()
+            .map(|_| {
+                let (nonces, commitments) = frost_secp256k1_tr::round1::commit(
+                    key_share.key_package.signing_share(),
+                    &mut OsRng,
+                );
+                (FrostSecNonce(nonces), FrostNonceCommitment(commitments))
+            })
+            .unzip())
+    }
+
+    fn sign_frost_share(
+        psbt: &Psbt,
+        key_share: &FrostKeyShare,
+        sec_nonces: Vec<FrostSecNonce>,
+        commitments: &BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+    ) -> Result<Vec<FrostSignatureShare>, CoreError> {
+        let public_key_package = &key_share.public_key_package;
+        let contexts = frost_input_contexts(psbt, public_key_package)?;
+        if sec_nonces.len() != contexts.len() {
+            return Err(CoreError::MismatchBetweenNumberOfInputsAndFrostNonces);
+        }
+        let commitments = input_commitments(commitments, public_key_package, contexts.len())?;
+
+        contexts
+            .iter()
+            .zip(sec_nonces)
+            .zip(commitments)
+            .map(|((ctx, sec_nonce), commitments)| {
+                let signing_package = SigningPackage::new(commitments, &ctx.message);
+                frost_secp256k1_tr::round2::sign_with_tweak(
+                    &signing_package,
+                    &sec_nonce.0,
+                    &key_share.key_package,
+                    ctx.merkle_root(),
+                )
+                .map(FrostSignatureShare)
+                .map_err(frost_error)
+            })
+            .collect()
+    }
+
+    fn aggregate_frost_signature_shares(
+        psbt: &mut Psbt,
+        public_key_package: &FrostPublicKeyPackage,
+        commitments: &BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+        signature_shares: &BTreeMap<FrostIdentifier, Vec<FrostSignatureShare>>,
+    ) -> Result<Self::PsbtHex, CoreError> {
+        let contexts = frost_input_contexts(psbt, public_key_package)?;
+        let input_commitments = input_commitments(commitments, public_key_package, contexts.len())?;
+
+        if signature_shares.keys().ne(commitments.keys())
+            || signature_shares
+                .values()
+                .any(|shares| shares.len() != contexts.len())
+        {
+            return Err(CoreError::Frost(
+                "expected one signature share per input from every committed signer".to_string(),
+            ));
+        }
+
+        let mut signatures = Vec::with_capacity(contexts.len());
+        for (index, (ctx, commitments)) in contexts.iter().zip(input_commitments).enumerate() {
+            let shares = signature_shares
+                .iter()
+                .map(|(identifier, shares)| Ok((frost_identifier(*identifier)?, shares[index].0)))
+                .collect::<Result<_, CoreError>>()?;
+
+            // Every share is verified against the verifying share of its signer
+            let signature = frost_secp256k1_tr::aggregate_with_tweak(
+                &SigningPackage::new(commitments, &ctx.message),
+                &shares,
+                &public_key_package.package,
+                ctx.merkle_root(),
+            )
+            .map_err(|e| {
+                blame(e, |signer| {
+                    CoreError::InvalidFrostSignatureShare(index, signer)
+                })
+            })?;
+
+            let signature =
+                schnorr::Signature::from_slice(&signature.serialize().map_err(frost_error)?)
+                    .map_err(|e| CoreError::Frost(e.to_string()))?;
+            get_global_secp()
+                .verify_schnorr(
+                    &signature,
+                    &Message::from_digest(ctx.message),
+                    &ctx.output_key,
+                )
+                .map_err(|_| CoreError::Frost("aggregated signature is invalid".to_string()))?;
+
+            signatures.push(taproot::Signature {
+                signature,
+                sighash_type: ctx.sighash_type,
+            });
+        }
+
+        for (input, signature) in psbt.inputs.iter_mut().zip(signatures) {
+            input.tap_key_sig = Some(signature);
+        }
+
+        Ok(psbt.serialize())
+    }
+}
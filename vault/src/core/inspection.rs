@@ -0,0 +1,269 @@
+use std::fmt;
+
+use bitcoin::{
+    hashes::sha256, sighash::SighashCache, taproot, Psbt, ScriptBuf, TapLeafHash, XOnlyPublicKey,
+};
+use serde::Serialize;
+
+use super::{
+    get_global_secp, plan_ecdsa_finalization, plan_input_finalization, verify_tap_script_sig,
+    TapScriptSig, Utils, VaultBranch,
+};
+
+/// Signing progress of a psbt input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputSigningStatus {
+    pub input: usize,
+    /// Leaf the input would be finalized with, `None` for a key path spend.
+    pub leaf_hash: Option<TapLeafHash>,
+    /// Keys of the leaf with a valid signature.
+    pub signed: Vec<XOnlyPublicKey>,
+    /// Keys with a signature that does not verify against the leaf sighash.
+    pub invalid: Vec<XOnlyPublicKey>,
+    /// Keys of the leaf that have not signed yet, in witness order.
+    pub missing: Vec<XOnlyPublicKey>,
+    /// Custodian weight required by the leaf, `None` if the leaf is not a vault branch.
+    pub quorum: Option<u8>,
+    /// Custodian weight of the valid signatures.
+    pub weight: u16,
+    /// Preimage the hashlock of the leaf still waits for.
+    pub missing_preimage: Option<sha256::Hash>,
+    /// The custodian quorum is reached and every party of the leaf has signed.
+    pub quorum_met: bool,
+    pub finalized: bool,
+}
+
+impl InputSigningStatus {
+    pub(crate) fn new(input: usize) -> Self {
+        Self {
+            input,
+            leaf_hash: None,
+            signed: vec![],
+            invalid: vec![],
+            missing: vec![],
+            quorum: None,
+            weight: 0,
+            missing_preimage: None,
+            quorum_met: false,
+            finalized: false,
+        }
+    }
+}
+
+impl fmt::Display for InputSigningStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "input {}", self.input)?;
+        if let Some(leaf_hash) = self.leaf_hash {
+            write!(f, " (leaf {})", leaf_hash)?;
+        }
+        if let Some(quorum) = self.quorum {
+            write!(f, ": weight {} of {}", self.weight, quorum)?;
+        }
+        if !self.missing.is_empty() {
+            let missing: Vec<String> = self.missing.iter().map(|key| key.to_string()).collect();
+            write!(f, ", missing signatures of {}", missing.join(" "))?;
+        }
+        if let Some(hash) = self.missing_preimage {
+            write!(f, ", missing preimage of {}", hash)?;
+        }
+        Ok(())
+    }
+}
+
+/// Signing progress of a psbt, `ready` once every input can be finalized.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PsbtSigningStatus {
+    pub inputs: Vec<InputSigningStatus>,
+    pub ready: bool,
+}
+
+/// Reports for every input of `psbt` which signatures of the leaf picked by the finalizer are
+/// valid, which keys are still missing and whether the quorum of the leaf is met.
+pub fn inspect_signing_status(psbt: &Psbt) -> PsbtSigningStatus {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let inputs: Vec<InputSigningStatus> = (0..psbt.inputs.len())
+        .map(|index| inspect_input(psbt, index, &mut cache))
+        .collect();
+    let ready = !inputs.is_empty() && inputs.iter().all(|input| input.quorum_met);
+
+    PsbtSigningStatus { inputs, ready }
+}
+
+fn inspect_input(
+    psbt: &Psbt,
+    index: usize,
+    cache: &mut SighashCache<&bitcoin::Transaction>,
+) -> InputSigningStatus {
+    if psbt.is_taproot_input(index) {
+        plan_input_finalization(psbt, index, None, cache).0
+    } else {
+        plan_ecdsa_finalization(psbt, index, cache).0
+    }
+}
+
+/// Signing progress of `index` through the leaf `script`, only counting valid signatures.
+pub(crate) fn leaf_signing_status(
+    psbt: &Psbt,
+    index: usize,
+    script: &ScriptBuf,
+    leaf_hash: TapLeafHash,
+    cache: &mut SighashCache<&bitcoin::Transaction>,
+) -> InputSigningStatus {
+    let input = &psbt.inputs[index];
+    let mut status = InputSigningStatus::new(index);
+    status.leaf_hash = Some(leaf_hash);
+
+    for ((key, _), sig) in input
+        .tap_script_sigs
+        .iter()
+        .filter(|((_, hash), _)| *hash == leaf_hash)
+    {
+        let tap_script_sig = TapScriptSig::new((*key, leaf_hash), *sig);
+        match verify_tap_script_sig(psbt, index as u64, &tap_script_sig, cache) {
+            Ok(()) => status.signed.push(*key),
+            Err(_) => status.invalid.push(*key),
+        }
+    }
+
+    // Leaves that are not vault branches need a signature of every key they push
+    let Ok(branch) = VaultBranch::from_script(script) else {
+        status.missing = leaf_keys(psbt, script)
+            .into_iter()
+            .filter(|key| !status.signed.contains(key))
+            .collect();
+        status.quorum_met = status.missing.is_empty() && !status.signed.is_empty();
+        return status;
+    };
+
+    if let VaultBranch::Hashlock { payment_hash, .. } = &branch {
+        if !input.sha256_preimages.contains_key(payment_hash) {
+            status.missing_preimage = Some(*payment_hash);
+        }
+    }
+
+    let (parties, custodians, quorum) = requirement(&branch);
+    status.quorum = Some(quorum);
+    status.weight = custodians
+        .iter()
+        .filter(|(key, _)| status.signed.contains(key))
+        .map(|(_, weight)| u16::from(*weight))
+        .sum();
+    status.quorum_met = status.weight >= u16::from(quorum)
+        && parties.iter().all(|party| status.signed.contains(party))
+        && status.missing_preimage.is_none();
+
+    let positions = psbt.calculate_push_bytes_positions(script);
+    let mut missing: Vec<(usize, XOnlyPublicKey)> = parties
+        .into_iter()
+        .chain(custodians.into_iter().map(|(key, _)| key))
+        .filter(|key| !status.signed.contains(key))
+        .filter_map(|key| {
+            psbt.get_push_bytes_position(&positions, &key)
+                .map(|pos| (pos, key))
+        })
+        .collect();
+    missing.sort();
+    status.missing = missing.into_iter().map(|(_, key)| key).collect();
+
+    status
+}
+
+/// The parties that must all sign, the custodians with their weight and the custodian quorum.
+fn requirement(branch: &VaultBranch) -> (Vec<XOnlyPublicKey>, Vec<(XOnlyPublicKey, u8)>, u8) {
+    let unweighted = |keys: &[XOnlyPublicKey]| keys.iter().map(|key| (*key, 1)).collect();
+    let weighted = |keys: &[XOnlyPublicKey], weights: &[u8]| {
+        keys.iter().copied().zip(weights.iter().copied()).collect()
+    };
+
+    match branch {
+        VaultBranch::TwoParty { x, y } => (vec![*x, *y], vec![], 0),
+        VaultBranch::CustodianAndParty {
+            party,
+            custodian_pubkeys,
+            custodian_quorum,
+            ..
+        } => (
+            vec![*party],
+            unweighted(custodian_pubkeys),
+            *custodian_quorum,
+        ),
+        VaultBranch::CustodianOnly {
+            custodian_pubkeys,
+            custodian_quorum,
+            ..
+        }
+        | VaultBranch::CustodianWithLockTime {
+            custodian_pubkeys,
+            custodian_quorum,
+            ..
+        } => (vec![], unweighted(custodian_pubkeys), *custodian_quorum),
+        VaultBranch::WeightedCustodianAndParty {
+            party,
+            custodian_pubkeys,
+            custodian_weights,
+            custodian_quorum,
+        } => (
+            vec![*party],
+            weighted(custodian_pubkeys, custodian_weights),
+            *custodian_quorum,
+        ),
+        VaultBranch::WeightedCustodianOnly {
+            custodian_pubkeys,
+            custodian_weights,
+            custodian_quorum,
+        } => (
+            vec![],
+            weighted(custodian_pubkeys, custodian_weights),
+            *custodian_quorum,
+        ),
+        VaultBranch::PartyWithSequenceVerification { party, .. }
+        | VaultBranch::PartyWithLockTimeVerification { party, .. }
+        | VaultBranch::Hashlock { party, .. } => (vec![*party], vec![], 0),
+    }
+}
+
+/// The x-only keys pushed by a leaf that is not a vault branch, in witness order.
+fn leaf_keys(psbt: &Psbt, script: &ScriptBuf) -> Vec<XOnlyPublicKey> {
+    let positions = psbt.calculate_push_bytes_positions(script);
+    let mut keys: Vec<(usize, XOnlyPublicKey)> = script
+        .instructions()
+        .flatten()
+        .filter_map(|ins| {
+            ins.push_bytes()
+                .and_then(|bytes| XOnlyPublicKey::from_slice(bytes.as_bytes()).ok())
+        })
+        .filter_map(|key| {
+            psbt.get_push_bytes_position(&positions, &key)
+                .map(|pos| (pos, key))
+        })
+        .collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+pub(crate) fn verify_tap_key_sig(
+    psbt: &Psbt,
+    index: usize,
+    sig: &taproot::Signature,
+    cache: &mut SighashCache<&bitcoin::Transaction>,
+) -> bool {
+    let Some(output_key) = psbt.inputs[index]
+        .witness_utxo
+        .as_ref()
+        .filter(|utxo| utxo.script_pubkey.is_p2tr())
+        .and_then(|utxo| XOnlyPublicKey::from_slice(&utxo.script_pubkey.as_bytes()[2..]).ok())
+    else {
+        return false;
+    };
+
+    match psbt.sighash_taproot(index, cache, None) {
+        Ok((msg, sighash_type)) => {
+            sig.sighash_type == sighash_type
+                && get_global_secp()
+                    .verify_schnorr(&sig.signature, &msg, &output_key)
+                    .is_ok()
+        }
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use bitcoin::{
+    bip32::{DerivationPath, Fingerprint, KeySource, Xpub},
+    key::Secp256k1,
+    secp256k1::Verification,
+    XOnlyPublicKey,
+};
+
+use super::CoreError;
+
+/// BIP32 origins of the vault participant keys, written to the vault PSBTs
+/// (`PSBT_IN_TAP_BIP32_DERIVATION`, `PSBT_IN_BIP32_DERIVATION` for segwit v0 funding inputs,
+/// and `PSBT_GLOBAL_XPUB`) so hardware wallets can find the key they sign with.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyOrigins {
+    keys: BTreeMap<XOnlyPublicKey, KeySource>,
+    xpubs: BTreeMap<Xpub, KeySource>,
+}
+
+impl KeyOrigins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the master key fingerprint and the full derivation path of a participant key.
+    pub fn insert_key(
+        &mut self,
+        key: XOnlyPublicKey,
+        fingerprint: Fingerprint,
+        path: DerivationPath,
+    ) -> &mut Self {
+        self.keys.insert(key, (fingerprint, path));
+        self
+    }
+
+    /// Records an account xpub for `PSBT_GLOBAL_XPUB`, `path` being its path from the master key.
+    pub fn insert_xpub(
+        &mut self,
+        xpub: Xpub,
+        fingerprint: Fingerprint,
+        path: DerivationPath,
+    ) -> &mut Self {
+        self.xpubs.insert(xpub, (fingerprint, path));
+        self
+    }
+
+    /// Derives the participant key at `child` below a recorded xpub and records its origin.
+    pub fn derive_key<C: Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        xpub: &Xpub,
+        child: &DerivationPath,
+    ) -> Result<XOnlyPublicKey, CoreError> {
+        let (fingerprint, path) = self
+            .xpubs
+            .get(xpub)
+            .cloned()
+            .ok_or_else(|| CoreError::InvalidParams(format!("unknown xpub {}", xpub)))?;
+
+        let key = xpub
+            .derive_pub(secp, child)
+            .map_err(|e| CoreError::InvalidParams(e.to_string()))?
+            .to_x_only_pub();
+
+        self.keys.insert(key, (fingerprint, path.extend(child)));
+        Ok(key)
+    }
+
+    pub fn key_source(&self, key: &XOnlyPublicKey) -> Option<&KeySource> {
+        self.keys.get(key)
+    }
+
+    pub fn xpubs(&self) -> &BTreeMap<Xpub, KeySource> {
+        &self.xpubs
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.xpubs.is_empty()
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::BTreeMap;
 
 use bitcoin::{
+    absolute,
     bip32::{DerivationPath, Fingerprint},
     key::Secp256k1,
     opcodes::all::OP_RETURN,
@@ -8,13 +9,14 @@ use bitcoin::{
     script,
     secp256k1::All,
     taproot::{LeafVersion, TaprootSpendInfo},
-    Amount, FeeRate, ScriptBuf, TapLeafHash, TapSighashType, Transaction, TxOut, XOnlyPublicKey,
+    Amount, FeeRate, Psbt, ScriptBuf, TapLeafHash, TapSighashType, Transaction, TxOut,
+    XOnlyPublicKey,
 };
 use lazy_static::lazy_static;
 
 use super::{
-    CoreError, DataScript, PreviousOutpoint, TransactionBuilder, UnlockingFeeParams,
-    UnlockingTaprootTreeType, HASH_SIZE, UNLOCKING_EMBEDDED_DATA_SCRIPT_SIZE,
+    validate_sighash_type, CoreError, DataScript, KeyOrigins, PreviousOutpoint, TransactionBuilder,
+    UnlockingFeeParams, UnlockingTaprootTreeType, HASH_SIZE, UNLOCKING_EMBEDDED_DATA_SCRIPT_SIZE,
 };
 
 lazy_static! {
@@ -41,10 +43,12 @@ pub struct UnlockingParams<'a> {
     pub tree_type: UnlockingTaprootTreeType,
     pub script: &'a ScriptBuf,
     pub rbf: bool,
+    pub lock_time: absolute::LockTime,
     pub fee_rate: u64,
     pub custodian_quorum: u8,
     pub session_sequence: u64,
     pub custodian_group_uid: [u8; HASH_SIZE],
+    pub sighash_type: Option<TapSighashType>,
 }
 
 impl VaultManager {
@@ -79,8 +83,9 @@ impl VaultManager {
         tree: &TaprootSpendInfo,
         branch: &ScriptBuf,
         keys: &[XOnlyPublicKey],
+        key_origins: Option<&KeyOrigins>,
     ) -> Vec<Input> {
-        let tap_key_origins = self.create_tap_key_origins(branch, keys);
+        let tap_key_origins = self.create_tap_key_origins(branch, keys, key_origins);
 
         let tap_scripts = self.create_tap_scripts(tree, branch);
 
@@ -91,22 +96,44 @@ impl VaultManager {
         &self,
         script: &ScriptBuf,
         keys: &[XOnlyPublicKey],
+        key_origins: Option<&KeyOrigins>,
     ) -> BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, (Fingerprint, DerivationPath))> {
         let mut tap_key_origins = BTreeMap::new();
 
         for &key in keys {
-            tap_key_origins.insert(
-                key,
-                (
-                    vec![script.tapscript_leaf_hash()],
-                    ([0u8; 4].into(), DerivationPath::default()),
-                ),
-            );
+            // Keys without a known origin keep an empty one, software signers match the key only
+            let key_source = key_origins
+                .and_then(|origins| origins.key_source(&key))
+                .cloned()
+                .unwrap_or(([0u8; 4].into(), DerivationPath::default()));
+            tap_key_origins.insert(key, (vec![script.tapscript_leaf_hash()], key_source));
         }
 
         tap_key_origins
     }
 
+    /// Sets the sighash type every input is signed with, `SIGHASH_DEFAULT` if `None`.
+    pub fn set_sighash_type(
+        &self,
+        psbt: &mut Psbt,
+        sighash_type: Option<TapSighashType>,
+    ) -> Result<(), CoreError> {
+        let sighash_type = sighash_type.unwrap_or(TapSighashType::Default);
+        validate_sighash_type(sighash_type, &psbt.unsigned_tx)?;
+
+        for input in psbt.inputs.iter_mut() {
+            input.sighash_type = Some(PsbtSighashType::from(sighash_type));
+        }
+        Ok(())
+    }
+
+    /// Writes the account xpubs of the participants to `PSBT_GLOBAL_XPUB`.
+    pub fn add_global_xpubs(&self, psbt: &mut Psbt, key_origins: Option<&KeyOrigins>) {
+        if let Some(key_origins) = key_origins {
+            psbt.xpub.extend(key_origins.xpubs().clone());
+        }
+    }
+
     fn create_tap_scripts(
         &self,
         tree: &bitcoin::taproot::TaprootSpendInfo,
@@ -244,13 +271,29 @@ impl VaultManager {
         &self,
         params: &UnlockingParams,
     ) -> Result<Transaction, CoreError> {
-        let mut tx_builder = TransactionBuilder::new(params.rbf);
+        let mut tx_builder = TransactionBuilder::new(params.rbf).with_lock_time(params.lock_time);
 
         self.add_inputs_to_builder(&mut tx_builder, params.inputs);
 
         // output[0]: indexed output (op_return)
         // output[1->n-2]: unlocking outputs
         // output[n-1]: change output
+        //
+        // With SIGHASH_SINGLE|ANYONECANPAY input i signs output i, the first unlocking outputs
+        // are paired with the inputs and the indexed output follows them.
+        let n_paired = match params.sighash_type {
+            Some(TapSighashType::SinglePlusAnyoneCanPay) => params.inputs.len(),
+            _ => 0,
+        };
+        let (paired, unpaired) = params.outputs.split_at_checked(n_paired).ok_or(
+            CoreError::SighashSingleWithoutOutput(
+                TapSighashType::SinglePlusAnyoneCanPay,
+                params.outputs.len(),
+                params.inputs.len(),
+            ),
+        )?;
+
+        tx_builder.add_outputs(paired);
 
         self.add_indexed_output_to_builder(
             &mut tx_builder,
@@ -259,7 +302,7 @@ impl VaultManager {
             params.custodian_group_uid,
         )?;
 
-        tx_builder.add_outputs(params.outputs);
+        tx_builder.add_outputs(unpaired);
 
         let change = self.calculate_change(params.total_input_value, params.total_output_value);
         let dust = FeeRate::DUST.to_sat_per_kwu();
@@ -274,6 +317,7 @@ impl VaultManager {
             n_outputs: unsigned_tx.output.len() as u64,
             fee_rate: params.fee_rate,
             quorum: params.custodian_quorum,
+            sighash_type: params.sighash_type,
         });
 
         self.distribute_fee(&mut unsigned_tx, params.total_output_value, fee)?;
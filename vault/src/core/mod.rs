@@ -1,10 +1,21 @@
 mod branches;
+mod coin_selection;
+mod combine;
 mod constants;
+mod custodian_group;
+mod descriptor;
 mod errors;
+mod external_signer;
 mod feat;
 mod fee;
+mod finalization;
+mod frost;
+mod inspection;
+mod key_origins;
 mod manager;
+mod musig;
 mod params;
+mod policy;
 mod psbt;
 mod scripts;
 mod signing;
@@ -13,13 +24,26 @@ mod traits;
 mod tx;
 mod types;
 mod utils;
+mod validation;
+mod verification;
 
 pub use branches::*;
+pub use coin_selection::*;
+pub use combine::*;
 pub use constants::*;
+pub use custodian_group::*;
+pub use descriptor::*;
 pub use errors::*;
+pub use external_signer::*;
 pub use fee::*;
+pub(crate) use finalization::*;
+pub use frost::*;
+pub use inspection::*;
+pub use key_origins::*;
 pub use manager::*;
+pub use musig::*;
 pub use params::*;
+pub use policy::*;
 pub use psbt::*;
 pub use scripts::*;
 pub use taproot::*;
@@ -27,3 +51,5 @@ pub use traits::*;
 pub use tx::*;
 pub use types::*;
 pub use utils::*;
+pub use validation::*;
+pub use verification::*;
@@ -0,0 +1,254 @@
+use bitcoin::{
+    key::Parity,
+    secp256k1::{schnorr, SecretKey},
+    sighash::SighashCache,
+    taproot, Psbt, PublicKey, ScriptBuf, TapLeafHash, XOnlyPublicKey,
+};
+use musig2::{
+    aggregate_partial_signatures, sign_partial, verify_partial, AggNonce, KeyAggContext,
+    PartialSignature, PubNonce, SecNonce,
+};
+use rand_core::{OsRng, RngCore};
+
+use super::{
+    get_global_secp, BuildCustodianSubsetBranch, CoreError, Musig2Signing, Utils, VaultManager,
+};
+
+/// Builds the MuSig2 key aggregation context of a custodian group.
+///
+/// Keys are lifted to their even-y point and sorted, so the aggregated key does not depend
+/// on the order in which the custodians are provided.
+pub fn custodian_key_agg_context(
+    custodian_pubkeys: &[XOnlyPublicKey],
+) -> Result<KeyAggContext, CoreError> {
+    if custodian_pubkeys.is_empty() {
+        return Err(CoreError::Musig2(
+            "custodian pubkeys must not be empty".to_string(),
+        ));
+    }
+
+    let mut sorted_pks = custodian_pubkeys.to_owned();
+    sorted_pks.sort();
+    sorted_pks.dedup();
+    if sorted_pks.len() != custodian_pubkeys.len() {
+        return Err(CoreError::DuplicateCustodianKeys);
+    }
+
+    KeyAggContext::new(
+        sorted_pks
+            .iter()
+            .map(|pk| pk.public_key(Parity::Even))
+            .collect::<Vec<_>>(),
+    )
+    .map_err(|e| CoreError::Musig2(e.to_string()))
+}
+
+/// Returns the untweaked MuSig2 aggregated key of a custodian group, used as taproot internal key.
+pub fn aggregate_custodian_pubkeys(
+    custodian_pubkeys: &[XOnlyPublicKey],
+) -> Result<XOnlyPublicKey, CoreError> {
+    Ok(custodian_key_agg_context(custodian_pubkeys)?.aggregated_pubkey_untweaked())
+}
+
+/// A MuSig2 signing context for one input, a key-path spend or the spend of a
+/// `<musig(custodians)> OP_CHECKSIG` leaf.
+struct Musig2InputContext {
+    key_agg_ctx: KeyAggContext,
+    message: [u8; 32],
+    sighash_type: bitcoin::TapSighashType,
+    leaf_hash: Option<TapLeafHash>,
+}
+
+fn musig2_input_contexts(
+    psbt: &Psbt,
+    custodian_pubkeys: &[PublicKey],
+) -> Result<Vec<Musig2InputContext>, CoreError> {
+    let x_only_pubkeys: Vec<XOnlyPublicKey> =
+        custodian_pubkeys.iter().map(|pk| pk.inner.into()).collect();
+    let untweaked_ctx = custodian_key_agg_context(&x_only_pubkeys)?;
+    let aggregated_key: XOnlyPublicKey = untweaked_ctx.aggregated_pubkey_untweaked();
+    let aggregated_leaf = <ScriptBuf as BuildCustodianSubsetBranch>::build(&x_only_pubkeys, true)?;
+
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+
+    psbt.inputs
+        .iter()
+        .enumerate()
+        .map(|(index, input)| {
+            let (key_agg_ctx, leaf_hash) = if input.tap_internal_key == Some(aggregated_key) {
+                let key_agg_ctx = match input.tap_merkle_root {
+                    Some(root) => untweaked_ctx.clone().with_taproot_tweak(root.as_ref()),
+                    None => untweaked_ctx.clone().with_unspendable_taproot_tweak(),
+                }
+                .map_err(|e| CoreError::Musig2(e.to_string()))?;
+                (key_agg_ctx, None)
+            } else if let Some((script, version)) = input
+                .tap_scripts
+                .values()
+                .find(|(script, _)| *script == aggregated_leaf)
+            {
+                (
+                    untweaked_ctx.clone(),
+                    Some(TapLeafHash::from_script(script, *version)),
+                )
+            } else {
+                return Err(CoreError::Musig2InternalKeyMismatch(index));
+            };
+
+            let (msg, sighash_type) = psbt
+                .sighash_taproot(index, &mut cache, leaf_hash)
+                .map_err(|e| CoreError::SigningPSBTFailed(e.to_string()))?;
+
+            Ok(Musig2InputContext {
+                key_agg_ctx,
+                message: *msg.as_ref(),
+                sighash_type,
+                leaf_hash,
+            })
+        })
+        .collect()
+}
+
+/// MuSig2 signs with the even-y lift of every key, so an odd-y secret key has to be negated.
+fn musig2_seckey(privkey: &[u8]) -> Result<SecretKey, CoreError> {
+    let seckey =
+        SecretKey::from_slice(privkey).map_err(|e| CoreError::InvalidPrivateKey(e.to_string()))?;
+    let (_, parity) = seckey.x_only_public_key(get_global_secp());
+    Ok(match parity {
+        Parity::Even => seckey,
+        Parity::Odd => seckey.negate(),
+    })
+}
+
+impl Musig2Signing for VaultManager {
+    fn generate_musig2_nonces(
+        psbt: &Psbt,
+        custodian_pubkeys: &[PublicKey],
+        privkey: &[u8],
+    ) -> Result<(Vec<SecNonce>, Vec<PubNonce>), CoreError> {
+        let seckey = musig2_seckey(privkey)?;
+        let contexts = musig2_input_contexts(psbt, custodian_pubkeys)?;
+
+        let sec_nonces: Vec<SecNonce> = contexts
+            .iter()
+            .enumerate()
+            .map(|(index, ctx)| {
+                let mut seed = [0u8; 32];
+                OsRng.fill_bytes(&mut seed);
+                let index_bytes = (index as u64).to_be_bytes();
+                SecNonce::build_with_seckey(seed, seckey)
+                    .with_message(&ctx.message)
+                    .with_aggregated_pubkey(
+                        ctx.key_agg_ctx.aggregated_pubkey::<musig2::secp::Point>(),
+                    )
+                    .with_extra_input(&index_bytes)
+                    .build()
+            })
+            .collect();
+
+        let pub_nonces = sec_nonces.iter().map(SecNonce::public_nonce).collect();
+
+        Ok((sec_nonces, pub_nonces))
+    }
+
+    fn aggregate_musig2_nonces(pub_nonces: &[Vec<PubNonce>]) -> Result<Vec<AggNonce>, CoreError> {
+        let n_inputs = pub_nonces
+            .first()
+            .map(Vec::len)
+            .ok_or(CoreError::Musig2("no public nonces".to_string()))?;
+
+        if pub_nonces.iter().any(|nonces| nonces.len() != n_inputs) {
+            return Err(CoreError::MismatchBetweenNumberOfInputsAndMusig2Nonces);
+        }
+
+        Ok((0..n_inputs)
+            .map(|index| AggNonce::sum(pub_nonces.iter().map(|nonces| &nonces[index])))
+            .collect())
+    }
+
+    fn sign_musig2_partial(
+        psbt: &Psbt,
+        custodian_pubkeys: &[PublicKey],
+        privkey: &[u8],
+        sec_nonces: Vec<SecNonce>,
+        agg_nonces: &[AggNonce],
+    ) -> Result<Vec<PartialSignature>, CoreError> {
+        let seckey = musig2_seckey(privkey)?;
+        let contexts = musig2_input_contexts(psbt, custodian_pubkeys)?;
+
+        if contexts.len() != sec_nonces.len() || contexts.len() != agg_nonces.len() {
+            return Err(CoreError::MismatchBetweenNumberOfInputsAndMusig2Nonces);
+        }
+
+        contexts
+            .iter()
+            .zip(sec_nonces)
+            .zip(agg_nonces)
+            .map(|((ctx, sec_nonce), agg_nonce)| {
+                sign_partial(&ctx.key_agg_ctx, seckey, sec_nonce, agg_nonce, ctx.message)
+                    .map_err(|e| CoreError::Musig2(e.to_string()))
+            })
+            .collect()
+    }
+
+    fn aggregate_musig2_partial_sigs(
+        psbt: &mut Psbt,
+        custodian_pubkeys: &[PublicKey],
+        pub_nonces: &[Vec<PubNonce>],
+        partial_sigs: &[Vec<PartialSignature>],
+    ) -> Result<Self::PsbtHex, CoreError> {
+        let contexts = musig2_input_contexts(psbt, custodian_pubkeys)?;
+        let agg_nonces = <Self as Musig2Signing>::aggregate_musig2_nonces(pub_nonces)?;
+
+        if pub_nonces.len() != custodian_pubkeys.len() || partial_sigs.len() != pub_nonces.len() {
+            return Err(CoreError::Musig2(
+                "expected one nonce and one partial signature set per custodian".to_string(),
+            ));
+        }
+
+        if contexts.len() != agg_nonces.len()
+            || partial_sigs.iter().any(|sigs| sigs.len() != contexts.len())
+        {
+            return Err(CoreError::MismatchBetweenNumberOfInputsAndMusig2Nonces);
+        }
+
+        for (index, ctx) in contexts.iter().enumerate() {
+            for (custodian, pubkey) in custodian_pubkeys.iter().enumerate() {
+                let x_only: XOnlyPublicKey = pubkey.inner.into();
+                verify_partial(
+                    &ctx.key_agg_ctx,
+                    partial_sigs[custodian][index],
+                    &agg_nonces[index],
+                    x_only.public_key(Parity::Even),
+                    &pub_nonces[custodian][index],
+                    ctx.message,
+                )
+                .map_err(|_| CoreError::InvalidMusig2PartialSignature(index, x_only))?;
+            }
+
+            let signature: schnorr::Signature = aggregate_partial_signatures(
+                &ctx.key_agg_ctx,
+                &agg_nonces[index],
+                partial_sigs.iter().map(|sigs| sigs[index]),
+                ctx.message,
+            )
+            .map_err(|e| CoreError::Musig2(e.to_string()))?;
+
+            let signature = taproot::Signature {
+                signature,
+                sighash_type: ctx.sighash_type,
+            };
+            match ctx.leaf_hash {
+                Some(leaf_hash) => {
+                    let aggregated_key = ctx.key_agg_ctx.aggregated_pubkey_untweaked();
+                    psbt.inputs[index]
+                        .tap_script_sigs
+                        .insert((aggregated_key, leaf_hash), signature);
+                }
+                None => psbt.inputs[index].tap_key_sig = Some(signature),
+            }
+        }
+
+        Ok(psbt.serialize())
+    }
+}
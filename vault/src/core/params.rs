@@ -1,24 +1,77 @@
-use bitcoin::{Amount, PublicKey, ScriptBuf, TxOut};
+use bitcoin::{
+    absolute,
+    hashes::{sha256, Hash},
+    relative, Amount, PublicKey, ScriptBuf, TapSighashType, TxOut,
+};
 use validator::Validate;
 
 use super::{
-    CoreError, DestinationChain, DestinationRecipientAddress, DestinationTokenAddress,
-    PreviousOutpoint, TimeGatedUnlockingType, UPCUnlockingType, HASH_SIZE,
+    BranchLayout, CoreError, CustodianGroup, DestinationChain, DestinationRecipientAddress,
+    DestinationTokenAddress, HtlcUnlockingType, KeyOrigins, LockingOutput, PreviousOutpoint,
+    TaprootInternalKey, TimeGatedUnlockingType, UPCUnlockingType, VaultPolicy, HASH_SIZE,
+    PREIMAGE_SIZE,
+};
+use crate::{
+    convert_pubkey_to_x_only_key, convert_pubkeys_to_x_only_keys, validate_custodians,
+    validate_distinct_keys, validate_locking_amount, validate_outputs, validate_sighash_payouts,
 };
 
-// TODO: Add validate for params
+/// Emergency recovery leaf of a UPC vault: the custodians alone can spend once `lock_time`
+/// is reached, with a quorum higher than the one of the standard custodian branches.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UPCEmergencyParams {
+    pub custodian_quorum: u8,
+    pub lock_time: absolute::LockTime,
+}
+
+/// Subset leaf mode of a custodian-only vault: next to the k-of-n leaf, the tree gets one
+/// k-of-k leaf per subset of `custodian_quorum` custodians, so a spend only reveals and signs
+/// with the custodians that are online.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CustodianSubsetParams {
+    /// Expected availability of each custodian in percent, in `custodian_pubkeys` order.
+    /// Subsets of more available custodians get shorter control blocks. Defaults to equal availability.
+    pub availabilities: Option<Vec<u8>>,
+    /// Keeps only the most available subsets, all subsets are enumerated when not set.
+    pub max_leaves: Option<usize>,
+    /// Aggregates each subset into a single MuSig2 key.
+    pub aggregate: bool,
+}
+
+/// `custodian_weights`, when set, gives each custodian (in `custodian_pubkeys` order) a voting
+/// weight and turns `custodian_quorum` into the total weight required.
 #[derive(Debug, Validate)]
 pub struct UPCLockingParams {
     pub user_pubkey: PublicKey,
     pub protocol_pubkey: PublicKey,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
+    pub custodian_weights: Option<Vec<u8>>,
+    pub internal_key: TaprootInternalKey,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
+    pub emergency: Option<UPCEmergencyParams>,
     pub locking_amount: u64,
     pub destination_chain: DestinationChain,
     pub destination_token_address: DestinationTokenAddress,
     pub destination_recipient_address: DestinationRecipientAddress,
 }
 
+impl UPCLockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        validate_locking_amount(self.locking_amount)?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )?;
+        validate_parties(
+            &[self.user_pubkey, self.protocol_pubkey],
+            &self.custodian_pubkeys,
+        )
+    }
+}
+
 /// Because the unlocking tx is formed from a previous locking tx, 1 - 1 mapping is used.
 /// So we just need one input and one output.
 #[derive(Debug, Validate)]
@@ -29,9 +82,18 @@ pub struct UPCUnlockingParams {
     pub protocol_pubkey: PublicKey,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
+    pub custodian_weights: Option<Vec<u8>>,
+    pub internal_key: TaprootInternalKey,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
+    pub emergency: Option<UPCEmergencyParams>,
     pub rbf: bool,
     pub fee_rate: u64,
     pub typ: UPCUnlockingType,
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
 }
 
 impl UPCUnlockingParams {
@@ -42,11 +104,21 @@ impl UPCUnlockingParams {
             ));
         }
 
-        if self.output.value == Amount::ZERO {
-            return Err(CoreError::InvalidParams(
-                "Unlocking output amount must be greater than 0".to_string(),
-            ));
-        }
+        validate_outputs(std::slice::from_ref(&self.output))?;
+        validate_sighash_payouts(
+            self.sighash_type,
+            self.inputs.len(),
+            std::slice::from_ref(&self.output),
+        )?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )?;
+        validate_parties(
+            &[self.user_pubkey, self.protocol_pubkey],
+            &self.custodian_pubkeys,
+        )?;
 
         let total_input_value: Amount = self.inputs.iter().map(|input| input.amount_in_sats).sum();
 
@@ -67,21 +139,49 @@ pub struct CustodianOnlyLockingParams {
     pub locking_amount: u64,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
+    pub custodian_weights: Option<Vec<u8>>,
+    pub internal_key: TaprootInternalKey,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
+    pub subsets: Option<CustodianSubsetParams>,
     pub destination_chain: DestinationChain,
     pub destination_token_address: DestinationTokenAddress,
     pub destination_recipient_address: DestinationRecipientAddress,
 }
 
+impl CustodianOnlyLockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        validate_locking_amount(self.locking_amount)?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )
+    }
+}
+
 #[derive(Debug, Validate)]
 pub struct CustodianOnlyUnlockingParams {
     pub inputs: Vec<PreviousOutpoint>,
     pub outputs: Vec<TxOut>,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
+    pub custodian_weights: Option<Vec<u8>>,
+    pub internal_key: TaprootInternalKey,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
+    pub subsets: Option<CustodianSubsetParams>,
+    /// The custodians who will sign, selects the smallest leaf they can spend in subset mode.
+    /// Fails with [`CoreError::CustodianSubsetNotFound`] when they form no sampled subset.
+    pub signers: Option<Vec<PublicKey>>,
     pub rbf: bool,
     pub fee_rate: u64,
     pub session_sequence: u64,
     pub custodian_group_uid: [u8; HASH_SIZE],
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
 }
 
 impl CustodianOnlyUnlockingParams {
@@ -98,6 +198,14 @@ impl CustodianOnlyUnlockingParams {
             ));
         }
 
+        validate_outputs(&self.outputs)?;
+        validate_sighash_payouts(self.sighash_type, self.inputs.len(), &self.outputs)?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )?;
+
         let total_input_value: Amount = self.inputs.iter().map(|input| input.amount_in_sats).sum();
 
         let total_output_value: Amount = self.outputs.iter().map(|output| output.value).sum();
@@ -114,35 +222,361 @@ impl CustodianOnlyUnlockingParams {
     }
 }
 
-// TODO: Add validate for params
+/// Layout of the vault being rotated, the new custodian set gets the same layout.
+#[derive(Debug, Clone, Copy)]
+pub enum RotationVault {
+    CustodianOnly,
+    /// Spent through the custodian - protocol branch, the user and protocol keys are kept.
+    UPC {
+        user_pubkey: PublicKey,
+        protocol_pubkey: PublicKey,
+        emergency: Option<UPCEmergencyParams>,
+    },
+}
+
+#[derive(Debug, Validate)]
+pub struct CustodianRotationParams {
+    pub inputs: Vec<PreviousOutpoint>,
+    pub vault: RotationVault,
+    /// The custodian group of the vault being rotated, its uid is in the rotation output.
+    pub old_custodians: CustodianGroup,
+    pub new_custodians: CustodianGroup,
+    pub rbf: bool,
+    pub fee_rate: u64,
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
+}
+
+impl CustodianRotationParams {
+    pub fn validate(&self) -> Result<Amount, CoreError> {
+        if self.inputs.is_empty() {
+            return Err(CoreError::InvalidParams(
+                "CustodianRotationParams must have at least one input".to_string(),
+            ));
+        }
+
+        if self.old_custodians.uid() == self.new_custodians.uid() {
+            return Err(CoreError::InvalidParams(
+                "the new custodian group uid must differ from the old one".to_string(),
+            ));
+        }
+
+        // The custodians of a group are validated when it is created
+        if let RotationVault::UPC {
+            user_pubkey,
+            protocol_pubkey,
+            ..
+        } = self.vault
+        {
+            for custodians in [&self.old_custodians, &self.new_custodians] {
+                validate_parties(
+                    &[user_pubkey, protocol_pubkey],
+                    custodians.custodian_pubkeys(),
+                )?;
+            }
+        }
+
+        Ok(self.inputs.iter().map(|input| input.amount_in_sats).sum())
+    }
+}
+
 #[derive(Debug, Validate)]
 pub struct TimeGatedLockingParams {
     pub locking_amount: u64,
-    pub sequence: u16,
+    pub sequence: relative::LockTime,
     pub party_pubkey: PublicKey,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
+    pub custodian_weights: Option<Vec<u8>>,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
+}
+
+/// A time gated utxo together with the relative lock time of the vault it was locked into.
+#[derive(Debug, Clone)]
+pub struct TimeGatedInput {
+    pub input: PreviousOutpoint,
+    pub sequence: relative::LockTime,
 }
 
 #[derive(Debug, Validate)]
 pub struct TimeGatedUnlockingParams {
-    pub input: PreviousOutpoint,
+    pub inputs: Vec<TimeGatedInput>,
+    pub outputs: Vec<TxOut>,
+    /// Receives whatever is left after the outputs and the fee, if above dust.
+    pub change_script_pubkey: ScriptBuf,
     pub party_pubkey: PublicKey,
-    pub script_pubkey: ScriptBuf,
     pub custodian_pubkeys: Vec<PublicKey>,
     pub custodian_quorum: u8,
-    pub sequence: u16,
+    pub custodian_weights: Option<Vec<u8>>,
+    /// Encoding of the custodian leaves, see [`BranchLayout`].
+    pub branch_layout: BranchLayout,
     pub fee_rate: u64,
     pub typ: TimeGatedUnlockingType,
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
+}
+
+impl TimeGatedLockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        validate_locking_amount(self.locking_amount)?;
+        validate_relative_lock_time(&self.sequence)?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )?;
+        validate_parties(&[self.party_pubkey], &self.custodian_pubkeys)
+    }
 }
 
 impl TimeGatedUnlockingParams {
+    pub fn validate(&self) -> Result<(Amount, Amount), CoreError> {
+        if self.inputs.is_empty() {
+            return Err(CoreError::InvalidParams(
+                "TimeGatedUnlockingParams must have at least one input".to_string(),
+            ));
+        }
+
+        for input in self.inputs.iter() {
+            if input.input.amount_in_sats == Amount::ZERO {
+                return Err(CoreError::ZeroAmount);
+            }
+            validate_relative_lock_time(&input.sequence)?;
+        }
+
+        validate_outputs(&self.outputs)?;
+        validate_sighash_payouts(self.sighash_type, self.inputs.len(), &self.outputs)?;
+        validate_custodian_set(
+            &self.custodian_pubkeys,
+            self.custodian_quorum,
+            &self.custodian_weights,
+        )?;
+        validate_parties(&[self.party_pubkey], &self.custodian_pubkeys)?;
+
+        let total_input_value: Amount = self
+            .inputs
+            .iter()
+            .map(|input| input.input.amount_in_sats)
+            .sum();
+
+        let total_output_value: Amount = self.outputs.iter().map(|output| output.value).sum();
+
+        if total_input_value < total_output_value {
+            return Err(CoreError::InvalidParams(format!(
+                "Total input value must be greater than total output value: {} <= {}",
+                total_input_value, total_output_value
+            )));
+        }
+
+        Ok((total_input_value, total_output_value))
+    }
+}
+
+/// A wallet utxo funding a locking transaction, spent with the key-path of `pubkey`:
+/// P2WPKH, P2SH-P2WPKH or P2TR without script tree.
+#[derive(Debug, Clone)]
+pub struct FundingUtxo {
+    pub input: PreviousOutpoint,
+    pub pubkey: PublicKey,
+}
+
+#[derive(Debug, Validate)]
+pub struct FundedLockingParams {
+    /// The output built by `build_locking_output` of the vault.
+    pub locking_output: LockingOutput,
+    /// Candidate utxos, only the selected ones are spent.
+    pub utxos: Vec<FundingUtxo>,
+    /// Receives the change, if worth an output.
+    pub change_script_pubkey: ScriptBuf,
+    pub fee_rate: u64,
+    pub rbf: bool,
+    /// BIP32 origins of the wallet keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+}
+
+impl FundedLockingParams {
     pub fn validate(&self) -> Result<(), CoreError> {
-        if self.input.amount_in_sats == Amount::ZERO {
+        if self.utxos.is_empty() {
             return Err(CoreError::InvalidParams(
-                "output amount must be greater than 0".to_string(),
+                "FundedLockingParams must have at least one utxo".to_string(),
             ));
         }
+
+        for (i, utxo) in self.utxos.iter().enumerate() {
+            if utxo.input.amount_in_sats == Amount::ZERO {
+                return Err(CoreError::ZeroAmount);
+            }
+            if self.utxos[..i]
+                .iter()
+                .any(|other| other.input.outpoint == utxo.input.outpoint)
+            {
+                return Err(CoreError::InvalidParams(format!(
+                    "utxo {} is given more than once",
+                    utxo.input.outpoint
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn validate_custodian_set(
+    custodian_pubkeys: &[PublicKey],
+    custodian_quorum: u8,
+    custodian_weights: &Option<Vec<u8>>,
+) -> Result<(), CoreError> {
+    validate_custodians(
+        &convert_pubkeys_to_x_only_keys(custodian_pubkeys),
+        custodian_quorum,
+        custodian_weights.as_deref(),
+    )
+}
+
+/// No key may be shared between the parties and the custodians of a vault.
+fn validate_parties(
+    parties: &[PublicKey],
+    custodian_pubkeys: &[PublicKey],
+) -> Result<(), CoreError> {
+    let keys: Vec<_> = parties
+        .iter()
+        .chain(custodian_pubkeys)
+        .map(convert_pubkey_to_x_only_key)
+        .collect();
+    validate_distinct_keys(&keys)
+}
+
+/// A zero relative lock time would make the party branch spendable right away.
+fn validate_relative_lock_time(lock_time: &relative::LockTime) -> Result<(), CoreError> {
+    let zero = match lock_time {
+        relative::LockTime::Blocks(height) => height.value() == 0,
+        relative::LockTime::Time(time) => time.value() == 0,
+    };
+    if zero {
+        return Err(CoreError::InvalidRelativeLockTime(
+            "relative lock time must be greater than 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Validate)]
+pub struct HtlcLockingParams {
+    pub locking_amount: u64,
+    pub recipient_pubkey: PublicKey,
+    pub refund_pubkey: PublicKey,
+    pub payment_hash: sha256::Hash,
+    pub lock_time: absolute::LockTime,
+}
+
+impl HtlcLockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        validate_locking_amount(self.locking_amount)?;
+        validate_parties(&[self.recipient_pubkey, self.refund_pubkey], &[])
+    }
+}
+
+#[derive(Debug, Validate)]
+pub struct HtlcUnlockingParams {
+    pub input: PreviousOutpoint,
+    pub script_pubkey: ScriptBuf,
+    pub recipient_pubkey: PublicKey,
+    pub refund_pubkey: PublicKey,
+    pub payment_hash: sha256::Hash,
+    pub lock_time: absolute::LockTime,
+    /// Required to claim, it is put in the psbt and revealed in the witness.
+    pub preimage: Option<Vec<u8>>,
+    pub fee_rate: u64,
+    pub typ: HtlcUnlockingType,
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
+}
+
+impl HtlcUnlockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        if self.input.amount_in_sats == Amount::ZERO {
+            return Err(CoreError::ZeroAmount);
+        }
+
+        validate_parties(&[self.recipient_pubkey, self.refund_pubkey], &[])?;
+
+        if self.typ == HtlcUnlockingType::Claim {
+            let preimage = self.preimage.as_ref().ok_or(CoreError::InvalidParams(
+                "preimage is required to claim".to_string(),
+            ))?;
+
+            if preimage.len() != PREIMAGE_SIZE || sha256::Hash::hash(preimage) != self.payment_hash
+            {
+                return Err(CoreError::InvalidPreimage);
+            }
+        }
+
         Ok(())
     }
 }
+
+#[derive(Debug, Validate)]
+pub struct PolicyLockingParams {
+    pub locking_amount: u64,
+    pub policy: VaultPolicy,
+}
+
+impl PolicyLockingParams {
+    pub fn validate(&self) -> Result<(), CoreError> {
+        validate_locking_amount(self.locking_amount)
+    }
+}
+
+#[derive(Debug, Validate)]
+pub struct PolicyUnlockingParams {
+    pub inputs: Vec<PreviousOutpoint>,
+    pub outputs: Vec<TxOut>,
+    pub policy: VaultPolicy,
+    /// The name of the policy leaf to spend
+    pub leaf_name: String,
+    pub rbf: bool,
+    pub fee_rate: u64,
+    /// BIP32 origins of the signing keys, for hardware wallets.
+    pub key_origins: Option<KeyOrigins>,
+    /// Sighash type of the signatures, `SIGHASH_DEFAULT` if unset.
+    pub sighash_type: Option<TapSighashType>,
+}
+
+impl PolicyUnlockingParams {
+    pub fn validate(&self) -> Result<(Amount, Amount), CoreError> {
+        if self.inputs.is_empty() {
+            return Err(CoreError::InvalidParams(
+                "PolicyUnlockingParams must have at least one input".to_string(),
+            ));
+        }
+
+        if self.outputs.is_empty() {
+            return Err(CoreError::InvalidParams(
+                "PolicyUnlockingParams must have at least one unlocking output".to_string(),
+            ));
+        }
+
+        validate_outputs(&self.outputs)?;
+        validate_sighash_payouts(self.sighash_type, self.inputs.len(), &self.outputs)?;
+
+        let total_input_value: Amount = self.inputs.iter().map(|input| input.amount_in_sats).sum();
+
+        let total_output_value: Amount = self.outputs.iter().map(|output| output.value).sum();
+
+        if total_input_value < total_output_value {
+            return Err(CoreError::InvalidParams(format!(
+                "Total input value must be greater than total output value: {} <= {}",
+                total_input_value, total_output_value
+            )));
+        }
+
+        Ok((total_input_value, total_output_value))
+    }
+}
@@ -0,0 +1,208 @@
+use bitcoin::{
+    key::Secp256k1, relative, secp256k1::All, taproot::TaprootBuilder, ScriptBuf, Sequence,
+    XOnlyPublicKey,
+};
+
+use super::{
+    BranchLayout, BuildCustodianAndPartyBranch, BuildCustodianOnlyBranch,
+    BuildPartyWithSequenceVerification, BuildTwoPartyBranch, CoreError, TaprootTree, NUMS_BIP_341,
+};
+
+/// A spending condition built from one of the branch primitives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyBranch {
+    /// `TwoPartyBranch`: both parties sign.
+    TwoParty {
+        x: XOnlyPublicKey,
+        y: XOnlyPublicKey,
+    },
+    /// `CustodianAndPartyBranch`: the party and a quorum of custodians sign.
+    CustodianAndParty {
+        party: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+    },
+    /// `CustodianOnlyBranch`: a quorum of custodians sign.
+    CustodianOnly {
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+    },
+    /// `PartyWithSequenceVerification`: the party signs once `sequence`, in blocks or in
+    /// 512 seconds intervals, has passed.
+    PartyWithSequenceVerification {
+        party: XOnlyPublicKey,
+        sequence: relative::LockTime,
+    },
+}
+
+impl PolicyBranch {
+    pub fn build_script(&self) -> Result<ScriptBuf, CoreError> {
+        match self {
+            PolicyBranch::TwoParty { x, y } => <ScriptBuf as BuildTwoPartyBranch>::build(x, y),
+            PolicyBranch::CustodianAndParty {
+                party,
+                custodian_pubkeys,
+                custodian_quorum,
+            } => <ScriptBuf as BuildCustodianAndPartyBranch>::build(
+                party,
+                custodian_pubkeys,
+                *custodian_quorum,
+                BranchLayout::Legacy,
+            ),
+            PolicyBranch::CustodianOnly {
+                custodian_pubkeys,
+                custodian_quorum,
+            } => <ScriptBuf as BuildCustodianOnlyBranch>::build(
+                custodian_pubkeys,
+                *custodian_quorum,
+                BranchLayout::Legacy,
+            ),
+            PolicyBranch::PartyWithSequenceVerification { party, sequence } => {
+                <ScriptBuf as BuildPartyWithSequenceVerification>::build(
+                    party,
+                    sequence.to_consensus_u32().into(),
+                    BranchLayout::Legacy,
+                )
+            }
+        }
+    }
+
+    /// The keys which can sign this branch.
+    pub fn keys(&self) -> Vec<XOnlyPublicKey> {
+        match self {
+            PolicyBranch::TwoParty { x, y } => vec![*x, *y],
+            PolicyBranch::CustodianAndParty {
+                party,
+                custodian_pubkeys,
+                ..
+            } => {
+                let mut keys = vec![*party];
+                keys.extend_from_slice(custodian_pubkeys);
+                keys
+            }
+            PolicyBranch::CustodianOnly {
+                custodian_pubkeys, ..
+            } => custodian_pubkeys.clone(),
+            PolicyBranch::PartyWithSequenceVerification { party, .. } => vec![*party],
+        }
+    }
+
+    /// The number of signatures required to spend this branch.
+    pub fn required_signatures(&self) -> u8 {
+        match self {
+            PolicyBranch::TwoParty { .. } => 2,
+            PolicyBranch::CustodianAndParty {
+                custodian_quorum, ..
+            } => custodian_quorum.saturating_add(1),
+            PolicyBranch::CustodianOnly {
+                custodian_quorum, ..
+            } => *custodian_quorum,
+            PolicyBranch::PartyWithSequenceVerification { .. } => 1,
+        }
+    }
+
+    /// The input sequence required to spend this branch, if any.
+    pub fn sequence(&self) -> Option<Sequence> {
+        match self {
+            PolicyBranch::PartyWithSequenceVerification { sequence, .. } => {
+                Some(sequence.to_sequence())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A named leaf of a [`VaultPolicy`].
+///
+/// `weight` is the relative spending probability of the leaf, a higher weight puts the leaf
+/// closer to the root. Leaves without weight count as `1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolicyLeaf {
+    pub name: String,
+    pub branch: PolicyBranch,
+    pub weight: Option<u32>,
+}
+
+impl PolicyLeaf {
+    pub fn new(name: impl Into<String>, branch: PolicyBranch) -> Self {
+        Self {
+            name: name.into(),
+            branch,
+            weight: None,
+        }
+    }
+
+    pub fn with_weight(mut self, weight: u32) -> Self {
+        self.weight = Some(weight);
+        self
+    }
+}
+
+/// A declarative vault layout, the leaves are arranged in a huffman tree by weight.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaultPolicy {
+    pub leaves: Vec<PolicyLeaf>,
+}
+
+impl VaultPolicy {
+    pub fn new(leaves: Vec<PolicyLeaf>) -> Self {
+        Self { leaves }
+    }
+
+    pub fn leaf(&self, name: &str) -> Option<&PolicyLeaf> {
+        self.leaves.iter().find(|leaf| leaf.name == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyTreeLeaf {
+    pub leaf: PolicyLeaf,
+    pub script: ScriptBuf,
+}
+
+#[derive(Debug, Clone)]
+pub struct PolicyTree {
+    pub leaves: Vec<PolicyTreeLeaf>,
+}
+
+impl PolicyTree {
+    pub fn leaf(&self, name: &str) -> Option<&PolicyTreeLeaf> {
+        self.leaves.iter().find(|leaf| leaf.leaf.name == name)
+    }
+}
+
+impl TaprootTree<PolicyTree> {
+    /// Creates a Taproot locking script from a declarative policy.
+    ///
+    /// Leaves are placed with [`TaprootBuilder::with_huffman_tree`], so equally weighted
+    /// leaves give a balanced tree and heavier leaves get shorter control blocks.
+    pub fn new(secp: &Secp256k1<All>, policy: &VaultPolicy) -> Result<Self, CoreError> {
+        let mut leaves: Vec<PolicyTreeLeaf> = Vec::with_capacity(policy.leaves.len());
+
+        for leaf in policy.leaves.iter() {
+            if leaves.iter().any(|l| l.leaf.name == leaf.name) {
+                return Err(CoreError::DuplicatePolicyLeaf(leaf.name.clone()));
+            }
+
+            leaves.push(PolicyTreeLeaf {
+                leaf: leaf.clone(),
+                script: leaf.branch.build_script()?,
+            });
+        }
+
+        let builder = TaprootBuilder::with_huffman_tree(
+            leaves
+                .iter()
+                .map(|l| (l.leaf.weight.unwrap_or(1), l.script.clone())),
+        )?;
+
+        let taproot_spend_info = builder
+            .finalize(secp, *NUMS_BIP_341)
+            .map_err(|_| CoreError::TaprootFinalizationFailed)?;
+
+        Ok(Self {
+            root: taproot_spend_info,
+            raw: PolicyTree { leaves },
+        })
+    }
+}
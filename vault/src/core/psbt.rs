@@ -1,29 +1,49 @@
 use std::{borrow::Borrow, collections::BTreeMap};
 
 use bitcoin::{
+    bip32::{DerivationPath, Error as Bip32Error, KeySource, Xpriv},
     ecdsa,
     hashes::{hash160, Hash},
+    key::FromWifError,
     key::{Keypair, Parity, Secp256k1, TapTweak, Verification},
+    opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_CHECKSIGVERIFY, OP_SHA256},
     psbt::{
         GetKey, GetKeyError, IndexOutOfBoundsError, Input, KeyRequest, OutputType, PsbtSighashType,
         SignError, SigningAlgorithm, SigningErrors, SigningKeys, SigningKeysMap,
     },
+    script::Instruction,
     secp256k1::{Error as Secp256k1Error, Message, PublicKey as Secp256k1PublicKey, Signing},
     sighash::{Prevouts, SighashCache},
     taproot::{self, ControlBlock},
     NetworkKind, PrivateKey, Psbt, PublicKey, ScriptBuf, TapLeafHash, TapSighashType, Transaction,
-    Witness, XOnlyPublicKey,
+    XOnlyPublicKey,
 };
 
-#[derive(Debug)]
-pub struct SigningKeyMap(BTreeMap<XOnlyPublicKey, PrivateKey>);
+use super::{
+    plan_ecdsa_finalization, plan_input_finalization, CoreError, InputSigningStatus,
+    PsbtSigningStatus,
+};
+
+/// Private keys a PSBT is signed with, looked up by public key or by BIP32 origin.
+#[derive(Debug, Default)]
+pub struct SigningKeyMap {
+    keys: BTreeMap<XOnlyPublicKey, PrivateKey>,
+    /// Extended keys with their own origin, answering `KeyRequest::Bip32`
+    xprivs: Vec<(Xpriv, KeySource)>,
+}
 
 #[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub struct AliasPushBytes(Vec<u8>);
 
+impl From<Vec<u8>> for AliasPushBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
 impl SigningKeyMap {
     fn inner(&self) -> &BTreeMap<XOnlyPublicKey, PrivateKey> {
-        &self.0
+        &self.keys
     }
 
     pub fn from_privkey_slice<C: Signing + Verification>(
@@ -31,9 +51,70 @@ impl SigningKeyMap {
         privkey_slice: &[u8],
         network_kind: NetworkKind,
     ) -> Result<Self, Secp256k1Error> {
-        let privkey = PrivateKey::from_slice(privkey_slice, network_kind)?;
-        let x_only_pubkey = privkey.public_key(secp).into();
-        Ok(Self(BTreeMap::from([(x_only_pubkey, privkey)])))
+        let mut key_map = Self::default();
+        key_map.insert(secp, PrivateKey::from_slice(privkey_slice, network_kind)?);
+        Ok(key_map)
+    }
+
+    /// Builds a key map from several raw 32 bytes private keys.
+    pub fn from_privkey_slices<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        privkey_slices: &[&[u8]],
+        network_kind: NetworkKind,
+    ) -> Result<Self, Secp256k1Error> {
+        let mut key_map = Self::default();
+        for privkey_slice in privkey_slices {
+            key_map.insert(secp, PrivateKey::from_slice(privkey_slice, network_kind)?);
+        }
+        Ok(key_map)
+    }
+
+    /// Builds a key map from several WIF encoded private keys.
+    pub fn from_wifs<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        wifs: &[&str],
+    ) -> Result<Self, FromWifError> {
+        let mut key_map = Self::default();
+        for wif in wifs {
+            key_map.insert(secp, PrivateKey::from_wif(wif)?);
+        }
+        Ok(key_map)
+    }
+
+    /// Builds a key map from an extended private key and the `paths` of the keys to sign
+    /// with, relative to `xpriv`.
+    ///
+    /// `origin` is the master fingerprint and path of `xpriv` itself, a master key when not set.
+    /// Keys at other paths are still derived on request from the PSBT key origins.
+    pub fn from_xpriv<C: Signing + Verification>(
+        secp: &Secp256k1<C>,
+        xpriv: Xpriv,
+        origin: Option<KeySource>,
+        paths: &[DerivationPath],
+    ) -> Result<Self, Bip32Error> {
+        let mut key_map = Self::default();
+        key_map.insert_xpriv(secp, xpriv, origin, paths)?;
+        Ok(key_map)
+    }
+
+    pub fn insert<C: Signing>(&mut self, secp: &Secp256k1<C>, privkey: PrivateKey) {
+        self.keys.insert(privkey.public_key(secp).into(), privkey);
+    }
+
+    /// Same as [`Self::from_xpriv`], adding to an existing key map.
+    pub fn insert_xpriv<C: Signing + Verification>(
+        &mut self,
+        secp: &Secp256k1<C>,
+        xpriv: Xpriv,
+        origin: Option<KeySource>,
+        paths: &[DerivationPath],
+    ) -> Result<(), Bip32Error> {
+        for path in paths {
+            self.insert(secp, xpriv.derive_priv(secp, path)?.to_priv());
+        }
+        let origin = origin.unwrap_or((xpriv.fingerprint(secp), DerivationPath::master()));
+        self.xprivs.push((xpriv, origin));
+        Ok(())
     }
 
     pub fn get_x_only_pubkey(&self) -> Option<&XOnlyPublicKey> {
@@ -42,6 +123,37 @@ impl SigningKeyMap {
         }
         None
     }
+
+    pub fn x_only_pubkeys(&self) -> impl Iterator<Item = &XOnlyPublicKey> {
+        self.inner().keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty() && self.xprivs.is_empty()
+    }
+
+    /// Derives the key of a BIP32 origin below one of the extended keys.
+    fn derive_bip32<C: Signing>(
+        &self,
+        (fingerprint, path): &KeySource,
+        secp: &Secp256k1<C>,
+    ) -> Result<Option<PrivateKey>, GetKeyError> {
+        for (xpriv, (xpriv_fingerprint, xpriv_path)) in self.xprivs.iter() {
+            if xpriv_fingerprint != fingerprint {
+                continue;
+            }
+            let Some(child) = path.as_ref().strip_prefix(xpriv_path.as_ref()) else {
+                continue;
+            };
+            let child: DerivationPath = child.into();
+            return Ok(Some(xpriv.derive_priv(secp, &child)?.to_priv()));
+        }
+        Ok(None)
+    }
 }
 
 impl GetKey for SigningKeyMap {
@@ -50,14 +162,14 @@ impl GetKey for SigningKeyMap {
     fn get_key<C: Signing>(
         &self,
         key_request: KeyRequest,
-        _: &Secp256k1<C>,
+        secp: &Secp256k1<C>,
     ) -> Result<Option<PrivateKey>, Self::Error> {
         match key_request {
             KeyRequest::Pubkey(pk) => {
                 let pubkey: XOnlyPublicKey = pk.into();
                 Ok(self.inner().get(&pubkey).cloned())
             }
-            KeyRequest::Bip32(_) => Err(GetKeyError::NotSupported),
+            KeyRequest::Bip32(key_source) => self.derive_bip32(&key_source, secp),
             _ => Err(GetKeyError::NotSupported),
         }
     }
@@ -83,7 +195,13 @@ pub trait SignByKeyMap<C> {
     ) -> Result<SigningKeysMap, (SigningKeysMap, SigningErrors)>
     where
         C: Signing + Verification;
-    fn finalize(&mut self);
+
+    /// Finalizes every input that is satisfied, through the leaf of `leaves` for the inputs
+    /// that have one, and reports the progress of every input.
+    fn finalize(
+        &mut self,
+        leaves: &BTreeMap<usize, TapLeafHash>,
+    ) -> Result<PsbtSigningStatus, CoreError>;
 }
 
 impl<C> SignByKeyMap<C> for Psbt {
@@ -139,15 +257,29 @@ impl<C> SignByKeyMap<C> for Psbt {
         }
     }
 
-    fn finalize(&mut self) {
+    fn finalize(
+        &mut self,
+        leaves: &BTreeMap<usize, TapLeafHash>,
+    ) -> Result<PsbtSigningStatus, CoreError> {
+        if let Some((index, leaf_hash)) = leaves
+            .iter()
+            .find(|(index, _)| **index >= self.inputs.len())
+        {
+            return Err(CoreError::TapLeafNotFound(*index, *leaf_hash));
+        }
+
+        let mut inputs = Vec::with_capacity(self.inputs.len());
         for i in 0..self.inputs.len() {
             if !self.is_taproot_input(i) {
-                self.finalize_ecdsa_input(i);
+                inputs.push(self.finalize_ecdsa_input(i));
                 continue;
             }
 
-            self.finalize_taproot_input(i);
+            inputs.push(self.finalize_taproot_input(i, leaves.get(&i).copied())?);
         }
+
+        let ready = !inputs.is_empty() && inputs.iter().all(|input| input.finalized);
+        Ok(PsbtSigningStatus { inputs, ready })
     }
 }
 
@@ -184,8 +316,12 @@ pub trait Utils {
         C: Signing + Verification,
         T: Borrow<Transaction>;
     fn is_taproot_input(&self, input_index: usize) -> bool;
-    fn finalize_taproot_input(&mut self, input_index: usize);
-    fn finalize_ecdsa_input(&mut self, input_index: usize);
+    fn finalize_taproot_input(
+        &mut self,
+        input_index: usize,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<InputSigningStatus, CoreError>;
+    fn finalize_ecdsa_input(&mut self, input_index: usize) -> InputSigningStatus;
 
     fn find_tap_leaf_to_finalize(
         &self,
@@ -220,65 +356,55 @@ pub trait Utils {
 }
 
 impl Utils for Psbt {
-    /// Finalizes a taproot input by adding the signatures to the input.
-    fn finalize_taproot_input(&mut self, input_index: usize) {
-        let input = &mut self.inputs[input_index];
-
-        if let Some(signature) = input.tap_key_sig {
-            let mut script_witness: Witness = Witness::new();
-            script_witness.push(signature.to_vec());
-            input.final_script_witness = Some(script_witness);
-            self.reset_taproot_input(input_index);
-            return;
-        }
-
-        let result = self.find_tap_leaf_to_finalize(input_index);
-
-        if result.is_none() {
-            return;
+    /// Finalizes a taproot input through the key path or its satisfied leaf with the smallest
+    /// witness, or through `leaf_hash` when set. Unsatisfied inputs are left untouched.
+    fn finalize_taproot_input(
+        &mut self,
+        input_index: usize,
+        leaf_hash: Option<TapLeafHash>,
+    ) -> Result<InputSigningStatus, CoreError> {
+        if let Some(leaf_hash) = leaf_hash {
+            let found = self.inputs[input_index]
+                .tap_scripts
+                .values()
+                .any(|(script, version)| TapLeafHash::from_script(script, *version) == leaf_hash);
+            if !found {
+                return Err(CoreError::TapLeafNotFound(input_index, leaf_hash));
+            }
         }
 
-        let (tap_script, control_block, _, sigs_map) = result.unwrap();
-
-        let push_bytes_positions = self.calculate_push_bytes_positions(&tap_script);
+        let tx = self.unsigned_tx.clone();
+        let mut cache = SighashCache::new(&tx);
+        let (mut status, witness) =
+            plan_input_finalization(self, input_index, leaf_hash, &mut cache);
 
-        if push_bytes_positions.is_empty() {
-            return;
+        if let Some(witness) = witness {
+            self.inputs[input_index].final_script_witness = Some(witness);
+            self.reset_taproot_input(input_index);
+            status.finalized = true;
         }
+        Ok(status)
+    }
 
-        let mut raw_witness = vec![Vec::new(); push_bytes_positions.len()];
+    /// Finalizes a non-taproot input following the script type of its utxo, see
+    /// [`OutputType`]. Unsatisfied inputs are left untouched.
+    fn finalize_ecdsa_input(&mut self, input_index: usize) -> InputSigningStatus {
+        let tx = self.unsigned_tx.clone();
+        let mut cache = SighashCache::new(&tx);
+        let (mut status, satisfaction) = plan_ecdsa_finalization(self, input_index, &mut cache);
 
-        for (key, signature) in sigs_map.iter() {
-            let pos = self.get_push_bytes_position(&push_bytes_positions, key);
-            if let Some(pos) = pos {
-                raw_witness[pos] = signature.to_vec();
+        if let Some((script_sig, witness)) = satisfaction {
+            let input = &mut self.inputs[input_index];
+            if !script_sig.is_empty() {
+                input.final_script_sig = Some(script_sig);
             }
+            if !witness.is_empty() {
+                input.final_script_witness = Some(witness);
+            }
+            self.reset_taproot_input(input_index);
+            status.finalized = true;
         }
-
-        let mut script_witness: Witness = Witness::new();
-        for witness in raw_witness {
-            script_witness.push(witness);
-        }
-
-        script_witness.push(tap_script.to_bytes());
-        script_witness.push(control_block.serialize());
-
-        self.inputs[input_index].final_script_witness = Some(script_witness);
-
-        self.reset_taproot_input(input_index);
-    }
-
-    fn finalize_ecdsa_input(&mut self, input_index: usize) {
-        let input = &mut self.inputs[input_index];
-        let sigs = input.partial_sigs.values().collect::<Vec<_>>();
-        let pubkeys = input.partial_sigs.keys().collect::<Vec<_>>();
-        let mut script_witness: Witness = Witness::new();
-        for i in 0..sigs.len() {
-            script_witness.push(sigs[i].to_vec());
-            script_witness.push(pubkeys[i].to_bytes());
-        }
-        input.final_script_witness = Some(script_witness);
-        self.reset_taproot_input(input_index);
+        status
     }
 
     fn reset_taproot_input(&mut self, input_index: usize) {
@@ -294,6 +420,7 @@ impl Utils for Psbt {
         input.tap_internal_key = None;
         input.tap_merkle_root = None;
         input.tap_key_origins = BTreeMap::new();
+        input.sha256_preimages = BTreeMap::new();
     }
 
     fn find_tap_leaf_to_finalize(
@@ -308,7 +435,7 @@ impl Utils for Psbt {
         let input = &self.inputs[input_index];
 
         let mut tap_scripts: Vec<_> = input.tap_scripts.iter().collect();
-        tap_scripts.sort_by(|a, b| a.0.serialize().len().cmp(&b.0.serialize().len()));
+        tap_scripts.sort_by_key(|a| a.0.serialize().len());
 
         tap_scripts
             .into_iter()
@@ -338,8 +465,22 @@ impl Utils for Psbt {
     ) -> BTreeMap<AliasPushBytes, usize> {
         let mut push_bytes: Vec<AliasPushBytes> = vec![];
 
-        for ins in script.instructions().flatten() {
-            if let Some(data_push) = ins.push_bytes() {
+        // Only the keys checked by a signature opcode and the hashes whose preimage is checked
+        // take a witness slot, other pushes like lock times or custodian weights are part of
+        // the script itself.
+        let instructions: Vec<Instruction> = script.instructions().flatten().collect();
+        for (i, ins) in instructions.iter().enumerate() {
+            let Some(data_push) = ins.push_bytes() else {
+                continue;
+            };
+            let is_key = matches!(
+                instructions.get(i + 1),
+                Some(Instruction::Op(
+                    OP_CHECKSIG | OP_CHECKSIGVERIFY | OP_CHECKSIGADD
+                ))
+            );
+            let is_hash = i > 0 && instructions[i - 1] == Instruction::Op(OP_SHA256);
+            if is_key || is_hash {
                 push_bytes.push(AliasPushBytes(data_push.as_bytes().to_vec()));
             }
         }
@@ -399,13 +540,35 @@ impl Utils for Psbt {
 
         let mut used = vec![]; // List of pubkeys used to sign the input.
 
-        for (&xonly, (leaf_hashes, _)) in input.tap_key_origins.iter() {
+        // A wallet internal key may come without origin, it is then only looked up by pubkey
+        let mut candidates: Vec<(XOnlyPublicKey, Vec<TapLeafHash>, Option<KeySource>)> = input
+            .tap_key_origins
+            .iter()
+            .map(|(&xonly, (leaf_hashes, key_source))| {
+                (xonly, leaf_hashes.clone(), Some(key_source.clone()))
+            })
+            .collect();
+        if let Some(internal_key) = input.tap_internal_key {
+            if !input.tap_key_origins.contains_key(&internal_key) {
+                candidates.push((internal_key, vec![], None));
+            }
+        }
+
+        for (xonly, leaf_hashes, key_source) in candidates {
             let key: Secp256k1PublicKey =
                 Secp256k1PublicKey::from_x_only_public_key(xonly, Parity::Even); // even or odd is not relevant for signing, just needs to be consistent with the KeyRequest::Pubkey
             let pubkey: PublicKey = key.into();
             let sk = if let Ok(Some(secret_key)) = key_map.get_key(KeyRequest::Pubkey(pubkey), secp)
             {
                 secret_key
+            } else if let Some(Ok(Some(secret_key))) =
+                key_source.map(|key_source| key_map.get_key(KeyRequest::Bip32(key_source), secp))
+            {
+                // The origin may be wrong or point to another key of the same wallet
+                if XOnlyPublicKey::from(secret_key.public_key(secp)) != xonly {
+                    continue;
+                }
+                secret_key
             } else {
                 continue;
             };
@@ -652,16 +815,56 @@ impl Utils for Psbt {
 
         let mut used = vec![]; // List of pubkeys used to sign the input.
 
-        for (pk, _) in input.tap_key_origins.iter() {
-            let key: Secp256k1PublicKey =
-                Secp256k1PublicKey::from_x_only_public_key(*pk, Parity::Even); // even or odd is not relevant for signing, just needs to be consistent with the KeyRequest::Pubkey
-            let pubkey: PublicKey = key.into();
+        // Wallet inputs without origin are matched against the p2wpkh program they pay to,
+        // the redeem script of a nested input or the script pubkey of a native one
+        let program = input.redeem_script.clone().or_else(|| {
+            input
+                .witness_utxo
+                .as_ref()
+                .map(|utxo| utxo.script_pubkey.clone())
+        });
+        let wallet_keys = key_map.keys.values().filter_map(|sk| {
+            let pk = sk.public_key(secp);
+            let wpubkey_hash = pk.wpubkey_hash().ok()?;
+            (program.as_ref() == Some(&ScriptBuf::new_p2wpkh(&wpubkey_hash))).then_some((pk, None))
+        });
+
+        // Vault inputs carry their keys in `tap_key_origins`, wallet inputs in `bip32_derivation`
+        let pubkeys: Vec<(PublicKey, Option<KeySource>)> = input
+            .tap_key_origins
+            .iter()
+            .map(|(pk, (_, key_source))| {
+                // even or odd is not relevant for signing, just needs to be consistent with the KeyRequest::Pubkey
+                let pubkey = Secp256k1PublicKey::from_x_only_public_key(*pk, Parity::Even).into();
+                (pubkey, Some(key_source.clone()))
+            })
+            .chain(
+                input
+                    .bip32_derivation
+                    .iter()
+                    .map(|(pk, key_source)| (PublicKey::new(*pk), Some(key_source.clone()))),
+            )
+            .chain(wallet_keys)
+            .collect();
 
+        for (pubkey, key_source) in pubkeys {
             let sk = if let Ok(Some(sk)) = key_map.get_key(KeyRequest::Pubkey(pubkey), secp) {
                 sk
+            } else if let Some(Ok(Some(sk))) =
+                key_source.map(|key_source| key_map.get_key(KeyRequest::Bip32(key_source), secp))
+            {
+                // The origin may be wrong or point to another key of the same wallet
+                if XOnlyPublicKey::from(sk.public_key(secp)) != XOnlyPublicKey::from(pubkey) {
+                    continue;
+                }
+                sk
             } else {
                 continue;
             };
+            let pk = sk.public_key(secp);
+            if used.contains(&pk) {
+                continue;
+            }
 
             // // Only return the error if we have a secret key to sign this input.
             let (msg, sighash_ty) = match msg_sighash_ty_res {
@@ -674,8 +877,6 @@ impl Utils for Psbt {
                 sighash_type: sighash_ty,
             };
 
-            let pk = sk.public_key(secp);
-
             input.partial_sigs.insert(pk, sig);
             used.push(pk);
         }
@@ -80,12 +80,20 @@ pub enum TaprootTreeType {
      * User - Protocol, Custodian - Protocol, User - Custodian
      */
     UPCBranch = 0b10000000,
+
+    /**
+     * Party after a relative lock time, Custodian only
+     * `0b11000000` is reserved for the many branches with custodians tree
+     */
+    TimeGated = 0b11000010,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum UnlockingTaprootTreeType {
     CustodianOnlyBranch = 0b01000001,
     UPCBranch = 0b10000001,
+    /// Moves the funds of a vault to a new custodian set
+    CustodianRotation = 0b01000011,
 }
 
 impl TryFrom<u8> for TaprootTreeType {
@@ -96,6 +104,7 @@ impl TryFrom<u8> for TaprootTreeType {
             0b00000000 => Ok(Self::OnlyKeys),
             0b01000000 => Ok(Self::CustodianOnly),
             0b10000000 => Ok(Self::UPCBranch),
+            0b11000010 => Ok(Self::TimeGated),
             _ => Err(CoreError::InvalidTaprootTreeType),
         }
     }
@@ -108,6 +117,7 @@ impl TryFrom<u8> for UnlockingTaprootTreeType {
         match value {
             0b01000001 => Ok(Self::CustodianOnlyBranch),
             0b10000001 => Ok(Self::UPCBranch),
+            0b01000011 => Ok(Self::CustodianRotation),
             _ => Err(CoreError::InvalidTaprootTreeType),
         }
     }
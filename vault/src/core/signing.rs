@@ -1,5 +1,15 @@
-use super::{get_global_secp, SignByKeyMap, TapScriptSig, TapScriptSigsMap};
-use bitcoin::{consensus::serialize, secp256k1::All, NetworkKind, Psbt};
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{
+    combine_psbts, get_global_secp, insert_external_signatures, sighash_requests,
+    verify_tap_script_sigs, AsyncExternalSigner, ExternalSigner, PsbtSigningStatus, SignByKeyMap,
+    TapScriptSig, TapScriptSigsMap,
+};
+
+use bitcoin::{
+    consensus::serialize, psbt::SigningKeys, secp256k1::All, NetworkKind, Psbt, TapLeafHash,
+    XOnlyPublicKey,
+};
 
 use super::{CoreError, Signing, SigningKeyMap, VaultManager};
 
@@ -18,22 +28,42 @@ impl Signing for VaultManager {
         let key_map = SigningKeyMap::from_privkey_slice(secp, privkey, network_kind)
             .map_err(|err| CoreError::InvalidPrivateKey(err.to_string()))?;
 
-        psbt.sign_by_key_map(&key_map, secp).map_err(|err| {
-            let (_, errors) = err;
-            let error_messages: Vec<String> = errors.values().map(|e| e.to_string()).collect();
-            CoreError::SigningPSBTFailed(error_messages.join(", "))
-        })?;
+        let (hex, _) =
+            <VaultManager as Signing>::sign_psbt_by_multiple_keys(psbt, &key_map, finalize)?;
+        Ok((hex, key_map))
+    }
+
+    fn sign_psbt_by_multiple_keys(
+        psbt: &mut Psbt,
+        key_map: &SigningKeyMap,
+        finalize: bool,
+    ) -> Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError> {
+        if key_map.is_empty() {
+            return Err(CoreError::SigningKeyMapIsEmpty);
+        }
+
+        let used = psbt
+            .sign_by_key_map(key_map, get_global_secp())
+            .map_err(|err| {
+                let (_, errors) = err;
+                let error_messages: Vec<String> = errors.values().map(|e| e.to_string()).collect();
+                CoreError::SigningPSBTFailed(error_messages.join(", "))
+            })?;
+
+        let signed_keys = used
+            .into_values()
+            .flat_map(|keys| match keys {
+                SigningKeys::Schnorr(keys) => keys,
+                SigningKeys::Ecdsa(keys) => keys.into_iter().map(XOnlyPublicKey::from).collect(),
+            })
+            .collect();
 
         if !finalize {
-            return Ok((psbt.serialize(), key_map));
+            return Ok((psbt.serialize(), signed_keys));
         }
 
-        <Psbt as SignByKeyMap<All>>::finalize(psbt);
-        let tx = psbt
-            .clone()
-            .extract_tx()
-            .map_err(|e| CoreError::FailedToExtractTx(e.to_string()))?;
-        Ok((serialize(&tx), key_map))
+        let tx_hex = <VaultManager as Signing>::finalize_psbt_and_extract_tx(psbt)?;
+        Ok((tx_hex, signed_keys))
     }
 
     fn sign_psbt_and_collect_tap_script_sigs(
@@ -72,6 +102,11 @@ impl Signing for VaultManager {
             return Err(CoreError::MismatchBetweenNumberOfInputsAndTapScriptSigs);
         }
 
+        let rejected = verify_tap_script_sigs(psbt, tap_script_sigs);
+        if !rejected.is_empty() {
+            return Err(CoreError::RejectedTapScriptSigs(rejected));
+        }
+
         for (index, input) in psbt.inputs.iter_mut().enumerate() {
             if let Some(sigs) = tap_script_sigs.get(index as u64) {
                 for tap_script_sig in sigs {
@@ -85,8 +120,63 @@ impl Signing for VaultManager {
         Ok(psbt.serialize())
     }
 
+    fn sign_psbt_by_external_signer<S: ExternalSigner>(
+        psbt: &mut Psbt,
+        signer: &S,
+    ) -> Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError> {
+        let requests = sighash_requests(psbt, &signer.x_only_pubkeys())?;
+        let signatures = requests
+            .iter()
+            .map(|request| signer.sign_schnorr(request))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| CoreError::ExternalSigner(e.to_string()))?;
+
+        let signed_keys = insert_external_signatures(psbt, &requests, &signatures)?;
+        Ok((psbt.serialize(), signed_keys))
+    }
+
+    async fn sign_psbt_by_async_external_signer<S: AsyncExternalSigner + Sync>(
+        psbt: &mut Psbt,
+        signer: &S,
+    ) -> Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError> {
+        let requests = sighash_requests(psbt, &signer.x_only_pubkeys())?;
+        let mut signatures = Vec::with_capacity(requests.len());
+        for request in &requests {
+            let signature = signer
+                .sign_schnorr(request)
+                .await
+                .map_err(|e| CoreError::ExternalSigner(e.to_string()))?;
+            signatures.push(signature);
+        }
+
+        let signed_keys = insert_external_signatures(psbt, &requests, &signatures)?;
+        Ok((psbt.serialize(), signed_keys))
+    }
+
+    fn combine_psbts(psbt: &mut Psbt, others: &[Psbt]) -> Result<Self::PsbtHex, CoreError> {
+        combine_psbts(psbt, others)?;
+        Ok(psbt.serialize())
+    }
+
+    fn finalize_psbt(
+        psbt: &mut Psbt,
+        leaves: &BTreeMap<usize, TapLeafHash>,
+    ) -> Result<PsbtSigningStatus, CoreError> {
+        <Psbt as SignByKeyMap<All>>::finalize(psbt, leaves)
+    }
+
     fn finalize_psbt_and_extract_tx(psbt: &mut Psbt) -> Result<Self::TxHex, CoreError> {
-        <Psbt as SignByKeyMap<All>>::finalize(psbt);
+        let status = <VaultManager as Signing>::finalize_psbt(psbt, &BTreeMap::new())?;
+        if !status.ready {
+            return Err(CoreError::InputsNotFinalized(
+                status
+                    .inputs
+                    .into_iter()
+                    .filter(|input| !input.finalized)
+                    .collect(),
+            ));
+        }
+
         let tx = psbt
             .clone()
             .extract_tx()
@@ -1,17 +1,26 @@
 use bitcoin::{
+    absolute,
+    hashes::sha256,
     key::{Secp256k1, UntweakedPublicKey},
+    relative,
     secp256k1::All,
     taproot::{TaprootBuilder, TaprootSpendInfo},
     ScriptBuf, TapNodeHash, XOnlyPublicKey,
 };
 
 use super::{
-    BuildCustodianAndPartyBranch, BuildCustodianOnlyBranch, BuildPartyWithSequenceVerification,
-    BuildTwoPartyBranch, CoreError, CustodianAndPartyBranch, CustodianOnlyBranch,
-    PartyWithSequenceVerification, TwoPartyBranch,
+    aggregate_custodian_pubkeys, custodian_signature_count, validate_distinct_keys, BranchLayout,
+    BuildCustodianAndPartyBranch, BuildCustodianOnlyBranch, BuildCustodianSubsetBranch,
+    BuildCustodianWithLockTimeBranch, BuildHashlockBranch, BuildPartyWithLockTimeVerification,
+    BuildPartyWithSequenceVerification, BuildTwoPartyBranch, CoreError, CustodianAndPartyBranch,
+    CustodianOnlyBranch, CustodianScriptBuilder, CustodianSubsetBranch, CustodianSubsetParams,
+    CustodianWithLockTimeBranch, HashlockBranch, PartyWithLockTimeVerification,
+    PartyWithSequenceVerification, TwoPartyBranch, UPCEmergencyParams,
+    MAX_CUSTODIAN_SUBSET_COMBINATIONS,
 };
 
 use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 
 lazy_static! {
     // NUMS BIP-341
@@ -24,16 +33,63 @@ lazy_static! {
     .unwrap();
 }
 
+/// The internal key a taproot tree is finalized with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TaprootInternalKey {
+    /// The unspendable NUMS point, only script-path spends are possible.
+    #[default]
+    Nums,
+    /// The MuSig2 aggregated key of all custodians, allowing a key-path spend when all of them cooperate.
+    CustodianMusig2,
+    /// The FROST group key of the custodians, allowing a key-path spend by any threshold of them.
+    FrostGroupKey(XOnlyPublicKey),
+}
+
+impl TaprootInternalKey {
+    pub fn resolve(
+        &self,
+        custodian_pubkeys: &[XOnlyPublicKey],
+    ) -> Result<XOnlyPublicKey, CoreError> {
+        match self {
+            TaprootInternalKey::Nums => Ok(*NUMS_BIP_341),
+            TaprootInternalKey::CustodianMusig2 => aggregate_custodian_pubkeys(custodian_pubkeys),
+            TaprootInternalKey::FrostGroupKey(group_key) => Ok(*group_key),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct UPCTaprootTree {
     pub user_protocol_branch: TwoPartyBranch,
     pub custodian_user_branch: CustodianAndPartyBranch,
     pub custodian_protocol_branch: CustodianAndPartyBranch,
+    pub emergency_branch: Option<CustodianWithLockTimeBranch>,
 }
 
 #[derive(Debug, Clone)]
 pub struct CustodianOnlyTree {
     pub custodian_only_branch: CustodianOnlyBranch,
+    /// The k-of-k leaves of the subset mode, empty otherwise.
+    pub subset_branches: Vec<CustodianSubsetLeaf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CustodianSubsetLeaf {
+    /// The sorted custodians of the subset.
+    pub custodian_pubkeys: Vec<XOnlyPublicKey>,
+    pub branch: CustodianSubsetBranch,
+    /// The huffman weight of the leaf.
+    pub weight: u32,
+}
+
+impl CustodianOnlyTree {
+    /// The subset leaf spendable by `signers` with the shortest control block.
+    pub fn subset_leaf(&self, signers: &[XOnlyPublicKey]) -> Option<&CustodianSubsetLeaf> {
+        self.subset_branches
+            .iter()
+            .filter(|leaf| leaf.custodian_pubkeys.iter().all(|pk| signers.contains(pk)))
+            .max_by_key(|leaf| leaf.weight)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +98,12 @@ pub struct TimeGatedTree {
     pub custodian_only_branch: CustodianOnlyBranch,
 }
 
+#[derive(Debug, Clone)]
+pub struct HtlcTree {
+    pub hashlock_branch: HashlockBranch,
+    pub timelock_branch: PartyWithLockTimeVerification,
+}
+
 #[derive(Debug, Clone)]
 pub struct TaprootTree<T> {
     pub root: TaprootSpendInfo,
@@ -90,28 +152,155 @@ impl TaprootTree<UPCTaprootTree> {
         custodian_pubkeys: Vec<XOnlyPublicKey>,
         custodian_quorum: u8,
     ) -> Result<Self, CoreError> {
+        Self::new_with_internal_key(
+            secp,
+            user_pubkey,
+            protocol_pubkey,
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::Nums,
+        )
+    }
+
+    /// Same as [`Self::new`], finalized with the given internal key.
+    pub fn new_with_internal_key(
+        secp: &Secp256k1<All>,
+        user_pubkey: XOnlyPublicKey,
+        protocol_pubkey: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+    ) -> Result<Self, CoreError> {
+        Self::new_with_emergency(
+            secp,
+            user_pubkey,
+            protocol_pubkey,
+            custodian_pubkeys,
+            custodian_quorum,
+            internal_key,
+            None,
+        )
+    }
+
+    /// Same as [`Self::new_with_internal_key`], with an optional emergency recovery leaf.
+    ///
+    /// When the emergency leaf is present, the standard branches are moved one level down:
+    ///
+    /// ```text
+    ///              Root
+    ///             /    \
+    ///            /      \
+    ///   Emergency        Standard
+    ///   CLTV + C         /      \
+    ///                 U + P    Custodian
+    ///                           /     \
+    ///                        U + C   P + C
+    /// ```
+    ///
+    /// The emergency quorum must be higher than `custodian_quorum`.
+    pub fn new_with_emergency(
+        secp: &Secp256k1<All>,
+        user_pubkey: XOnlyPublicKey,
+        protocol_pubkey: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        emergency: Option<UPCEmergencyParams>,
+    ) -> Result<Self, CoreError> {
+        Self::new_weighted(
+            secp,
+            user_pubkey,
+            protocol_pubkey,
+            custodian_pubkeys,
+            None,
+            custodian_quorum,
+            internal_key,
+            emergency,
+            BranchLayout::Legacy,
+        )
+    }
+
+    /// Same as [`Self::new_with_emergency`], the `U + C` and `P + C` branches use the weighted
+    /// custodian script when `custodian_weights` is set, `custodian_quorum` being the total
+    /// weight required.
+    ///
+    /// The emergency leaf keeps counting one vote per custodian, its quorum must be higher than
+    /// the number of custodians the weighted quorum may take.
+    ///
+    /// The custodian leaves are encoded following `layout`, see [`BranchLayout`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_weighted(
+        secp: &Secp256k1<All>,
+        user_pubkey: XOnlyPublicKey,
+        protocol_pubkey: XOnlyPublicKey,
+        custodian_pubkeys: Vec<XOnlyPublicKey>,
+        custodian_weights: Option<&[u8]>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        emergency: Option<UPCEmergencyParams>,
+        layout: BranchLayout,
+    ) -> Result<Self, CoreError> {
+        let parties = [user_pubkey, protocol_pubkey];
+        validate_distinct_keys(&[&parties[..], &custodian_pubkeys].concat())?;
+
         let mut builder = TaprootBuilder::new();
 
         let up_branch = <ScriptBuf as BuildTwoPartyBranch>::build(&user_pubkey, &protocol_pubkey)?;
 
-        let uc_branch = <ScriptBuf as BuildCustodianAndPartyBranch>::build(
-            &user_pubkey,
+        let uc_branch = build_custodian_branch(
+            Some(&user_pubkey),
             &custodian_pubkeys,
+            custodian_weights,
             custodian_quorum,
+            layout,
         )?;
 
-        let pc_branch = <ScriptBuf as BuildCustodianAndPartyBranch>::build(
-            &protocol_pubkey,
+        let pc_branch = build_custodian_branch(
+            Some(&protocol_pubkey),
             &custodian_pubkeys,
+            custodian_weights,
             custodian_quorum,
+            layout,
         )?;
 
-        builder = builder.add_leaf(1, up_branch.clone())?;
-        builder = builder.add_leaf(2, uc_branch.clone())?;
-        builder = builder.add_leaf(2, pc_branch.clone())?;
+        let emergency_branch = match emergency {
+            Some(emergency) => {
+                // With weights the standard quorum is a weight, compare with the most custodians
+                // it may take to reach it
+                let standard_quorum =
+                    custodian_signature_count(custodian_weights, custodian_quorum);
+                if emergency.custodian_quorum <= standard_quorum
+                    || emergency.custodian_quorum as usize > custodian_pubkeys.len()
+                {
+                    return Err(CoreError::InvalidEmergencyQuorum(
+                        emergency.custodian_quorum,
+                    ));
+                }
+
+                Some(<ScriptBuf as BuildCustodianWithLockTimeBranch>::build(
+                    &custodian_pubkeys,
+                    emergency.custodian_quorum,
+                    emergency.lock_time,
+                    layout,
+                )?)
+            }
+            None => None,
+        };
+
+        let depth = match emergency_branch {
+            Some(ref branch) => {
+                builder = builder.add_leaf(1, branch.clone())?;
+                1
+            }
+            None => 0,
+        };
+
+        builder = builder.add_leaf(depth + 1, up_branch.clone())?;
+        builder = builder.add_leaf(depth + 2, uc_branch.clone())?;
+        builder = builder.add_leaf(depth + 2, pc_branch.clone())?;
 
         let taproot_spend_info = builder
-            .finalize(secp, *NUMS_BIP_341)
+            .finalize(secp, internal_key.resolve(&custodian_pubkeys)?)
             .map_err(|_| CoreError::TaprootFinalizationFailed)?;
 
         Ok(Self {
@@ -120,6 +309,7 @@ impl TaprootTree<UPCTaprootTree> {
                 user_protocol_branch: up_branch,
                 custodian_user_branch: uc_branch,
                 custodian_protocol_branch: pc_branch,
+                emergency_branch,
             },
         })
     }
@@ -139,27 +329,178 @@ impl TaprootTree<CustodianOnlyTree> {
         secp: &Secp256k1<All>,
         custodian_pubkeys: &[XOnlyPublicKey],
         custodian_quorum: u8,
+    ) -> Result<Self, CoreError> {
+        Self::new_with_internal_key(
+            secp,
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::Nums,
+        )
+    }
+
+    /// Same as [`Self::new`], finalized with the given internal key.
+    pub fn new_with_internal_key(
+        secp: &Secp256k1<All>,
+        custodian_pubkeys: &[XOnlyPublicKey],
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+    ) -> Result<Self, CoreError> {
+        Self::new_weighted(
+            secp,
+            custodian_pubkeys,
+            None,
+            custodian_quorum,
+            internal_key,
+            BranchLayout::Legacy,
+        )
+    }
+
+    /// Same as [`Self::new_with_internal_key`], using the weighted custodian script when
+    /// `custodian_weights` is set and encoding the leaf following `layout` otherwise.
+    pub fn new_weighted(
+        secp: &Secp256k1<All>,
+        custodian_pubkeys: &[XOnlyPublicKey],
+        custodian_weights: Option<&[u8]>,
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        layout: BranchLayout,
     ) -> Result<Self, CoreError> {
         let mut builder = TaprootBuilder::new();
 
-        let only_custodian_branch =
-            <ScriptBuf as BuildCustodianOnlyBranch>::build(custodian_pubkeys, custodian_quorum)?;
+        let only_custodian_branch = build_custodian_branch(
+            None,
+            custodian_pubkeys,
+            custodian_weights,
+            custodian_quorum,
+            layout,
+        )?;
 
         builder = builder.add_leaf(0, only_custodian_branch.clone())?;
 
         let taproot_spend_info = builder
-            .finalize(secp, *NUMS_BIP_341)
+            .finalize(secp, internal_key.resolve(custodian_pubkeys)?)
+            .map_err(|_| CoreError::TaprootFinalizationFailed)?;
+
+        Ok(Self {
+            root: taproot_spend_info,
+            raw: CustodianOnlyTree {
+                custodian_only_branch: only_custodian_branch,
+                subset_branches: vec![],
+            },
+        })
+    }
+
+    /// Same as [`Self::new_with_internal_key`], adding one k-of-k leaf per subset of
+    /// `custodian_quorum` custodians, `k = custodian_quorum`.
+    ///
+    /// A subset weighs the product of its custodians availabilities, leaves are placed with
+    /// [`TaprootBuilder::with_huffman_tree`] and the k-of-n leaf, kept as fallback, is the lightest.
+    pub fn new_with_subsets(
+        secp: &Secp256k1<All>,
+        custodian_pubkeys: &[XOnlyPublicKey],
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        subsets: &CustodianSubsetParams,
+        layout: BranchLayout,
+    ) -> Result<Self, CoreError> {
+        let only_custodian_branch = <ScriptBuf as BuildCustodianOnlyBranch>::build(
+            custodian_pubkeys,
+            custodian_quorum,
+            layout,
+        )?;
+
+        let subset_branches = custodian_subsets(custodian_pubkeys, custodian_quorum, subsets)?
+            .into_iter()
+            .map(|(subset, weight)| {
+                Ok(CustodianSubsetLeaf {
+                    branch: <ScriptBuf as BuildCustodianSubsetBranch>::build(
+                        &subset,
+                        subsets.aggregate,
+                    )?,
+                    custodian_pubkeys: subset,
+                    weight,
+                })
+            })
+            .collect::<Result<Vec<_>, CoreError>>()?;
+
+        let builder = TaprootBuilder::with_huffman_tree(
+            subset_branches
+                .iter()
+                .map(|leaf| (leaf.weight, leaf.branch.clone()))
+                .chain(std::iter::once((1, only_custodian_branch.clone()))),
+        )?;
+
+        let taproot_spend_info = builder
+            .finalize(secp, internal_key.resolve(custodian_pubkeys)?)
             .map_err(|_| CoreError::TaprootFinalizationFailed)?;
 
         Ok(Self {
             root: taproot_spend_info,
             raw: CustodianOnlyTree {
                 custodian_only_branch: only_custodian_branch,
+                subset_branches,
             },
         })
     }
 }
 
+/// Enumerates the subsets of `custodian_quorum` custodians with their huffman weight, most
+/// available first and truncated to `max_leaves`.
+fn custodian_subsets(
+    custodian_pubkeys: &[XOnlyPublicKey],
+    custodian_quorum: u8,
+    params: &CustodianSubsetParams,
+) -> Result<Vec<(Vec<XOnlyPublicKey>, u32)>, CoreError> {
+    let n = custodian_pubkeys.len();
+    let k = custodian_quorum as usize;
+
+    if k == 0 || k > n || params.max_leaves == Some(0) {
+        return Err(CoreError::InvalidCustodianSubset);
+    }
+
+    let availabilities: Vec<f64> = match &params.availabilities {
+        Some(availabilities) => {
+            if availabilities.len() != n || availabilities.iter().any(|a| *a > 100) {
+                return Err(CoreError::InvalidCustodianSubset);
+            }
+            availabilities.iter().map(|a| *a as f64 / 100.0).collect()
+        }
+        None => vec![1.0; n],
+    };
+
+    // C(n, k), each step stays an integer
+    let combinations = (0..k).fold(1u128, |acc, i| acc * (n - i) as u128 / (i + 1) as u128);
+    if combinations > MAX_CUSTODIAN_SUBSET_COMBINATIONS as u128 {
+        return Err(CoreError::TooManyCustodianSubsets(combinations as usize));
+    }
+
+    let mut subsets = Vec::with_capacity(combinations as usize);
+    let mut indices: Vec<usize> = (0..k).collect();
+    loop {
+        let probability: f64 = indices.iter().map(|&i| availabilities[i]).product();
+        let mut subset: Vec<XOnlyPublicKey> =
+            indices.iter().map(|&i| custodian_pubkeys[i]).collect();
+        subset.sort();
+        subsets.push((subset, ((probability * 1_000_000.0) as u32).max(1)));
+
+        // Next combination in lexicographic order
+        let Some(pos) = (0..k).rev().find(|&i| indices[i] != i + n - k) else {
+            break;
+        };
+        indices[pos] += 1;
+        for i in pos + 1..k {
+            indices[i] = indices[i - 1] + 1;
+        }
+    }
+
+    subsets.sort_by_key(|subset| std::cmp::Reverse(subset.1));
+    if let Some(max_leaves) = params.max_leaves {
+        subsets.truncate(max_leaves);
+    }
+
+    Ok(subsets)
+}
+
 impl TaprootTree<TimeGatedTree> {
     /// Creates a Taproot locking script with only custodian spending path and a sequence locked path.
     ///
@@ -181,15 +522,47 @@ impl TaprootTree<TimeGatedTree> {
         party: &XOnlyPublicKey,
         custodian_pubkeys: &[XOnlyPublicKey],
         custodian_quorum: u8,
-        sequence: u16,
+        sequence: relative::LockTime,
     ) -> Result<Self, CoreError> {
+        Self::new_weighted(
+            secp,
+            party,
+            custodian_pubkeys,
+            None,
+            custodian_quorum,
+            sequence,
+            BranchLayout::Legacy,
+        )
+    }
+
+    /// Same as [`Self::new`], using the weighted custodian script when `custodian_weights` is set.
+    /// Both leaves are encoded following `layout`, see [`BranchLayout`].
+    pub fn new_weighted(
+        secp: &Secp256k1<All>,
+        party: &XOnlyPublicKey,
+        custodian_pubkeys: &[XOnlyPublicKey],
+        custodian_weights: Option<&[u8]>,
+        custodian_quorum: u8,
+        sequence: relative::LockTime,
+        layout: BranchLayout,
+    ) -> Result<Self, CoreError> {
+        validate_distinct_keys(&[&[*party], custodian_pubkeys].concat())?;
+
         let mut builder = TaprootBuilder::new();
 
-        let only_custodian_branch =
-            <ScriptBuf as BuildCustodianOnlyBranch>::build(custodian_pubkeys, custodian_quorum)?;
+        let only_custodian_branch = build_custodian_branch(
+            None,
+            custodian_pubkeys,
+            custodian_weights,
+            custodian_quorum,
+            layout,
+        )?;
 
-        let csv_branch =
-            <ScriptBuf as BuildPartyWithSequenceVerification>::build(party, sequence.into())?;
+        let csv_branch = <ScriptBuf as BuildPartyWithSequenceVerification>::build(
+            party,
+            sequence.to_consensus_u32().into(),
+            layout,
+        )?;
 
         builder = builder.add_leaf(1, csv_branch.clone())?;
         builder = builder.add_leaf(1, only_custodian_branch.clone())?;
@@ -208,6 +581,89 @@ impl TaprootTree<TimeGatedTree> {
     }
 }
 
+impl TaprootTree<HtlcTree> {
+    /// Creates a Taproot locking script for a hash time locked contract.
+    ///
+    /// ```text
+    ///        Root
+    ///       /    \
+    ///      /      \
+    ///     /        \
+    ///    /          \
+    ///   1            1
+    ///   |            |
+    ///   |            |
+    /// Hashlock     Timelock
+    /// Recipient    Refund
+    /// ```
+    ///
+    /// - Hashlock: `OP_SIZE 32 OP_EQUALVERIFY OP_SHA256 <payment_hash> OP_EQUALVERIFY <recipient> OP_CHECKSIG`
+    /// - Timelock: `<lock_time> OP_CHECKLOCKTIMEVERIFY OP_VERIFY <refund> OP_CHECKSIG`
+    pub fn new(
+        secp: &Secp256k1<All>,
+        recipient: &XOnlyPublicKey,
+        refund: &XOnlyPublicKey,
+        payment_hash: &sha256::Hash,
+        lock_time: absolute::LockTime,
+    ) -> Result<Self, CoreError> {
+        validate_distinct_keys(&[*recipient, *refund])?;
+
+        let mut builder = TaprootBuilder::new();
+
+        let hashlock_branch = <ScriptBuf as BuildHashlockBranch>::build(payment_hash, recipient)?;
+
+        let timelock_branch =
+            <ScriptBuf as BuildPartyWithLockTimeVerification>::build(refund, lock_time)?;
+
+        builder = builder.add_leaf(1, hashlock_branch.clone())?;
+        builder = builder.add_leaf(1, timelock_branch.clone())?;
+
+        let taproot_spend_info = builder
+            .finalize(secp, *NUMS_BIP_341)
+            .map_err(|_| CoreError::TaprootFinalizationFailed)?;
+
+        Ok(Self {
+            root: taproot_spend_info,
+            raw: HtlcTree {
+                hashlock_branch,
+                timelock_branch,
+            },
+        })
+    }
+}
+
+/// Builds a custodian branch, prefixed by `party` when given.
+fn build_custodian_branch(
+    party: Option<&XOnlyPublicKey>,
+    custodian_pubkeys: &[XOnlyPublicKey],
+    custodian_weights: Option<&[u8]>,
+    custodian_quorum: u8,
+    layout: BranchLayout,
+) -> Result<ScriptBuf, CoreError> {
+    match (custodian_weights, party) {
+        (Some(_), _) if layout == BranchLayout::Miniscript => Err(CoreError::InvalidParams(
+            "weighted custodian leaves only have the legacy layout".to_string(),
+        )),
+        (Some(weights), _) => CustodianScriptBuilder::build_weighted(
+            custodian_pubkeys,
+            weights,
+            custodian_quorum,
+            party,
+        ),
+        (None, Some(party)) => <ScriptBuf as BuildCustodianAndPartyBranch>::build(
+            party,
+            custodian_pubkeys,
+            custodian_quorum,
+            layout,
+        ),
+        (None, None) => <ScriptBuf as BuildCustodianOnlyBranch>::build(
+            custodian_pubkeys,
+            custodian_quorum,
+            layout,
+        ),
+    }
+}
+
 impl<T> TaprootTree<T> {
     pub fn internal_key(&self) -> UntweakedPublicKey {
         self.root.internal_key()
@@ -1,10 +1,24 @@
-use bitcoin::{NetworkKind, Psbt, PublicKey, XOnlyPublicKey};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    future::Future,
+};
+
+use bitcoin::{
+    absolute, hashes::sha256, relative, secp256k1::schnorr, NetworkKind, Psbt, PublicKey,
+    TapLeafHash, XOnlyPublicKey,
+};
+use musig2::{AggNonce, PartialSignature, PubNonce, SecNonce};
 
 use super::{
-    CoreError, CustodianOnlyLockingParams, CustodianOnlyUnlockingParams, DataScript,
-    DestinationChain, DestinationRecipientAddress, DestinationTokenAddress, LockingOutput,
-    LockingScript, SigningKeyMap, TapScriptSigsMap, TimeGatedLockingParams,
-    TimeGatedUnlockingParams, UPCLockingParams, UPCUnlockingParams,
+    BranchLayout, CoreError, CustodianOnlyLockingParams, CustodianOnlyUnlockingParams,
+    CustodianRotationParams, CustodianSubsetParams, DataScript, DestinationChain,
+    DestinationRecipientAddress, DestinationTokenAddress, FrostIdentifier, FrostKeyShare,
+    FrostNonceCommitment, FrostPublicKeyPackage, FrostSecNonce, FrostSignatureShare,
+    FundedLockingParams, HtlcLockingParams, HtlcUnlockingParams, LockingOutput, LockingScript,
+    PolicyLockingParams, PolicyUnlockingParams, PsbtSigningStatus, SighashRequest, SigningKeyMap,
+    TapScriptSigsMap, TaprootInternalKey, TimeGatedLockingParams, TimeGatedUnlockingParams,
+    UPCEmergencyParams, UPCLockingParams, UPCUnlockingParams, VaultPolicy, HASH_SIZE,
 };
 
 pub trait UPC {
@@ -14,11 +28,15 @@ pub trait UPC {
 
     fn build_unlocking_psbt(&self, params: &UPCUnlockingParams) -> Result<Psbt, Self::Error>;
 
+    /// The script of the legacy [`BranchLayout`], `build_locking_output` follows `branch_layout`.
     fn locking_script(
         user_pub_key: &PublicKey,
         protocol_pub_key: &PublicKey,
         custodian_pub_keys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        emergency: Option<UPCEmergencyParams>,
     ) -> Result<LockingScript, Self::Error>;
 
     fn data_script<'a>(
@@ -42,9 +60,13 @@ pub trait CustodianOnly {
         params: &CustodianOnlyUnlockingParams,
     ) -> Result<Psbt, Self::Error>;
 
+    /// The script of the legacy [`BranchLayout`], `build_locking_output` follows `branch_layout`.
     fn locking_script(
         custodian_pub_keys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+        subsets: Option<&CustodianSubsetParams>,
     ) -> Result<LockingScript, Self::Error>;
 
     fn data_script<'a>(
@@ -63,13 +85,80 @@ pub trait TimeGated {
         params: &TimeGatedLockingParams,
     ) -> Result<LockingOutput, Self::Error>;
     fn build_unlocking_psbt(&self, params: &TimeGatedUnlockingParams) -> Result<Psbt, Self::Error>;
+    fn data_script(
+        &self,
+        party_pub_key: &PublicKey,
+        custodian_quorum: u8,
+        sequence: relative::LockTime,
+    ) -> Result<DataScript, Self::Error>;
+    /// The script of the legacy [`BranchLayout`], `build_locking_output` follows `branch_layout`.
     fn locking_script(
         party_pub_key: &PublicKey,
         custodian_pub_keys: &[PublicKey],
+        custodian_weights: Option<&[u8]>,
         custodian_quorum: u8,
-        sequence: u16,
+        sequence: relative::LockTime,
     ) -> Result<LockingScript, Self::Error>;
 }
+/// Funds the locking output of a vault from wallet utxos.
+pub trait FundedLocking {
+    type Error;
+
+    /// Builds the psbt locking `params.locking_output`, ready to be signed by the wallet.
+    ///
+    /// The utxos are picked with branch-and-bound, avoiding a change output, and fall back to
+    /// knapsack selection with a change output to `params.change_script_pubkey`.
+    fn build_funded_locking_psbt(&self, params: &FundedLockingParams) -> Result<Psbt, Self::Error>;
+}
+
+/// Moves vault funds from one custodian set to another.
+pub trait CustodianRotation {
+    type Error;
+
+    /// Builds the psbt spending `params.inputs` with the old quorum into the vault
+    /// of the new custodian set.
+    fn build_rotation_psbt(&self, params: &CustodianRotationParams) -> Result<Psbt, Self::Error>;
+
+    /// OP_RETURN recording the old and the new custodian group uids.
+    fn rotation_data_script(
+        &self,
+        old_custodian_group_uid: &[u8; HASH_SIZE],
+        new_custodian_group_uid: &[u8; HASH_SIZE],
+    ) -> Result<DataScript, Self::Error>;
+}
+
+pub trait Htlc {
+    type Error;
+    fn build_locking_output(
+        &self,
+        params: &HtlcLockingParams,
+    ) -> Result<LockingOutput, Self::Error>;
+
+    /// Builds the claim (recipient with the preimage) or the refund (sender after the lock time) psbt.
+    fn build_unlocking_psbt(&self, params: &HtlcUnlockingParams) -> Result<Psbt, Self::Error>;
+
+    fn locking_script(
+        recipient_pub_key: &PublicKey,
+        refund_pub_key: &PublicKey,
+        payment_hash: &sha256::Hash,
+        lock_time: absolute::LockTime,
+    ) -> Result<LockingScript, Self::Error>;
+}
+
+/// Vaults laid out from a declarative [`VaultPolicy`] instead of a hand-written tree.
+pub trait PolicyVault {
+    type Error;
+    fn build_locking_output(
+        &self,
+        params: &PolicyLockingParams,
+    ) -> Result<LockingOutput, Self::Error>;
+
+    /// Builds a psbt spending the leaf named `params.leaf_name`.
+    fn build_unlocking_psbt(&self, params: &PolicyUnlockingParams) -> Result<Psbt, Self::Error>;
+
+    fn locking_script(policy: &VaultPolicy) -> Result<LockingScript, Self::Error>;
+}
+
 pub trait Signing {
     type PsbtHex;
 
@@ -82,20 +171,115 @@ pub trait Signing {
         finalize: bool,
     ) -> Result<(Self::PsbtHex, SigningKeyMap), CoreError>;
 
+    /// Signs with every key of `key_map` in one pass, returns the keys that produced a signature.
+    fn sign_psbt_by_multiple_keys(
+        psbt: &mut Psbt,
+        key_map: &SigningKeyMap,
+        finalize: bool,
+    ) -> Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError>;
+
     fn sign_psbt_and_collect_tap_script_sigs(
         psbt: &mut Psbt,
         privkey: &[u8],
         network_kind: NetworkKind,
     ) -> Result<TapScriptSigsMap, CoreError>;
 
+    /// Verifies the submitted signatures first, none is added if any is rejected.
     fn aggregate_tap_script_sigs(
         psbt: &mut Psbt,
         tap_script_sigs: &TapScriptSigsMap,
     ) -> Result<Self::PsbtHex, CoreError>;
 
+    /// Signs with the keys held by `signer`, e.g. in an HSM, without the private keys entering
+    /// the process. The returned signatures are verified before being added to `tap_script_sigs`.
+    fn sign_psbt_by_external_signer<S: ExternalSigner>(
+        psbt: &mut Psbt,
+        signer: &S,
+    ) -> Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError>;
+
+    /// Async version of [`Signing::sign_psbt_by_external_signer`], for remote signers like a KMS.
+    fn sign_psbt_by_async_external_signer<S: AsyncExternalSigner + Sync>(
+        psbt: &mut Psbt,
+        signer: &S,
+    ) -> impl Future<Output = Result<(Self::PsbtHex, BTreeSet<XOnlyPublicKey>), CoreError>> + Send;
+
+    /// Merges partially signed copies of `psbt` into it, see [`combine_psbts`](super::combine_psbts).
+    fn combine_psbts(psbt: &mut Psbt, others: &[Psbt]) -> Result<Self::PsbtHex, CoreError>;
+
+    /// Finalizes the satisfied inputs and reports the leaf, quorum and missing signatures of
+    /// every input. Each input is finalized through its key path or the satisfied leaf with the
+    /// smallest witness, unless `leaves` forces a leaf for it.
+    fn finalize_psbt(
+        psbt: &mut Psbt,
+        leaves: &BTreeMap<usize, TapLeafHash>,
+    ) -> Result<PsbtSigningStatus, CoreError>;
+
+    /// Fails with [`CoreError::InputsNotFinalized`] when an input is not satisfied.
     fn finalize_psbt_and_extract_tx(psbt: &mut Psbt) -> Result<Self::TxHex, CoreError>;
 }
 
+/// Signs taproot script path sighashes with keys kept outside of the vault process.
+pub trait ExternalSigner {
+    type Error: Display;
+
+    /// Keys the signer can sign with.
+    fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey>;
+
+    /// BIP-340 signature of `request.message` by `request.key`.
+    fn sign_schnorr(&self, request: &SighashRequest) -> Result<schnorr::Signature, Self::Error>;
+}
+
+/// [`ExternalSigner`] for signers reached over the network.
+pub trait AsyncExternalSigner {
+    type Error: Display;
+
+    /// Keys the signer can sign with.
+    fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey>;
+
+    /// BIP-340 signature of `request.message` by `request.key`.
+    fn sign_schnorr(
+        &self,
+        request: &SighashRequest,
+    ) -> impl Future<Output = Result<schnorr::Signature, Self::Error>> + Send;
+}
+
+/// MuSig2 rounds for a cooperative key-path spend of inputs locked with
+/// [`TaprootInternalKey::CustodianMusig2`](super::TaprootInternalKey::CustodianMusig2).
+///
+/// Every call works on all inputs of the PSBT, nonces and partial signatures are indexed by input.
+///
+/// 1. Each custodian calls `generate_musig2_nonces` and shares the public nonces.
+/// 2. Each custodian calls `sign_musig2_partial` with its secret nonces and the aggregated nonces.
+/// 3. The coordinator calls `aggregate_musig2_partial_sigs` to set `tap_key_sig` on every input,
+///    then finalizes the PSBT with [`Signing::finalize_psbt_and_extract_tx`].
+pub trait Musig2Signing: Signing {
+    /// Fresh nonces are drawn from the OS RNG for every call, they are used for one session.
+    fn generate_musig2_nonces(
+        psbt: &Psbt,
+        custodian_pubkeys: &[PublicKey],
+        privkey: &[u8],
+    ) -> Result<(Vec<SecNonce>, Vec<PubNonce>), CoreError>;
+
+    /// `pub_nonces` contains the public nonces of every custodian.
+    fn aggregate_musig2_nonces(pub_nonces: &[Vec<PubNonce>]) -> Result<Vec<AggNonce>, CoreError>;
+
+    fn sign_musig2_partial(
+        psbt: &Psbt,
+        custodian_pubkeys: &[PublicKey],
+        privkey: &[u8],
+        sec_nonces: Vec<SecNonce>,
+        agg_nonces: &[AggNonce],
+    ) -> Result<Vec<PartialSignature>, CoreError>;
+
+    /// `pub_nonces` and `partial_sigs` are ordered like `custodian_pubkeys`.
+    fn aggregate_musig2_partial_sigs(
+        psbt: &mut Psbt,
+        custodian_pubkeys: &[PublicKey],
+        pub_nonces: &[Vec<PubNonce>],
+        partial_sigs: &[Vec<PartialSignature>],
+    ) -> Result<Self::PsbtHex, CoreError>;
+}
+
 pub trait BuildTwoPartyBranch {
     fn build(x: &XOnlyPublicKey, y: &XOnlyPublicKey) -> Result<Self, CoreError>
     where
@@ -107,6 +291,18 @@ pub trait BuildCustodianAndPartyBranch {
         x: &XOnlyPublicKey,
         custodian_pub_keys: &[XOnlyPublicKey],
         custodian_quorum: u8,
+        layout: BranchLayout,
+    ) -> Result<Self, CoreError>
+    where
+        Self: Sized;
+}
+
+pub trait BuildCustodianWithLockTimeBranch {
+    fn build(
+        custodian_pub_keys: &[XOnlyPublicKey],
+        custodian_quorum: u8,
+        lock_time: absolute::LockTime,
+        layout: BranchLayout,
     ) -> Result<Self, CoreError>
     where
         Self: Sized;
@@ -116,13 +312,68 @@ pub trait BuildCustodianOnlyBranch {
     fn build(
         custodian_pub_keys: &[XOnlyPublicKey],
         custodian_quorum: u8,
+        layout: BranchLayout,
     ) -> Result<Self, CoreError>
     where
         Self: Sized;
 }
 
+pub trait BuildCustodianSubsetBranch {
+    fn build(custodian_pub_keys: &[XOnlyPublicKey], aggregate: bool) -> Result<Self, CoreError>
+    where
+        Self: Sized;
+}
+
 pub trait BuildPartyWithSequenceVerification {
-    fn build(x: &XOnlyPublicKey, sequence: i64) -> Result<Self, CoreError>
+    fn build(x: &XOnlyPublicKey, sequence: i64, layout: BranchLayout) -> Result<Self, CoreError>
+    where
+        Self: Sized;
+}
+
+pub trait BuildPartyWithLockTimeVerification {
+    fn build(x: &XOnlyPublicKey, lock_time: absolute::LockTime) -> Result<Self, CoreError>
+    where
+        Self: Sized;
+}
+
+pub trait BuildHashlockBranch {
+    fn build(payment_hash: &sha256::Hash, x: &XOnlyPublicKey) -> Result<Self, CoreError>
     where
         Self: Sized;
 }
+
+/// FROST rounds for a key-path spend by any `threshold` custodians of inputs locked with
+/// [`TaprootInternalKey::FrostGroupKey`](super::TaprootInternalKey::FrostGroupKey), the script
+/// leaf of the custodians staying available as fallback.
+///
+/// The key shares come from [`frost_trusted_dealer_keygen`](super::frost_trusted_dealer_keygen)
+/// or the DKG rounds. Nonce commitments and signature shares are indexed by input.
+///
+/// 1. Each signer calls `generate_frost_nonces` and sends its commitments to the coordinator.
+/// 2. The coordinator picks at least `threshold` signers and sends them all their commitments.
+/// 3. Each signer calls `sign_frost_share` with its secret nonces and the commitments.
+/// 4. The coordinator calls `aggregate_frost_signature_shares` to set `tap_key_sig` on every
+///    input, then finalizes the PSBT with [`Signing::finalize_psbt_and_extract_tx`].
+pub trait FrostSigning: Signing {
+    /// Fresh nonces are drawn from the OS RNG for every call, they are used for one session.
+    fn generate_frost_nonces(
+        psbt: &Psbt,
+        key_share: &FrostKeyShare,
+    ) -> Result<(Vec<FrostSecNonce>, Vec<FrostNonceCommitment>), CoreError>;
+
+    /// `commitments` contains the nonce commitments of every signer of the session.
+    fn sign_frost_share(
+        psbt: &Psbt,
+        key_share: &FrostKeyShare,
+        sec_nonces: Vec<FrostSecNonce>,
+        commitments: &BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+    ) -> Result<Vec<FrostSignatureShare>, CoreError>;
+
+    /// Verifies every share against the verifying share of its signer before aggregating.
+    fn aggregate_frost_signature_shares(
+        psbt: &mut Psbt,
+        public_key_package: &FrostPublicKeyPackage,
+        commitments: &BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+        signature_shares: &BTreeMap<FrostIdentifier, Vec<FrostSignatureShare>>,
+    ) -> Result<Self::PsbtHex, CoreError>;
+}
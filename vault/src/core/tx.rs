@@ -9,6 +9,7 @@ pub struct TransactionBuilder {
     inputs: Vec<TxIn>,
     outputs: Vec<TxOut>,
     rbf: bool,
+    lock_time: absolute::LockTime,
 }
 
 impl TransactionBuilder {
@@ -18,16 +19,25 @@ impl TransactionBuilder {
             inputs: Vec::new(),
             outputs: Vec::new(),
             rbf,
+            lock_time: absolute::LockTime::ZERO,
         }
     }
 
+    /// Sets the transaction lock time, inputs added afterwards get a non-final sequence
+    /// so that the lock time is enforced.
+    pub fn with_lock_time(mut self, lock_time: absolute::LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
     pub fn add_input(&mut self, outpoint: OutPoint) {
         self.inputs.push(TxIn {
             previous_output: outpoint,
             script_sig: ScriptBuf::default(),
-            sequence: match self.rbf {
-                true => Sequence::ENABLE_RBF_NO_LOCKTIME,
-                false => Sequence::MAX,
+            sequence: match (self.rbf, self.lock_time == absolute::LockTime::ZERO) {
+                (true, _) => Sequence::ENABLE_RBF_NO_LOCKTIME,
+                (false, true) => Sequence::MAX,
+                (false, false) => Sequence::ENABLE_LOCKTIME_NO_RBF,
             },
             witness: Witness::default(),
         });
@@ -62,7 +72,7 @@ impl TransactionBuilder {
     pub fn build(self) -> Transaction {
         Transaction {
             version: self.version,
-            lock_time: absolute::LockTime::ZERO,
+            lock_time: self.lock_time,
             input: self.inputs,
             output: self.outputs,
         }
@@ -30,6 +30,7 @@ pub enum UPCUnlockingType {
     UserProtocol,
     CustodianProtocol,
     CustodianUser,
+    Emergency,
 }
 
 #[derive(Debug, PartialEq)]
@@ -38,6 +39,12 @@ pub enum TimeGatedUnlockingType {
     CustodianOnly,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum HtlcUnlockingType {
+    Claim,
+    Refund,
+}
+
 #[derive(Debug, Clone)]
 pub struct LockingOutput {
     amount: u64,
@@ -114,8 +121,9 @@ pub struct TapScriptSigSerialized {
     pub key_x_only: [u8; 32],
     #[serde_as(as = "Bytes")]
     pub leaf_hash: [u8; 32],
+    /// 64 bytes for `SIGHASH_DEFAULT`, 65 bytes with the sighash type appended otherwise
     #[serde_as(as = "Bytes")]
-    pub signature: [u8; 64],
+    pub signature: Vec<u8>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -150,11 +158,7 @@ impl TapScriptSig {
 
     pub fn serialize(&self) -> Result<TapScriptSigSerialized, CoreError> {
         let key = self.key().serialize();
-        let signature: [u8; 64] = self
-            .sig()
-            .to_vec()
-            .try_into()
-            .map_err(|_| CoreError::InvalidSignatureSize)?;
+        let signature = self.sig().to_vec();
         let mut leaf_hash_bytes = vec![];
         self.leaf_hash()
             .consensus_encode(&mut leaf_hash_bytes)
@@ -200,11 +204,7 @@ impl Serialize for TapScriptSig {
                     .try_into()
                     .map_err(|e| serde::ser::Error::custom(format!("{:?}", e)))?
             },
-            signature: self
-                .sig()
-                .to_vec()
-                .try_into()
-                .map_err(|e| serde::ser::Error::custom(format!("{:?}", e)))?,
+            signature: self.sig().to_vec(),
         };
         serialized.serialize(serializer)
     }
@@ -250,6 +250,10 @@ impl TapScriptSigsMap {
     pub fn get(&self, index: InputIndex) -> Option<&Vec<TapScriptSig>> {
         self.0.get(&index)
     }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&InputIndex, &Vec<TapScriptSig>)> {
+        self.0.iter()
+    }
 }
 
 impl Serialize for TapScriptSigsMap {
@@ -284,6 +288,17 @@ fn test_tap_script_sigs_map() {
             taproot::Signature::from_slice(&[3; 64]).unwrap(),
         )],
     );
+    // A 65 bytes signature keeps its sighash type
+    map.insert(
+        1,
+        vec![TapScriptSig::new(
+            (
+                XOnlyPublicKey::from_slice(&[1; 32]).unwrap(),
+                TapLeafHash::from_slice(&[2; 32]).unwrap(),
+            ),
+            taproot::Signature::from_slice(&[[3; 64].as_slice(), &[0x83]].concat()).unwrap(),
+        )],
+    );
     let serialized = serde_json::to_string(&map).unwrap();
 
     // write to file
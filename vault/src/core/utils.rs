@@ -1,4 +1,8 @@
-use bitcoin::{PublicKey, XOnlyPublicKey};
+use std::time::Duration;
+
+use bitcoin::{relative, PublicKey, XOnlyPublicKey};
+
+use super::CoreError;
 
 pub fn convert_pubkey_to_x_only_key(pubkey: &PublicKey) -> XOnlyPublicKey {
     XOnlyPublicKey::from(*pubkey)
@@ -7,3 +11,45 @@ pub fn convert_pubkey_to_x_only_key(pubkey: &PublicKey) -> XOnlyPublicKey {
 pub fn convert_pubkeys_to_x_only_keys(pub_keys: &[PublicKey]) -> Vec<XOnlyPublicKey> {
     pub_keys.iter().map(convert_pubkey_to_x_only_key).collect()
 }
+
+/// Block-based BIP-68 relative lock time.
+pub fn relative_lock_time_from_blocks(blocks: u16) -> relative::LockTime {
+    relative::LockTime::from_height(blocks)
+}
+
+/// Time-based BIP-68 relative lock time, rounded up to the next 512-second interval
+/// so the output is never spendable earlier than requested.
+pub fn relative_lock_time_from_duration(
+    duration: Duration,
+) -> Result<relative::LockTime, CoreError> {
+    let seconds = u32::try_from(duration.as_secs() + u64::from(duration.subsec_nanos() > 0))
+        .map_err(|_| CoreError::InvalidRelativeLockTime(format!("{:?}", duration)))?;
+    relative::LockTime::from_seconds_ceil(seconds)
+        .map_err(|e| CoreError::InvalidRelativeLockTime(e.to_string()))
+}
+
+/// Parses a human readable relative lock time: `<n> blocks` (or `<n>b`) for a block-based
+/// lock, `<n>s`, `<n>m`, `<n>h`, `<n>d` or `<n>w` for a time-based one.
+pub fn parse_relative_lock_time(s: &str) -> Result<relative::LockTime, CoreError> {
+    let invalid = || CoreError::InvalidRelativeLockTime(s.to_string());
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (value, unit) = s.split_at(split);
+    let value: u64 = value.parse().map_err(|_| invalid())?;
+
+    let seconds = match unit.trim() {
+        "b" | "block" | "blocks" => {
+            return u16::try_from(value)
+                .map(relative_lock_time_from_blocks)
+                .map_err(|_| invalid());
+        }
+        "s" => value,
+        "m" => value.checked_mul(60).ok_or_else(invalid)?,
+        "h" => value.checked_mul(60 * 60).ok_or_else(invalid)?,
+        "d" => value.checked_mul(24 * 60 * 60).ok_or_else(invalid)?,
+        "w" => value.checked_mul(7 * 24 * 60 * 60).ok_or_else(invalid)?,
+        _ => return Err(invalid()),
+    };
+
+    relative_lock_time_from_duration(Duration::from_secs(seconds))
+}
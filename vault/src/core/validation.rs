@@ -0,0 +1,187 @@
+use bitcoin::{
+    taproot::TAPROOT_CONTROL_MAX_SIZE, Amount, Script, TapSighashType, Transaction, TxOut, Witness,
+    XOnlyPublicKey,
+};
+
+use super::{
+    CoreError, MAX_CUSTODIANS, MAX_STANDARD_TX_WEIGHT, MAX_TAPSCRIPT_STACK_SIZE, P2TR_DUST_LIMIT,
+};
+
+/// Checks a custodian set: non-empty, at most [`MAX_CUSTODIANS`] distinct keys and a reachable
+/// quorum, `custodian_quorum` being a total weight when `custodian_weights` is set.
+pub fn validate_custodians(
+    custodian_pubkeys: &[XOnlyPublicKey],
+    custodian_quorum: u8,
+    custodian_weights: Option<&[u8]>,
+) -> Result<(), CoreError> {
+    if custodian_pubkeys.is_empty() {
+        return Err(CoreError::EmptyCustodianSet);
+    }
+
+    if custodian_pubkeys.len() > MAX_CUSTODIANS {
+        return Err(CoreError::TooManyCustodians(custodian_pubkeys.len()));
+    }
+
+    let mut sorted_pks = custodian_pubkeys.to_vec();
+    sorted_pks.sort();
+    if sorted_pks.windows(2).any(|pair| pair[0] == pair[1]) {
+        return Err(CoreError::DuplicateCustodianKeys);
+    }
+
+    match custodian_weights {
+        Some(weights) => {
+            if weights.len() != custodian_pubkeys.len()
+                || weights.contains(&0)
+                || custodian_quorum == 0
+                || custodian_quorum as u32 > weights.iter().map(|w| *w as u32).sum()
+            {
+                return Err(CoreError::InvalidCustodianWeights);
+            }
+        }
+        None => {
+            if custodian_quorum == 0 || custodian_quorum as usize > custodian_pubkeys.len() {
+                return Err(CoreError::InvalidCustodianQuorum {
+                    quorum: custodian_quorum,
+                    custodians: custodian_pubkeys.len(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The parties of a vault (user, protocol, party, custodians...) must not share a key,
+/// otherwise one signer could satisfy two roles of a branch.
+pub fn validate_distinct_keys(keys: &[XOnlyPublicKey]) -> Result<(), CoreError> {
+    let mut sorted_keys = keys.to_vec();
+    sorted_keys.sort();
+    match sorted_keys.windows(2).find(|pair| pair[0] == pair[1]) {
+        Some(pair) => Err(CoreError::OverlappingKeys(pair[0])),
+        None => Ok(()),
+    }
+}
+
+/// Locking outputs are P2TR, they must be above its dust limit.
+pub fn validate_locking_amount(amount: u64) -> Result<(), CoreError> {
+    if amount == 0 {
+        return Err(CoreError::ZeroAmount);
+    }
+    if amount < P2TR_DUST_LIMIT {
+        return Err(CoreError::AmountBelowDust {
+            amount,
+            dust: P2TR_DUST_LIMIT,
+        });
+    }
+    Ok(())
+}
+
+/// Unlocking outputs must be above the dust limit of their script, OP_RETURN outputs excepted.
+pub fn validate_outputs(outputs: &[TxOut]) -> Result<(), CoreError> {
+    for output in outputs {
+        validate_output_amount(output.value, &output.script_pubkey)?;
+    }
+    Ok(())
+}
+
+fn validate_output_amount(amount: Amount, script_pubkey: &Script) -> Result<(), CoreError> {
+    if script_pubkey.is_op_return() {
+        return Ok(());
+    }
+    if amount == Amount::ZERO {
+        return Err(CoreError::ZeroAmount);
+    }
+    let dust = script_pubkey.minimal_non_dust();
+    if amount < dust {
+        return Err(CoreError::AmountBelowDust {
+            amount: amount.to_sat(),
+            dust: dust.to_sat(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks the witness spending a tapscript leaf with stack elements of the given sizes.
+///
+/// The element count, script and control block included, is bounded by BIP-342 and the witness
+/// weight by the standard tx weight. The control block is
+/// taken at its deepest since the leaf depth is not known when the script is built.
+pub fn validate_tapscript_witness(
+    stack_element_sizes: &[usize],
+    script: &Script,
+) -> Result<(), CoreError> {
+    let n_elements = stack_element_sizes.len() + 2;
+    if n_elements > MAX_TAPSCRIPT_STACK_SIZE {
+        return Err(CoreError::TapscriptStackLimitExceeded(n_elements));
+    }
+
+    let mut witness = Witness::new();
+    for size in stack_element_sizes {
+        witness.push(vec![0u8; *size]);
+    }
+    witness.push(script.as_bytes());
+    witness.push(vec![0u8; TAPROOT_CONTROL_MAX_SIZE]);
+    if witness.size() > MAX_STANDARD_TX_WEIGHT {
+        return Err(CoreError::TapscriptWitnessTooLarge(witness.size()));
+    }
+
+    Ok(())
+}
+
+/// With `SIGHASH_SINGLE|ANYONECANPAY` every input signs the payout at its own index, the
+/// builders put the first `n_inputs` payouts of the caller in front of the vault outputs.
+pub fn validate_sighash_payouts(
+    sighash_type: Option<TapSighashType>,
+    n_inputs: usize,
+    payouts: &[TxOut],
+) -> Result<(), CoreError> {
+    let Some(sighash_type @ TapSighashType::SinglePlusAnyoneCanPay) = sighash_type else {
+        return Ok(());
+    };
+    if payouts.len() < n_inputs {
+        return Err(CoreError::SighashSingleWithoutOutput(
+            sighash_type,
+            payouts.len(),
+            n_inputs,
+        ));
+    }
+    match payouts[..n_inputs]
+        .iter()
+        .position(|output| output.script_pubkey.is_op_return())
+    {
+        Some(index) => Err(CoreError::SighashSingleWithDataOutput(sighash_type, index)),
+        None => Ok(()),
+    }
+}
+
+/// Vault signatures commit to every output, or with `SIGHASH_SINGLE|ANYONECANPAY` to the output
+/// at the index of their input, which must then be a payout. The anyone can pay types let a
+/// third party add fee inputs.
+pub fn validate_sighash_type(
+    sighash_type: TapSighashType,
+    unsigned_tx: &Transaction,
+) -> Result<(), CoreError> {
+    match sighash_type {
+        TapSighashType::Default | TapSighashType::All | TapSighashType::AllPlusAnyoneCanPay => {
+            Ok(())
+        }
+        TapSighashType::SinglePlusAnyoneCanPay => {
+            if unsigned_tx.output.len() < unsigned_tx.input.len() {
+                return Err(CoreError::SighashSingleWithoutOutput(
+                    sighash_type,
+                    unsigned_tx.output.len(),
+                    unsigned_tx.input.len(),
+                ));
+            }
+            // Each input only commits to its own output, it must be a payout and not the
+            // vault data
+            match (0..unsigned_tx.input.len())
+                .find(|&index| unsigned_tx.output[index].script_pubkey.is_op_return())
+            {
+                Some(index) => Err(CoreError::SighashSingleWithDataOutput(sighash_type, index)),
+                None => Ok(()),
+            }
+        }
+        _ => Err(CoreError::UnsupportedSighashType(sighash_type)),
+    }
+}
@@ -0,0 +1,103 @@
+use std::{borrow::Borrow, fmt};
+
+use bitcoin::{
+    script::Instruction, sighash::SighashCache, taproot::LeafVersion, Psbt, TapLeafHash,
+    Transaction, XOnlyPublicKey,
+};
+
+use super::{get_global_secp, TapScriptSig, TapScriptSigsMap, Utils};
+
+/// Why a submitted tap script signature was not aggregated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapScriptSigRejection {
+    InputNotFound,
+    LeafNotFound,
+    KeyNotInLeaf,
+    SighashTypeMismatch,
+    InvalidSignature,
+    Sighash(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectedTapScriptSig {
+    pub input: u64,
+    pub key: XOnlyPublicKey,
+    pub leaf_hash: TapLeafHash,
+    pub reason: TapScriptSigRejection,
+}
+
+impl fmt::Display for RejectedTapScriptSig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "input {}: signature of {} for leaf {} rejected: {:?}",
+            self.input, self.key, self.leaf_hash, self.reason
+        )
+    }
+}
+
+/// Checks every signature of `tap_script_sigs` against the taproot sighash of its input and
+/// leaf, and that its key appears in the leaf script. Returns the rejected signatures.
+pub fn verify_tap_script_sigs(
+    psbt: &Psbt,
+    tap_script_sigs: &TapScriptSigsMap,
+) -> Vec<RejectedTapScriptSig> {
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut rejected = Vec::new();
+
+    for (index, sigs) in tap_script_sigs.iter() {
+        for tap_script_sig in sigs {
+            if let Err(reason) = verify_tap_script_sig(psbt, *index, tap_script_sig, &mut cache) {
+                rejected.push(RejectedTapScriptSig {
+                    input: *index,
+                    key: *tap_script_sig.key(),
+                    leaf_hash: *tap_script_sig.leaf_hash(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    rejected
+}
+
+pub(crate) fn verify_tap_script_sig<T: Borrow<Transaction>>(
+    psbt: &Psbt,
+    index: u64,
+    tap_script_sig: &TapScriptSig,
+    cache: &mut SighashCache<T>,
+) -> Result<(), TapScriptSigRejection> {
+    let input_index = index as usize;
+    let input = psbt
+        .inputs
+        .get(input_index)
+        .ok_or(TapScriptSigRejection::InputNotFound)?;
+
+    let leaf_hash = *tap_script_sig.leaf_hash();
+    let (script, _) = input
+        .tap_scripts
+        .values()
+        .find(|(script, version)| TapLeafHash::from_script(script, *version) == leaf_hash)
+        .filter(|(_, version)| *version == LeafVersion::TapScript)
+        .ok_or(TapScriptSigRejection::LeafNotFound)?;
+
+    let key = tap_script_sig.key().serialize();
+    let key_in_leaf = script
+        .instructions()
+        .any(|instruction| matches!(instruction, Ok(Instruction::PushBytes(bytes)) if bytes.as_bytes() == key));
+    if !key_in_leaf {
+        return Err(TapScriptSigRejection::KeyNotInLeaf);
+    }
+
+    let (msg, sighash_type) = psbt
+        .sighash_taproot(input_index, cache, Some(leaf_hash))
+        .map_err(|e| TapScriptSigRejection::Sighash(e.to_string()))?;
+    let sig = tap_script_sig.sig();
+    if sig.sighash_type != sighash_type {
+        return Err(TapScriptSigRejection::SighashTypeMismatch);
+    }
+
+    get_global_secp()
+        .verify_schnorr(&sig.signature, &msg, tap_script_sig.key())
+        .map_err(|_| TapScriptSigRejection::InvalidSignature)
+}
@@ -1,8 +1,9 @@
 use crate::{
-    DestinationChain, DestinationRecipientAddress, DestinationTokenAddress, TaprootTreeType,
-    UnlockingTaprootTreeType, CUSTODIAN_QUORUM_SIZE, DEST_CHAIN_SIZE, DEST_RECIPIENT_ADDRESS_SIZE,
-    DEST_TOKEN_ADDRESS_SIZE, FLAGS_SIZE, HASH_SIZE, NETWORK_ID_SIZE, SEQUENCE_SIZE,
-    SERVICE_TAG_HASH_SIZE, TAG_HASH_SIZE, VERSION_SIZE,
+    CustodianGroup, CustodianGroupRegistry, DestinationChain, DestinationRecipientAddress,
+    DestinationTokenAddress, TaprootTreeType, UnlockingTaprootTreeType, CUSTODIAN_QUORUM_SIZE,
+    DEST_CHAIN_SIZE, DEST_RECIPIENT_ADDRESS_SIZE, DEST_TOKEN_ADDRESS_SIZE, FLAGS_SIZE, HASH_SIZE,
+    NETWORK_ID_SIZE, RELATIVE_LOCK_TIME_SIZE, SEQUENCE_SIZE, SERVICE_TAG_HASH_SIZE, TAG_HASH_SIZE,
+    VERSION_SIZE, X_ONLY_PUBKEY_SIZE,
 };
 use bitcoin::{consensus::Encodable, Amount, ScriptBuf, Transaction, TxIn, TxOut, Txid};
 use log::debug;
@@ -25,16 +26,13 @@ impl From<&TxOut> for VaultLockTxOutput {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, Default)]
 pub enum VaultReturnTxOutputType {
+    #[default]
     Unlocking,
     Locking,
-}
-
-impl Default for VaultReturnTxOutputType {
-    fn default() -> Self {
-        Self::Unlocking
-    }
+    /// Moves the funds of a vault to a new custodian set
+    Rotation,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
@@ -52,6 +50,12 @@ pub struct VaultReturnTxOutput {
     pub script_pubkey: ScriptBuf,
     pub session_sequence: u64,
     pub custodian_group_uid: [u8; HASH_SIZE],
+    /// Custodian group receiving the funds of a rotation, `custodian_group_uid` is the old one
+    pub new_custodian_group_uid: [u8; HASH_SIZE],
+    /// Consensus encoded BIP-68 relative lock time of a time gated vault
+    pub relative_lock_time: u32,
+    /// X-only public key of the party of a time gated vault
+    pub party_pubkey: [u8; X_ONLY_PUBKEY_SIZE],
 }
 
 fn read_bytes(bytes: &[u8], cursor: &mut usize, len: usize) -> Result<Vec<u8>, ParserError> {
@@ -121,6 +125,52 @@ impl TryFrom<&TxOut> for VaultReturnTxOutput {
                     ..Default::default()
                 })
             }
+            Ok(UnlockingTaprootTreeType::CustodianRotation) => {
+                let service_tag = read_bytes(bytes, &mut cursor, SERVICE_TAG_HASH_SIZE)?;
+                let custodian_group_uid = read_bytes(bytes, &mut cursor, HASH_SIZE)?;
+                let new_custodian_group_uid = read_bytes(bytes, &mut cursor, HASH_SIZE)?;
+                //Check if no extra bytes left
+                if cursor != bytes.len() {
+                    return Err(ParserError::InvalidScript("Invalid script".to_string()));
+                }
+                Ok(VaultReturnTxOutput {
+                    tag: tag.try_into().unwrap(),
+                    version,
+                    network_id,
+                    flags,
+                    service_tag: service_tag.try_into().unwrap(),
+                    transaction_type: VaultReturnTxOutputType::Rotation,
+                    custodian_group_uid: custodian_group_uid.try_into().unwrap(),
+                    new_custodian_group_uid: new_custodian_group_uid.try_into().unwrap(),
+                    script_pubkey: txo.script_pubkey.clone(),
+                    ..Default::default()
+                })
+            }
+            Err(_) if flags == TaprootTreeType::TimeGated as u8 => {
+                let service_tag = read_bytes(bytes, &mut cursor, SERVICE_TAG_HASH_SIZE)?;
+                let custodian_quorum = read_bytes(bytes, &mut cursor, CUSTODIAN_QUORUM_SIZE)?[0];
+                let relative_lock_time = read_bytes(bytes, &mut cursor, RELATIVE_LOCK_TIME_SIZE)?;
+                let relative_lock_time = u32::from_be_bytes(relative_lock_time.try_into().unwrap());
+                let party_pubkey = read_bytes(bytes, &mut cursor, X_ONLY_PUBKEY_SIZE)?;
+                //Check if no extra bytes left
+                if cursor != bytes.len() {
+                    return Err(ParserError::InvalidScript("Invalid script".to_string()));
+                }
+                debug!("Found candiate for Scalar VaultTx with tree_type: TimeGated");
+                Ok(VaultReturnTxOutput {
+                    tag: tag.try_into().unwrap(),
+                    service_tag: service_tag.try_into().unwrap(),
+                    version,
+                    network_id,
+                    flags,
+                    transaction_type: VaultReturnTxOutputType::Locking,
+                    custodian_quorum,
+                    script_pubkey: txo.script_pubkey.clone(),
+                    relative_lock_time,
+                    party_pubkey: party_pubkey.try_into().unwrap(),
+                    ..Default::default()
+                })
+            }
             Err(_) => {
                 let tree_type = TaprootTreeType::try_from(flags)
                     .map_err(|_| ParserError::InvalidScript(format!("Invalid flags: {}", flags)))?;
@@ -190,6 +240,22 @@ impl VaultReturnTxOutput {
             script_pubkey: ScriptBuf::from_bytes(script_pubkey.to_vec()),
         })
     }
+
+    /// The known custodian group of an unlocking tx, the old group of a rotation tx.
+    pub fn custodian_group<'a>(
+        &self,
+        registry: &'a CustodianGroupRegistry,
+    ) -> Option<&'a CustodianGroup> {
+        registry.get(&self.custodian_group_uid)
+    }
+
+    /// The known custodian group receiving the funds of a rotation tx.
+    pub fn new_custodian_group<'a>(
+        &self,
+        registry: &'a CustodianGroupRegistry,
+    ) -> Option<&'a CustodianGroup> {
+        registry.get(&self.new_custodian_group_uid)
+    }
 }
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct VaultChangeTxOutput {
@@ -229,22 +295,25 @@ impl TryFrom<&Transaction> for VaultTransaction {
         let mut tx_content = vec![];
         tx.consensus_encode(&mut tx_content).unwrap();
 
-        //2. Parse the op_return data
-        let return_tx = VaultReturnTxOutput::try_from(&tx.output[0])?;
+        //2. Parse the op_return data, unlocking transactions signed with
+        // SIGHASH_SINGLE|ANYONECANPAY have their paired outputs in front of it
+        let index = tx
+            .output
+            .iter()
+            .position(|output| output.script_pubkey.is_op_return())
+            .unwrap_or(0);
+        let return_tx = VaultReturnTxOutput::try_from(&tx.output[index])?;
 
         let (lock_tx, change_tx) = match return_tx.transaction_type {
             VaultReturnTxOutputType::Unlocking => (None, None),
             VaultReturnTxOutputType::Locking => {
-                let lock_tx = tx
-                    .output
-                    .get(1)
-                    .map(|output| VaultLockTxOutput::from(output));
-                let change_tx = tx
-                    .output
-                    .get(2)
-                    .map(|output| VaultChangeTxOutput::from(output));
+                let lock_tx = tx.output.get(index + 1).map(VaultLockTxOutput::from);
+                let change_tx = tx.output.get(index + 2).map(VaultChangeTxOutput::from);
                 (lock_tx, change_tx)
             }
+            VaultReturnTxOutputType::Rotation => {
+                (tx.output.get(index + 1).map(VaultLockTxOutput::from), None)
+            }
         };
         Ok(VaultTransaction {
             txid,
@@ -261,6 +330,11 @@ impl TryFrom<&Transaction> for VaultTransaction {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        parse_relative_lock_time, BranchLayout, ParsingStaking, StakingParser, TimeGated,
+        TimeGatedLockingParams, VaultManager,
+    };
+    use bitcoin::{absolute, secp256k1::Keypair, transaction, PublicKey, XOnlyPublicKey};
 
     #[test]
     fn test_op_return_data() {
@@ -307,4 +381,58 @@ mod tests {
             println!("Vault tx: {:?}", vault_tx);
         }
     }
+
+    #[test]
+    fn test_time_gated_vault_transaction() {
+        let secp = crate::get_global_secp();
+        let keys: Vec<PublicKey> = (1..5u8)
+            .map(|i| {
+                PublicKey::new(
+                    Keypair::from_seckey_slice(secp, &[i; 32])
+                        .unwrap()
+                        .public_key(),
+                )
+            })
+            .collect();
+        let sequence = parse_relative_lock_time("7d").unwrap();
+
+        let manager = VaultManager::new(b"SCALAR".to_vec(), b"light".to_vec(), 3, 1);
+        let output = <VaultManager as TimeGated>::build_locking_output(
+            &manager,
+            &TimeGatedLockingParams {
+                locking_amount: 10_000,
+                sequence,
+                party_pubkey: keys[0],
+                custodian_pubkeys: keys[1..].to_vec(),
+                custodian_quorum: 2,
+                custodian_weights: None,
+                branch_layout: BranchLayout::Legacy,
+            },
+        )
+        .unwrap();
+
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: output.into_tx_outs(),
+        };
+
+        let vault_tx = StakingParser::new(b"SCALAR".to_vec(), 3)
+            .parse(&tx)
+            .unwrap();
+        let return_tx = &vault_tx.return_tx;
+        assert_eq!(return_tx.flags, TaprootTreeType::TimeGated as u8);
+        assert_eq!(return_tx.transaction_type, VaultReturnTxOutputType::Locking);
+        assert_eq!(return_tx.custodian_quorum, 2);
+        assert_eq!(return_tx.relative_lock_time, sequence.to_consensus_u32());
+        assert_eq!(
+            return_tx.party_pubkey,
+            XOnlyPublicKey::from(keys[0]).serialize()
+        );
+        assert_eq!(
+            vault_tx.lock_tx.unwrap().script_pubkey,
+            tx.output[1].script_pubkey
+        );
+    }
 }
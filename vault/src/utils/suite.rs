@@ -1,19 +1,18 @@
 use crate::{
-    get_basic_fee, log_tx_result, CustodianOnly, CustodianOnlyLockingParams,
-    CustodianOnlyUnlockingParams, LockingOutput, PreviousOutpoint, Signing, TaprootTreeType,
-    TimeGated, TimeGatedLockingParams, TimeGatedUnlockingParams, TimeGatedUnlockingType,
-    UPCLockingParams, UPCUnlockingParams, UPCUnlockingType, VaultManager, HASH_SIZE, UPC,
+    log_tx_result, BranchLayout, CustodianOnly, CustodianOnlyLockingParams,
+    CustodianOnlyUnlockingParams, FundedLocking, FundedLockingParams, FundingUtxo, LockingOutput,
+    PreviousOutpoint, Signing, TaprootInternalKey, TaprootTreeType, TimeGated, TimeGatedInput,
+    TimeGatedLockingParams, TimeGatedUnlockingParams, TimeGatedUnlockingType, UPCLockingParams,
+    UPCUnlockingParams, UPCUnlockingType, VaultManager, HASH_SIZE, UPC,
 };
 use anyhow::{anyhow, Result};
-use bitcoin::bip32::DerivationPath;
 use bitcoin::hex::DisplayHex;
 use bitcoin::key::rand;
-use bitcoin::psbt::Input;
 use bitcoin::{
-    absolute, key::Secp256k1, transaction, NetworkKind, PrivateKey, Psbt, PublicKey, ScriptBuf,
-    Sequence, Transaction, TxIn, TxOut, Witness,
+    key::Secp256k1, relative, NetworkKind, PrivateKey, Psbt, PublicKey, ScriptBuf, Transaction,
+    TxOut,
 };
-use bitcoin::{AddressType, Amount, OutPoint};
+use bitcoin::{Amount, OutPoint};
 use bitcoincore_rpc::json::GetRawTransactionResult;
 use log::info;
 
@@ -135,24 +134,32 @@ impl TestSuite {
         dest: DestinationInfo,
         utxos: Vec<NeededUtxo>,
     ) -> Result<Transaction, anyhow::Error> {
-        let outputs: Vec<TxOut> = match taproot_tree_type {
+        let locking_output = match taproot_tree_type {
             TaprootTreeType::OnlyKeys => {
                 panic!("not implemented");
             }
+            TaprootTreeType::TimeGated => {
+                return Err(anyhow!(
+                    "time gated outputs need a party and a lock time, use build_time_gated_locking_output"
+                ));
+            }
             TaprootTreeType::CustodianOnly => {
                 <VaultManager as CustodianOnly>::build_locking_output(
                     &self.manager,
                     &CustodianOnlyLockingParams {
                         custodian_pubkeys: self.custodian_pubkeys(),
                         custodian_quorum: self.env.custodian_quorum,
+                        custodian_weights: None,
+                        internal_key: TaprootInternalKey::Nums,
+                        branch_layout: BranchLayout::Legacy,
                         locking_amount: amount,
                         destination_chain: dest.destination_chain,
                         destination_token_address: dest.destination_token_address,
                         destination_recipient_address: dest.destination_recipient_address,
+                        subsets: None,
                     },
                 )
                 .map_err(|e| anyhow!(e))?
-                .into_tx_outs()
             }
             TaprootTreeType::UPCBranch => <VaultManager as UPC>::build_locking_output(
                 &self.manager,
@@ -161,91 +168,42 @@ impl TestSuite {
                     protocol_pubkey: self.protocol_pubkey(),
                     custodian_pubkeys: self.custodian_pubkeys(),
                     custodian_quorum: self.env.custodian_quorum,
+                    custodian_weights: None,
+                    internal_key: TaprootInternalKey::Nums,
+                    branch_layout: BranchLayout::Legacy,
+                    emergency: None,
                     locking_amount: amount,
                     destination_chain: dest.destination_chain,
                     destination_token_address: dest.destination_token_address,
                     destination_recipient_address: dest.destination_recipient_address,
                 },
             )
-            .map_err(|e| anyhow!(e))?
-            .into_tx_outs(),
-        };
-
-        let mut unsigned_tx = Transaction {
-            version: transaction::Version::TWO,
-            lock_time: absolute::LockTime::ZERO,
-            input: utxos
-                .iter()
-                .map(|utxo| TxIn {
-                    previous_output: OutPoint::new(utxo.txid, utxo.vout),
-                    script_sig: ScriptBuf::default(),
-                    sequence: Sequence::MAX,
-                    witness: Witness::new(),
-                })
-                .collect(),
-            output: outputs,
+            .map_err(|e| anyhow!(e))?,
         };
 
-        let address_type = account.address().address_type().unwrap();
-
-        let fee = get_basic_fee(
-            unsigned_tx.input.len() as u64,
-            unsigned_tx.output.len() as u64,
-            get_fee_rate(),
-            address_type,
-        );
-
-        info!("Staking Fee: {:?}", fee);
-
-        let total_output_value = unsigned_tx
-            .output
-            .iter()
-            .map(|o| o.value.to_sat())
-            .sum::<u64>();
-        let total_input_amount = utxos
-            .iter()
-            .map(|utxo| utxo.amount)
-            .reduce(|a, b| a + b)
-            .unwrap_or_default();
-
-        let change =
-            total_input_amount - Amount::from_sat(total_output_value) - Amount::from_sat(fee);
-
-        if change > Amount::ZERO {
-            unsigned_tx.output.push(TxOut {
-                value: change,
-                script_pubkey: account.address().script_pubkey(),
-            });
-        }
-
-        let mut psbt = Psbt::from_unsigned_tx(unsigned_tx).map_err(|e| anyhow!(e))?;
-        for (i, utxo) in utxos.iter().enumerate() {
-            psbt.inputs[i] = Input {
-                witness_utxo: Some(TxOut {
-                    value: utxo.amount,
-                    script_pubkey: account.address().script_pubkey(),
-                }),
-                // TODO: fix this, taproot address: leaf hash, no key origin
-                // TODO: fix this, taproot address: leaf hash, no key origin
-                // TODO: fix this, segwit address: no leaf hash, key origin
-                tap_internal_key: match account.address().address_type() {
-                    Some(AddressType::P2tr) => {
-                        Some(account.public_key().inner.x_only_public_key().0)
-                    }
-                    _ => None,
-                },
-                tap_key_origins: {
-                    let mut map = BTreeMap::new();
-                    // Note: no need leaf hash when staking
-                    map.insert(
-                        account.public_key().inner.x_only_public_key().0,
-                        (vec![], ([0u8; 4].into(), DerivationPath::default())),
-                    );
-                    map
-                },
-                ..Default::default()
-            };
-        }
+        let script_pubkey = account.address().script_pubkey();
+        let mut psbt = <VaultManager as FundedLocking>::build_funded_locking_psbt(
+            &self.manager,
+            &FundedLockingParams {
+                locking_output,
+                utxos: utxos
+                    .iter()
+                    .map(|utxo| FundingUtxo {
+                        input: PreviousOutpoint {
+                            outpoint: OutPoint::new(utxo.txid, utxo.vout),
+                            amount_in_sats: utxo.amount,
+                            script_pubkey: script_pubkey.clone(),
+                        },
+                        pubkey: account.public_key(),
+                    })
+                    .collect(),
+                change_script_pubkey: script_pubkey,
+                fee_rate: get_fee_rate(),
+                rbf: false,
+                key_origins: None,
+            },
+        )
+        .map_err(|e| anyhow!(e))?;
 
         <VaultManager as Signing>::sign_psbt_by_single_key(
             &mut psbt,
@@ -290,7 +248,13 @@ impl TestSuite {
                 protocol_pubkey: self.protocol_pubkey(),
                 custodian_pubkeys: self.custodian_pubkeys(),
                 custodian_quorum: self.env.custodian_quorum,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
+                emergency: None,
                 fee_rate: get_fee_rate(),
+                key_origins: None,
+                sighash_type: None,
                 rbf: true,
                 typ: unstaking_type,
             },
@@ -318,10 +282,17 @@ impl TestSuite {
                 outputs,
                 custodian_pubkeys: self.custodian_pubkeys(),
                 custodian_quorum: self.env.custodian_quorum,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
                 fee_rate: get_fee_rate(),
+                key_origins: None,
+                sighash_type: None,
                 rbf: true,
                 session_sequence: 0,
                 custodian_group_uid: [0u8; HASH_SIZE],
+                signers: None,
+                subsets: None,
             },
         )
         .unwrap()
@@ -331,7 +302,7 @@ impl TestSuite {
         &self,
         party_pubkey: PublicKey,
         amount: u64,
-        sequence: u16,
+        sequence: relative::LockTime,
     ) -> LockingOutput {
         <VaultManager as TimeGated>::build_locking_output(
             self.manager(),
@@ -339,6 +310,8 @@ impl TestSuite {
                 party_pubkey,
                 custodian_pubkeys: self.custodian_pubkeys(),
                 custodian_quorum: self.env.custodian_quorum,
+                custodian_weights: None,
+                branch_layout: BranchLayout::Legacy,
                 locking_amount: amount,
                 sequence,
             },
@@ -351,19 +324,23 @@ impl TestSuite {
         input: PreviousOutpoint,
         party_pubkey: PublicKey,
         script_pubkey: ScriptBuf,
-        sequence: u16,
+        sequence: relative::LockTime,
         typ: TimeGatedUnlockingType,
     ) -> Psbt {
         <VaultManager as TimeGated>::build_unlocking_psbt(
             &self.manager,
             &TimeGatedUnlockingParams {
-                input,
+                inputs: vec![TimeGatedInput { input, sequence }],
+                outputs: vec![],
+                change_script_pubkey: script_pubkey,
                 party_pubkey,
-                script_pubkey,
                 custodian_pubkeys: self.custodian_pubkeys(),
                 custodian_quorum: self.env.custodian_quorum,
-                sequence,
+                custodian_weights: None,
+                branch_layout: BranchLayout::Legacy,
                 fee_rate: get_fee_rate(),
+                key_origins: None,
+                sighash_type: None,
                 typ,
             },
         )
@@ -438,7 +415,7 @@ impl TestSuite {
                     if retry_count > 10 {
                         return Err(e);
                     }
-                    info!("tx {} not found with error: {}", txid.to_string(), e);
+                    info!("tx {} not found with error: {}", txid, e);
                     // Add exponential backoff sleep
                     std::thread::sleep(std::time::Duration::from_millis(
                         100 * (2_u64.pow(retry_count)),
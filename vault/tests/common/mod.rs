@@ -0,0 +1,128 @@
+//! Fixtures shared by the tests that run without a bitcoin node.
+#![allow(dead_code)]
+
+use bitcoin::{
+    consensus::deserialize, hashes::Hash, secp256k1::Keypair, Amount, NetworkKind, OutPoint, Psbt,
+    PublicKey, ScriptBuf, Transaction, TxOut, Txid, XOnlyPublicKey,
+};
+use vault::{
+    get_global_secp, BranchLayout, CustodianOnly, CustodianOnlyUnlockingParams, PreviousOutpoint,
+    Signing, TaprootInternalKey, VaultManager, HASH_SIZE,
+};
+
+pub fn manager() -> VaultManager {
+    VaultManager::new(b"SCALAR".to_vec(), b"light".to_vec(), 3, 1)
+}
+
+/// Deterministic key pair whose secret key is `seed` repeated.
+pub fn keypair(seed: u8) -> Keypair {
+    Keypair::from_seckey_slice(get_global_secp(), &[seed; 32]).unwrap()
+}
+
+pub fn keypairs(seeds: impl IntoIterator<Item = u8>) -> Vec<Keypair> {
+    seeds.into_iter().map(keypair).collect()
+}
+
+pub fn pubkeys(keypairs: &[Keypair]) -> Vec<PublicKey> {
+    keypairs
+        .iter()
+        .map(|kp| PublicKey::new(kp.public_key()))
+        .collect()
+}
+
+pub fn x_only_pubkeys(keypairs: &[Keypair]) -> Vec<XOnlyPublicKey> {
+    keypairs.iter().map(|kp| kp.x_only_public_key().0).collect()
+}
+
+/// Output `vout` of a made-up funding transaction.
+pub fn outpoint(vout: u32) -> OutPoint {
+    OutPoint::new(Txid::from_byte_array([7u8; 32]), vout)
+}
+
+pub fn previous_outpoint(vout: u32, amount: u64, script_pubkey: &ScriptBuf) -> PreviousOutpoint {
+    PreviousOutpoint {
+        outpoint: outpoint(vout),
+        amount_in_sats: Amount::from_sat(amount),
+        script_pubkey: script_pubkey.clone(),
+    }
+}
+
+/// Key-path P2TR output of `keypair`.
+pub fn p2tr_output(amount: u64, keypair: &Keypair) -> TxOut {
+    TxOut {
+        value: Amount::from_sat(amount),
+        script_pubkey: ScriptBuf::new_p2tr(get_global_secp(), keypair.x_only_public_key().0, None),
+    }
+}
+
+pub fn custodian_only_script(custodian_pubkeys: &[PublicKey], custodian_quorum: u8) -> ScriptBuf {
+    <VaultManager as CustodianOnly>::locking_script(
+        custodian_pubkeys,
+        None,
+        custodian_quorum,
+        TaprootInternalKey::Nums,
+        None,
+    )
+    .unwrap()
+    .into_script()
+}
+
+/// Unlocking params of a plain k-of-n custodian only vault, to be adjusted by the tests.
+pub fn custodian_only_unlocking_params(
+    inputs: Vec<PreviousOutpoint>,
+    outputs: Vec<TxOut>,
+    custodian_pubkeys: &[PublicKey],
+    custodian_quorum: u8,
+) -> CustodianOnlyUnlockingParams {
+    CustodianOnlyUnlockingParams {
+        inputs,
+        outputs,
+        custodian_pubkeys: custodian_pubkeys.to_vec(),
+        custodian_quorum,
+        custodian_weights: None,
+        internal_key: TaprootInternalKey::Nums,
+        branch_layout: BranchLayout::Legacy,
+        subsets: None,
+        signers: None,
+        rbf: true,
+        fee_rate: 1,
+        session_sequence: 0,
+        custodian_group_uid: [0u8; HASH_SIZE],
+        key_origins: None,
+        sighash_type: None,
+    }
+}
+
+/// Psbt spending one 100_000 sats utxo of the 2-of-3 custodian only vault of `custodians`.
+pub fn custodian_only_psbt(custodians: &[Keypair]) -> Psbt {
+    let custodian_pubkeys = pubkeys(custodians);
+    let script_pubkey = custodian_only_script(&custodian_pubkeys, 2);
+    let params = custodian_only_unlocking_params(
+        vec![previous_outpoint(0, 100_000, &script_pubkey)],
+        vec![p2tr_output(90_000, &custodians[0])],
+        &custodian_pubkeys,
+        2,
+    );
+    <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap()
+}
+
+pub fn sign(psbt: &mut Psbt, keypair: &Keypair) {
+    <VaultManager as Signing>::sign_psbt_by_single_key(
+        psbt,
+        &keypair.secret_bytes(),
+        NetworkKind::Test,
+        false,
+    )
+    .unwrap();
+}
+
+pub fn sign_all(psbt: &mut Psbt, keypairs: &[Keypair]) {
+    for keypair in keypairs {
+        sign(psbt, keypair);
+    }
+}
+
+pub fn finalize(psbt: &mut Psbt) -> Transaction {
+    let tx = <VaultManager as Signing>::finalize_psbt_and_extract_tx(psbt).unwrap();
+    deserialize(&tx).unwrap()
+}
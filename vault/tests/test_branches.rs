@@ -0,0 +1,118 @@
+mod common;
+
+#[cfg(test)]
+mod test_branches {
+    use bitcoin::{
+        absolute,
+        hashes::{sha256, Hash},
+        opcodes::all::{OP_CHECKSIG, OP_CHECKSIGADD, OP_GREATERTHANOREQUAL},
+        script, ScriptBuf,
+    };
+    use vault::{BranchLayout, CoreError, VaultBranch};
+
+    use crate::common::*;
+
+    #[test]
+    fn test_recognize_vault_branches() {
+        let keys = x_only_pubkeys(&keypairs(1..=5));
+        let mut custodians = keys[2..].to_vec();
+        custodians.sort();
+        let lock_time = absolute::LockTime::from_height(900_000).unwrap();
+
+        let branches = vec![
+            VaultBranch::TwoParty {
+                x: keys[0],
+                y: keys[1],
+            },
+            VaultBranch::CustodianAndParty {
+                party: keys[0],
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                layout: BranchLayout::Legacy,
+            },
+            VaultBranch::CustodianOnly {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 3,
+                layout: BranchLayout::Legacy,
+            },
+            VaultBranch::CustodianWithLockTime {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                lock_time,
+                layout: BranchLayout::Legacy,
+            },
+            VaultBranch::CustodianAndParty {
+                party: keys[1],
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                layout: BranchLayout::Miniscript,
+            },
+            VaultBranch::CustodianOnly {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 1,
+                layout: BranchLayout::Miniscript,
+            },
+            VaultBranch::CustodianWithLockTime {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 3,
+                lock_time,
+                layout: BranchLayout::Miniscript,
+            },
+            VaultBranch::WeightedCustodianAndParty {
+                party: keys[1],
+                custodian_pubkeys: custodians.clone(),
+                custodian_weights: vec![3, 1, 2],
+                custodian_quorum: 4,
+            },
+            VaultBranch::WeightedCustodianOnly {
+                custodian_pubkeys: custodians.clone(),
+                custodian_weights: vec![1, 20, 1],
+                custodian_quorum: 21,
+            },
+            VaultBranch::PartyWithSequenceVerification {
+                party: keys[0],
+                sequence: 144,
+                layout: BranchLayout::Legacy,
+            },
+            VaultBranch::PartyWithSequenceVerification {
+                party: keys[1],
+                sequence: 144,
+                layout: BranchLayout::Miniscript,
+            },
+            VaultBranch::PartyWithLockTimeVerification {
+                party: keys[1],
+                lock_time,
+            },
+            VaultBranch::Hashlock {
+                payment_hash: sha256::Hash::hash(&[42u8; 32]),
+                party: keys[0],
+            },
+        ];
+
+        for branch in branches {
+            let script = branch.build_script().unwrap();
+            assert_eq!(VaultBranch::from_script(&script).unwrap(), branch);
+        }
+
+        // Unsorted custodian keys are not what the builder produces
+        let mut unsorted = script::Builder::new()
+            .push_x_only_key(&custodians[1])
+            .push_opcode(OP_CHECKSIG);
+        for key in [custodians[0], custodians[2]] {
+            unsorted = unsorted.push_x_only_key(&key).push_opcode(OP_CHECKSIGADD);
+        }
+        let unsorted = unsorted
+            .push_int(2)
+            .push_opcode(OP_GREATERTHANOREQUAL)
+            .into_script();
+        assert!(matches!(
+            VaultBranch::from_script(&unsorted),
+            Err(CoreError::NonCanonicalBranchScript)
+        ));
+
+        assert!(matches!(
+            VaultBranch::from_script(&ScriptBuf::new_op_return([1u8; 4])),
+            Err(CoreError::UnrecognizedBranchScript)
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+#[cfg(test)]
+mod test_coin_selection {
+    use vault::{select_coins_bnb, select_coins_knapsack};
+
+    fn total(values: &[u64], selection: &[usize]) -> u64 {
+        selection.iter().map(|i| values[*i]).sum()
+    }
+
+    #[test]
+    fn test_coin_selection() {
+        let values = [1_000, 7_000, 3_000, 5_000, 2_500];
+
+        // 7_000 + 3_000 is an exact match, 5_000 + 2_500 + 3_000 overshoots by 500
+        let selection = select_coins_bnb(&values, 10_000, 0).unwrap();
+        assert_eq!(total(&values, &selection), 10_000);
+        let selection = select_coins_bnb(&values, 10_400, 200).unwrap();
+        assert_eq!(total(&values, &selection), 10_500);
+        assert_eq!(select_coins_bnb(&values, 10_600, 100), None);
+        assert_eq!(select_coins_bnb(&values, 20_000, 1_000), None);
+
+        assert_eq!(select_coins_knapsack(&values, 5_000), Some(vec![3]));
+        let selection = select_coins_knapsack(&values, 4_000).unwrap();
+        assert_eq!(total(&values, &selection), 4_000);
+        // The smaller coins are not enough, the lowest larger coin is taken
+        assert_eq!(
+            select_coins_knapsack(&[1_000, 2_000, 9_000, 7_000], 5_000),
+            Some(vec![3])
+        );
+        // No larger coin, 7_000 + 3_000 + 1_000 is the closest subset
+        let selection = select_coins_knapsack(&values, 10_600).unwrap();
+        assert_eq!(total(&values, &selection), 11_000);
+        let selection = select_coins_knapsack(&values, values.iter().sum()).unwrap();
+        assert_eq!(selection.len(), values.len());
+        assert_eq!(select_coins_knapsack(&values, 20_000), None);
+    }
+}
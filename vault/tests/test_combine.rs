@@ -0,0 +1,89 @@
+mod common;
+
+#[cfg(test)]
+mod test_combine {
+    use bitcoin::{absolute, secp256k1::schnorr, NetworkKind, Psbt};
+    use vault::{get_global_secp, CoreError, PsbtConflict, Signing, SigningKeyMap, VaultManager};
+
+    use crate::common::*;
+
+    #[test]
+    fn test_combine_psbts() {
+        let keypairs = keypairs(1..=3);
+        let unsigned = custodian_only_psbt(&keypairs);
+
+        // Each custodian signs its own copy with its own tool
+        let signed: Vec<Psbt> = keypairs[..2]
+            .iter()
+            .map(|keypair| {
+                let mut psbt = unsigned.clone();
+                let key_map = SigningKeyMap::from_privkey_slice(
+                    get_global_secp(),
+                    &keypair.secret_bytes(),
+                    NetworkKind::Test,
+                )
+                .unwrap();
+                <VaultManager as Signing>::sign_psbt_by_multiple_keys(&mut psbt, &key_map, false)
+                    .unwrap();
+                psbt
+            })
+            .collect();
+
+        // A copy of another tx is rejected
+        let mut other_tx = signed[1].clone();
+        other_tx.unsigned_tx.lock_time = absolute::LockTime::from_height(1).unwrap();
+        let mut psbt = unsigned.clone();
+        assert!(matches!(
+            <VaultManager as Signing>::combine_psbts(&mut psbt, &[signed[0].clone(), other_tx]),
+            Err(CoreError::PsbtUnsignedTxMismatch(1))
+        ));
+
+        // Two copies carrying different signatures for the same key and leaf, the valid one
+        // is kept whichever copy carries it
+        let (&key_and_leaf, &valid_sig) =
+            signed[1].inputs[0].tap_script_sigs.iter().next().unwrap();
+        let foreign_sig = *signed[0].inputs[0].tap_script_sigs.values().next().unwrap();
+        let mut tampered = signed[1].clone();
+        tampered.inputs[0]
+            .tap_script_sigs
+            .insert(key_and_leaf, foreign_sig);
+        for others in [
+            [signed[1].clone(), tampered.clone()],
+            [tampered.clone(), signed[1].clone()],
+        ] {
+            let mut psbt = unsigned.clone();
+            <VaultManager as Signing>::combine_psbts(&mut psbt, &others).unwrap();
+            assert_eq!(psbt.inputs[0].tap_script_sigs[&key_and_leaf], valid_sig);
+        }
+
+        // Neither signature is valid
+        let mut forged_sig = foreign_sig;
+        let mut bytes = forged_sig.signature.serialize();
+        bytes[63] ^= 1;
+        forged_sig.signature = schnorr::Signature::from_slice(&bytes).unwrap();
+        let mut forged = signed[1].clone();
+        forged.inputs[0]
+            .tap_script_sigs
+            .insert(key_and_leaf, forged_sig);
+        let mut psbt = unsigned.clone();
+        let Err(CoreError::ConflictingPsbts(conflicts)) =
+            <VaultManager as Signing>::combine_psbts(&mut psbt, &[tampered, forged])
+        else {
+            panic!("conflicting invalid signatures must be reported");
+        };
+        assert_eq!(
+            conflicts,
+            vec![PsbtConflict::TapScriptSig {
+                input: 0,
+                key: key_and_leaf.0,
+                leaf_hash: key_and_leaf.1,
+            }]
+        );
+        assert_eq!(psbt, unsigned);
+
+        let mut psbt = unsigned.clone();
+        <VaultManager as Signing>::combine_psbts(&mut psbt, &signed).unwrap();
+        assert_eq!(psbt.inputs[0].tap_script_sigs.len(), 2);
+        assert!(<VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).is_ok());
+    }
+}
@@ -1,9 +1,11 @@
 #[cfg(test)]
+// Predates the clippy gate, kept as is
+#[allow(clippy::needless_borrow, clippy::needless_borrows_for_generic_args)]
 mod test_csv {
 
     use std::str::FromStr;
 
-    use bitcoin::{secp256k1::All, Amount, OutPoint, Psbt, ScriptBuf, Txid};
+    use bitcoin::{relative, secp256k1::All, Amount, OutPoint, Psbt, ScriptBuf, Txid};
     use bitcoincore_rpc::jsonrpc::base64;
     use vault::{
         get_approvable_utxos, log_tx_result, AccountEnv, DestinationInfo, DestinationInfoEnv,
@@ -24,7 +26,7 @@ mod test_csv {
     #[test]
     fn test_locking() {
         let amount = 10000;
-        let sequence = 2;
+        let sequence = relative::LockTime::from_height(2);
         let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), amount).unwrap();
 
         let staking_tx = TEST_SUITE
@@ -56,7 +58,7 @@ mod test_csv {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         //  send unstaking tx
         let result = TEST_SUITE.send_psbt_by_rpc(unstaked_psbt).unwrap().unwrap();
@@ -71,7 +73,7 @@ mod test_csv {
         let txid = std::env::var("TIME_GATED_TXID").unwrap();
         let txid = Txid::from_str(&txid).unwrap();
         let amount_in_sats = Amount::from_sat(9737);
-        let sequence = 2;
+        let sequence = relative::LockTime::from_height(2);
         let script_pubkey = ScriptBuf::from_hex(
             "51207c5c14f97f99b0af4db1f57a57a0e3c0e31080fea1546ca7139896392cfbc857",
         )
@@ -0,0 +1,146 @@
+mod common;
+
+#[cfg(test)]
+mod test_custodian_group {
+    use bitcoincore_rpc::jsonrpc::serde_json;
+    use vault::{
+        types::VaultTransaction, CustodianGroup, CustodianGroupRegistry, CustodianOnly,
+        TaprootInternalKey, VaultManager,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_custodian_group_uid() {
+        let keypairs = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&keypairs);
+
+        let group = CustodianGroup::new(custodian_pubkeys.clone(), 2, 1).unwrap();
+
+        // The uid commits to the set, the quorum and the epoch, not to the key order
+        let mut reversed = custodian_pubkeys.clone();
+        reversed.reverse();
+        assert_eq!(CustodianGroup::compute_uid(&reversed, 2, 1), *group.uid());
+        assert_ne!(
+            CustodianGroup::compute_uid(&custodian_pubkeys, 3, 1),
+            *group.uid()
+        );
+        assert_ne!(
+            CustodianGroup::compute_uid(&custodian_pubkeys, 2, 2),
+            *group.uid()
+        );
+
+        let mut registry = CustodianGroupRegistry::default();
+        registry.insert(group.clone());
+        let json = serde_json::to_string(&registry).unwrap();
+        let loaded: CustodianGroupRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded, registry);
+
+        // A group not matching its uid is rejected on load
+        let tampered = json.replacen("\"epoch\":1", "\"epoch\":2", 1);
+        assert!(serde_json::from_str::<CustodianGroupRegistry>(&tampered).is_err());
+
+        // The uid of an unlocking tx maps back to the group
+        let locking_script = custodian_only_script(&custodian_pubkeys, 2);
+        let params = group.unlocking_params(
+            vec![previous_outpoint(0, 100_000, &locking_script)],
+            vec![p2tr_output(90_000, &keypairs[0])],
+            true,
+            1,
+            7,
+        );
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+        sign_all(&mut psbt, &keypairs[..2]);
+        let tx = finalize(&mut psbt);
+
+        let vault_tx = VaultTransaction::try_from(&tx).unwrap();
+        assert_eq!(vault_tx.return_tx.custodian_group(&loaded), Some(&group));
+    }
+
+    #[test]
+    fn test_weighted_custodian_group() {
+        let keypairs = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&keypairs);
+
+        let group = CustodianGroup::new_weighted(
+            custodian_pubkeys.clone(),
+            Some(vec![3, 1, 1]),
+            4,
+            TaprootInternalKey::CustodianMusig2,
+            1,
+        )
+        .unwrap();
+        let mut reversed = custodian_pubkeys.clone();
+        reversed.reverse();
+        assert_eq!(
+            *group.uid(),
+            CustodianGroup::compute_weighted_uid(
+                &reversed,
+                Some(&[1, 1, 3]),
+                4,
+                TaprootInternalKey::CustodianMusig2,
+                1
+            )
+        );
+
+        // The uid commits to the weights and the internal key
+        let frost_key = TaprootInternalKey::FrostGroupKey(x_only_pubkeys(&keypairs)[0]);
+        for (weights, internal_key) in [
+            (vec![1, 3, 1], TaprootInternalKey::CustodianMusig2),
+            (vec![3, 1, 1], TaprootInternalKey::Nums),
+            (vec![3, 1, 1], frost_key),
+        ] {
+            let other = CustodianGroup::new_weighted(
+                custodian_pubkeys.clone(),
+                Some(weights),
+                4,
+                internal_key,
+                1,
+            )
+            .unwrap();
+            assert_ne!(other.uid(), group.uid());
+        }
+
+        // Unweighted NUMS groups keep the uid of the custodian set, quorum and epoch
+        let unweighted_uid = CustodianGroup::compute_uid(&custodian_pubkeys, 2, 1);
+        let uid = |internal_key| {
+            *CustodianGroup::new_weighted(custodian_pubkeys.clone(), None, 2, internal_key, 1)
+                .unwrap()
+                .uid()
+        };
+        assert_eq!(uid(TaprootInternalKey::Nums), unweighted_uid);
+        assert_ne!(uid(TaprootInternalKey::CustodianMusig2), unweighted_uid);
+
+        let json = serde_json::to_string(&group).unwrap();
+        assert_eq!(
+            serde_json::from_str::<CustodianGroup>(&json).unwrap(),
+            group
+        );
+
+        // Groups stored before the weights and the internal key were kept are unweighted
+        let unweighted = CustodianGroup::new(custodian_pubkeys.clone(), 2, 1).unwrap();
+        let json = serde_json::to_string(&unweighted)
+            .unwrap()
+            .replace(",\"custodian_weights\":null,\"internal_key\":\"Nums\"", "");
+        assert!(!json.contains("internal_key"));
+        assert_eq!(
+            serde_json::from_str::<CustodianGroup>(&json).unwrap(),
+            unweighted
+        );
+
+        let params = group.unlocking_params(vec![], vec![], true, 1, 7);
+        assert_eq!(params.custodian_weights, Some(vec![3, 1, 1]));
+        assert_eq!(params.internal_key, TaprootInternalKey::CustodianMusig2);
+
+        // The quorum must be reachable with the weights
+        assert!(CustodianGroup::new_weighted(
+            custodian_pubkeys,
+            Some(vec![1, 1, 1]),
+            4,
+            TaprootInternalKey::Nums,
+            1,
+        )
+        .is_err());
+    }
+}
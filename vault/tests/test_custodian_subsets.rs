@@ -0,0 +1,127 @@
+mod common;
+
+#[cfg(test)]
+mod test_custodian_subsets {
+    use bitcoin::{Psbt, PublicKey};
+    use vault::{
+        get_global_secp, BranchLayout, CoreError, CustodianOnly, CustodianOnlyTree,
+        CustodianSubsetParams, Musig2Signing, TaprootInternalKey, TaprootTree, VaultManager,
+    };
+
+    use crate::common::*;
+
+    fn subsets(aggregate: bool) -> CustodianSubsetParams {
+        CustodianSubsetParams {
+            availabilities: Some(vec![90, 90, 50, 10]),
+            max_leaves: Some(3),
+            aggregate,
+        }
+    }
+
+    fn subset_psbt(
+        custodian_pubkeys: &[PublicKey],
+        aggregate: bool,
+        signers: &[PublicKey],
+    ) -> Result<Psbt, CoreError> {
+        let script_pubkey = <VaultManager as CustodianOnly>::locking_script(
+            custodian_pubkeys,
+            None,
+            2,
+            TaprootInternalKey::Nums,
+            Some(&subsets(aggregate)),
+        )
+        .unwrap()
+        .into_script();
+
+        let mut params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &script_pubkey)],
+            vec![p2tr_output(90_000, &keypair(1))],
+            custodian_pubkeys,
+            2,
+        );
+        params.subsets = Some(subsets(aggregate));
+        params.signers = Some(signers.to_vec());
+        <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params)
+    }
+
+    #[test]
+    fn test_custodian_subset_unlocking() {
+        let secp = get_global_secp();
+        let keypairs = keypairs(1..=4);
+        let custodian_pubkeys = pubkeys(&keypairs);
+        let x_only_pubkeys = x_only_pubkeys(&keypairs);
+
+        let tree = TaprootTree::<CustodianOnlyTree>::new_with_subsets(
+            secp,
+            &x_only_pubkeys,
+            2,
+            TaprootInternalKey::Nums,
+            &subsets(false),
+            BranchLayout::Legacy,
+        )
+        .unwrap();
+        assert_eq!(tree.raw.subset_branches.len(), 3);
+        let best_leaf = tree.raw.subset_leaf(&x_only_pubkeys[..2]).unwrap();
+        assert!(tree
+            .raw
+            .subset_branches
+            .iter()
+            .all(|leaf| leaf.weight <= best_leaf.weight));
+        assert!(tree.raw.subset_leaf(&x_only_pubkeys[2..]).is_none());
+
+        // 2 signatures, the subset leaf and its control block
+        let mut psbt = subset_psbt(&custodian_pubkeys, false, &custodian_pubkeys[..2]).unwrap();
+        sign_all(&mut psbt, &keypairs[..2]);
+        assert_eq!(finalize(&mut psbt).input[0].witness.len(), 4);
+
+        // the least available pair was not sampled
+        assert!(matches!(
+            subset_psbt(&custodian_pubkeys, false, &custodian_pubkeys[2..]),
+            Err(CoreError::CustodianSubsetNotFound)
+        ));
+
+        // an aggregated subset signs its leaf with MuSig2
+        let signers = &custodian_pubkeys[..2];
+        let mut psbt = subset_psbt(&custodian_pubkeys, true, signers).unwrap();
+        let (sec_nonces, pub_nonces): (Vec<_>, Vec<_>) = keypairs[..2]
+            .iter()
+            .map(|kp| {
+                <VaultManager as Musig2Signing>::generate_musig2_nonces(
+                    &psbt,
+                    signers,
+                    &kp.secret_bytes(),
+                )
+                .unwrap()
+            })
+            .unzip();
+        let agg_nonces =
+            <VaultManager as Musig2Signing>::aggregate_musig2_nonces(&pub_nonces).unwrap();
+        let partial_sigs: Vec<_> = keypairs[..2]
+            .iter()
+            .zip(sec_nonces)
+            .map(|(kp, sec_nonces)| {
+                <VaultManager as Musig2Signing>::sign_musig2_partial(
+                    &psbt,
+                    signers,
+                    &kp.secret_bytes(),
+                    sec_nonces,
+                    &agg_nonces,
+                )
+                .unwrap()
+            })
+            .collect();
+        <VaultManager as Musig2Signing>::aggregate_musig2_partial_sigs(
+            &mut psbt,
+            signers,
+            &pub_nonces,
+            &partial_sigs,
+        )
+        .unwrap();
+        assert!(psbt.inputs[0].tap_key_sig.is_none());
+
+        let tx = finalize(&mut psbt);
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness.nth(0).unwrap().len(), 64);
+    }
+}
@@ -1,4 +1,10 @@
 #[cfg(test)]
+// Predates the clippy gate, kept as is
+#[allow(
+    unused_imports,
+    clippy::needless_borrow,
+    clippy::unused_enumerate_index
+)]
 mod test_custodians {
     use std::str::FromStr;
 
@@ -80,7 +86,7 @@ mod test_custodians {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         //  send unstaking tx
         match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
@@ -143,7 +149,7 @@ mod test_custodians {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         //  send unstaking tx
         match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
@@ -223,7 +229,7 @@ mod test_custodians {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         //  send unstaking tx
         match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
@@ -352,7 +358,7 @@ mod test_custodians {
         println!("\npsbt_hex: {}", psbt_hex);
 
         // Finalize and send
-        <Psbt as SignByKeyMap<All>>::finalize(&mut final_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut final_psbt, &Default::default()).unwrap();
         match TEST_SUITE.send_psbt_by_rpc(final_psbt) {
             Ok(Some(result)) => {
                 log_tx_result(&result);
@@ -449,7 +455,7 @@ mod test_custodians {
             unstaked_psbt.inputs[1].tap_script_sigs.len()
         );
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         let psbt_base64 = base64::encode(unstaked_psbt.serialize());
         println!("psbt_base64: {}", psbt_base64);
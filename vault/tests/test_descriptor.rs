@@ -0,0 +1,341 @@
+mod common;
+
+#[cfg(test)]
+mod test_descriptor {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        absolute,
+        hashes::{sha256, Hash},
+        relative,
+        sighash::Prevouts,
+    };
+    use bitcoincore_rpc::json::Timestamp;
+    use miniscript::interpreter::Interpreter;
+    use vault::{
+        descriptor_checksum, get_global_secp, BranchLayout, CoreError, CustodianOnly,
+        CustodianOnlyLockingParams, TaprootInternalKey, UPCEmergencyParams, UPCLockingParams,
+        VaultDescriptor, VaultManager, NUMS_BIP_341, UPC,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_descriptor_checksum() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+    }
+
+    #[test]
+    fn test_htlc_descriptor_round_trip() {
+        let secp = get_global_secp();
+        let keys = x_only_pubkeys(&keypairs(1..=2));
+        let payment_hash = sha256::Hash::hash(&[42u8; 32]);
+        let descriptor = VaultDescriptor::Htlc {
+            recipient_pubkey: keys[0],
+            refund_pubkey: keys[1],
+            payment_hash,
+            lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+        };
+
+        let miniscript = descriptor.to_descriptor(secp).unwrap();
+        assert_eq!(
+            miniscript.script_pubkey(),
+            descriptor.script_pubkey(secp).unwrap()
+        );
+
+        let rendered = miniscript.to_string();
+        assert!(rendered.contains(&format!(
+            "and_v(v:sha256({}),pk({}))",
+            payment_hash, keys[0]
+        )));
+        assert!(rendered.contains(&format!("and_v(v:after(900000),pk({}))", keys[1])));
+        assert_eq!(VaultDescriptor::from_str(&rendered).unwrap(), descriptor);
+
+        let tampered = rendered.replace("after(900000)", "after(900001)");
+        assert!(matches!(
+            VaultDescriptor::from_str(&tampered),
+            Err(CoreError::InvalidDescriptorChecksum)
+        ));
+    }
+
+    #[test]
+    fn test_legacy_custodian_vaults_have_no_tr_descriptor() {
+        let secp = get_global_secp();
+        let keys = x_only_pubkeys(&keypairs(1..=5));
+        let mut custodians = keys[2..].to_vec();
+        custodians.sort();
+
+        let descriptors = vec![
+            VaultDescriptor::UPC {
+                user_pubkey: keys[0],
+                protocol_pubkey: keys[1],
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                internal_key: TaprootInternalKey::Nums,
+                emergency: Some(UPCEmergencyParams {
+                    custodian_quorum: 3,
+                    lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+                }),
+                branch_layout: BranchLayout::Legacy,
+            },
+            VaultDescriptor::CustodianOnly {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
+            },
+            VaultDescriptor::TimeGated {
+                party_pubkey: keys[0],
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                sequence: relative::LockTime::from_512_second_intervals(42),
+                branch_layout: BranchLayout::Legacy,
+            },
+            // The key path is not expressible
+            VaultDescriptor::CustodianOnly {
+                custodian_pubkeys: custodians.clone(),
+                custodian_quorum: 2,
+                internal_key: TaprootInternalKey::CustodianMusig2,
+                branch_layout: BranchLayout::Miniscript,
+            },
+        ];
+
+        for descriptor in descriptors {
+            assert!(matches!(
+                descriptor.to_descriptor(secp),
+                Err(CoreError::InvalidDescriptor(_))
+            ));
+            assert!(descriptor.watch_only_descriptor(secp).is_ok());
+        }
+
+        // The custodian keys of a multi_a leaf are sorted
+        let multi_a = format!(
+            "tr({},multi_a(2,{},{},{}))",
+            *NUMS_BIP_341, custodians[1], custodians[0], custodians[2]
+        );
+        assert!(matches!(
+            VaultDescriptor::from_str(&multi_a),
+            Err(CoreError::InvalidDescriptor(_))
+        ));
+
+        // Only the NUMS point is accepted as internal key
+        let key_path = format!(
+            "tr({},{{and_v(v:sha256({}),pk({})),and_v(v:after(900000),pk({}))}})",
+            keys[2],
+            sha256::Hash::hash(&[42u8; 32]),
+            keys[0],
+            keys[1]
+        );
+        assert!(matches!(
+            VaultDescriptor::from_str(&key_path),
+            Err(CoreError::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn test_miniscript_layout_descriptor_round_trip() {
+        let secp = get_global_secp();
+        let keys = x_only_pubkeys(&keypairs(1..=5));
+        let mut custodians = keys[2..].to_vec();
+        custodians.sort();
+
+        let upc = |emergency| VaultDescriptor::UPC {
+            user_pubkey: keys[0],
+            protocol_pubkey: keys[1],
+            custodian_pubkeys: custodians.clone(),
+            custodian_quorum: 2,
+            internal_key: TaprootInternalKey::Nums,
+            emergency,
+            branch_layout: BranchLayout::Miniscript,
+        };
+        let custodian_only = VaultDescriptor::CustodianOnly {
+            custodian_pubkeys: custodians.clone(),
+            custodian_quorum: 2,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Miniscript,
+        };
+        let time_gated = VaultDescriptor::TimeGated {
+            party_pubkey: keys[0],
+            custodian_pubkeys: custodians.clone(),
+            custodian_quorum: 2,
+            sequence: relative::LockTime::from_512_second_intervals(42),
+            branch_layout: BranchLayout::Miniscript,
+        };
+
+        let descriptors = vec![
+            upc(None),
+            upc(Some(UPCEmergencyParams {
+                custodian_quorum: 3,
+                lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+            })),
+            custodian_only.clone(),
+            time_gated.clone(),
+        ];
+
+        for descriptor in descriptors {
+            let miniscript = descriptor.to_descriptor(secp).unwrap();
+            assert_eq!(
+                miniscript.script_pubkey(),
+                descriptor.script_pubkey(secp).unwrap()
+            );
+            assert_eq!(
+                VaultDescriptor::from_str(&miniscript.to_string()).unwrap(),
+                descriptor
+            );
+        }
+
+        let multi_a = format!(
+            "multi_a(2,{},{},{})",
+            custodians[0], custodians[1], custodians[2]
+        );
+        assert!(custodian_only
+            .to_descriptor(secp)
+            .unwrap()
+            .to_string()
+            .starts_with(&format!("tr({},{})#", *NUMS_BIP_341, multi_a)));
+
+        let rendered = time_gated.to_descriptor(secp).unwrap().to_string();
+        assert!(rendered.contains(&format!("and_v(v:older(4194346),pk({}))", keys[0])));
+        assert!(rendered.contains(&multi_a));
+
+        let rendered = upc(Some(UPCEmergencyParams {
+            custodian_quorum: 3,
+            lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+        }))
+        .to_descriptor(secp)
+        .unwrap()
+        .to_string();
+        assert!(rendered.contains(&format!("and_v(v:pk({}),pk({}))", keys[0], keys[1])));
+        assert!(rendered.contains(&format!("and_v(v:pk({}),{})", keys[1], multi_a)));
+        assert!(rendered.contains("and_v(v:after(900000),multi_a(3,"));
+
+        // The same leaves at other depths are another output key
+        let reordered = format!(
+            "tr({},{{{{and_v(v:pk({}),pk({})),and_v(v:pk({}),{})}},and_v(v:pk({}),{})}})",
+            *NUMS_BIP_341, keys[0], keys[1], keys[0], multi_a, keys[1], multi_a
+        );
+        assert!(matches!(
+            VaultDescriptor::from_str(&reordered),
+            Err(CoreError::InvalidDescriptor(_))
+        ));
+    }
+
+    #[test]
+    fn test_miniscript_layout_locking_output() {
+        let secp = get_global_secp();
+        let manager = manager();
+        let keys = pubkeys(&keypairs(1..=5));
+
+        let params = UPCLockingParams {
+            user_pubkey: keys[0],
+            protocol_pubkey: keys[1],
+            custodian_pubkeys: keys[2..].to_vec(),
+            custodian_quorum: 2,
+            custodian_weights: None,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Miniscript,
+            emergency: None,
+            locking_amount: 100_000,
+            destination_chain: [1u8; 8],
+            destination_token_address: [2u8; 20],
+            destination_recipient_address: [3u8; 20],
+        };
+        let locking_output = <VaultManager as UPC>::build_locking_output(&manager, &params)
+            .unwrap()
+            .into_tx_outs();
+        let descriptor = VaultDescriptor::try_from(&params).unwrap();
+        assert_eq!(
+            descriptor.to_descriptor(secp).unwrap().script_pubkey(),
+            locking_output[1].script_pubkey
+        );
+
+        // Weighted custodian leaves only have the legacy layout
+        let weighted = UPCLockingParams {
+            custodian_weights: Some(vec![1, 1, 2]),
+            ..params
+        };
+        assert!(matches!(
+            <VaultManager as UPC>::build_locking_output(&manager, &weighted),
+            Err(CoreError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn test_miniscript_layout_spend_takes_exact_quorum() {
+        let secp = get_global_secp();
+        let custodians = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&custodians);
+
+        let locking_output = <VaultManager as CustodianOnly>::build_locking_output(
+            &manager(),
+            &CustodianOnlyLockingParams {
+                locking_amount: 100_000,
+                custodian_pubkeys: custodian_pubkeys.clone(),
+                custodian_quorum: 2,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Miniscript,
+                subsets: None,
+                destination_chain: [1u8; 8],
+                destination_token_address: [2u8; 20],
+                destination_recipient_address: [3u8; 20],
+            },
+        )
+        .unwrap()
+        .into_tx_outs();
+        let prevout = locking_output[1].clone();
+
+        let mut params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &prevout.script_pubkey)],
+            vec![p2tr_output(90_000, &custodians[0])],
+            &custodian_pubkeys,
+            2,
+        );
+        params.branch_layout = BranchLayout::Miniscript;
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        // All the custodians sign, only two signatures go in the witness
+        sign_all(&mut psbt, &custodians);
+        let tx = finalize(&mut psbt);
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 5);
+        assert_eq!(witness.iter().take(3).filter(|e| !e.is_empty()).count(), 2);
+
+        let interpreter = Interpreter::from_txdata(
+            &prevout.script_pubkey,
+            &tx.input[0].script_sig,
+            witness,
+            tx.input[0].sequence,
+            tx.lock_time,
+        )
+        .unwrap();
+        let prevouts = [prevout];
+        assert!(interpreter
+            .iter(secp, &tx, 0, &Prevouts::All(&prevouts))
+            .all(|constraint| constraint.is_ok()));
+    }
+
+    #[test]
+    fn test_import_descriptors_json() {
+        let secp = get_global_secp();
+        let descriptor = VaultDescriptor::CustodianOnly {
+            custodian_pubkeys: x_only_pubkeys(&keypairs(1..=3)),
+            custodian_quorum: 2,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Legacy,
+        };
+
+        let output_key = descriptor
+            .spend_info(secp)
+            .unwrap()
+            .output_key()
+            .to_x_only_public_key();
+        let json = descriptor
+            .import_descriptors_json(secp, Timestamp::Now, Some("vault".to_string()))
+            .unwrap();
+
+        assert!(json.starts_with(&format!("[{{\"desc\":\"rawtr({})#", output_key)));
+        assert!(json.ends_with("\"timestamp\":\"now\",\"label\":\"vault\"}]"));
+    }
+}
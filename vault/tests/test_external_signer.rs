@@ -0,0 +1,98 @@
+mod common;
+
+#[cfg(test)]
+mod test_external_signer {
+    use std::collections::BTreeSet;
+
+    use bitcoin::{
+        secp256k1::{schnorr, Keypair},
+        Psbt, XOnlyPublicKey,
+    };
+    use vault::{
+        get_global_secp, sighash_requests, CoreError, CustodianOnly, ExternalSigner,
+        InMemorySigner, SighashRequest, Signing, VaultManager,
+    };
+
+    use crate::common::*;
+
+    /// Answers every request with a signature of another key.
+    struct CompromisedSigner(InMemorySigner, Keypair);
+
+    impl ExternalSigner for CompromisedSigner {
+        type Error = CoreError;
+
+        fn x_only_pubkeys(&self) -> Vec<XOnlyPublicKey> {
+            self.0.x_only_pubkeys()
+        }
+
+        fn sign_schnorr(
+            &self,
+            request: &SighashRequest,
+        ) -> Result<schnorr::Signature, Self::Error> {
+            Ok(get_global_secp().sign_schnorr_no_aux_rand(&request.message, &self.1))
+        }
+    }
+
+    /// Spends two 50_000 sats utxos of the 2-of-3 vault of `keypairs`.
+    fn unlocking_psbt(keypairs: &[Keypair]) -> Psbt {
+        let custodian_pubkeys = pubkeys(keypairs);
+        let locking_script = custodian_only_script(&custodian_pubkeys, 2);
+        let params = custodian_only_unlocking_params(
+            (0..2)
+                .map(|vout| previous_outpoint(vout, 50_000, &locking_script))
+                .collect(),
+            vec![p2tr_output(90_000, &keypairs[0])],
+            &custodian_pubkeys,
+            2,
+        );
+        <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_external_signer() {
+        let keypairs = keypairs(1..=3);
+        let unsigned = unlocking_psbt(&keypairs);
+
+        let hsm = InMemorySigner::new([keypairs[0]]);
+        let kms = InMemorySigner::new([keypairs[1]]);
+        assert_eq!(
+            sighash_requests(&unsigned, &[keypairs[0].x_only_public_key().0])
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let mut psbt = unsigned.clone();
+        let (_, signed_keys) =
+            <VaultManager as Signing>::sign_psbt_by_external_signer(&mut psbt, &hsm).unwrap();
+        assert_eq!(
+            signed_keys,
+            BTreeSet::from([keypairs[0].x_only_public_key().0])
+        );
+        let (_, signed_keys) =
+            <VaultManager as Signing>::sign_psbt_by_async_external_signer(&mut psbt, &kms)
+                .await
+                .unwrap();
+        assert_eq!(
+            signed_keys,
+            BTreeSet::from([keypairs[1].x_only_public_key().0])
+        );
+
+        // Already signed leaves are not requested again
+        assert!(sighash_requests(&psbt, &hsm.x_only_pubkeys())
+            .unwrap()
+            .is_empty());
+        assert!(<VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).is_ok());
+
+        let compromised = CompromisedSigner(InMemorySigner::new([keypairs[2]]), keypairs[0]);
+        let mut psbt = unsigned.clone();
+        assert!(matches!(
+            <VaultManager as Signing>::sign_psbt_by_external_signer(&mut psbt, &compromised),
+            Err(CoreError::InvalidExternalSignature(0, key)) if key == keypairs[2].x_only_public_key().0
+        ));
+        assert!(psbt
+            .inputs
+            .iter()
+            .all(|input| input.tap_script_sigs.is_empty()));
+    }
+}
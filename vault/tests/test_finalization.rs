@@ -0,0 +1,329 @@
+mod common;
+
+#[cfg(test)]
+mod test_finalization {
+    use std::{collections::BTreeMap, str::FromStr};
+
+    use bitcoin::{
+        bip32::{DerivationPath, Xpriv},
+        ecdsa,
+        opcodes::all::{OP_CHECKMULTISIG, OP_PUSHNUM_2, OP_PUSHNUM_3},
+        psbt::Input,
+        script::Builder,
+        secp256k1::{Keypair, SecretKey},
+        sighash::SighashCache,
+        Amount, NetworkKind, Psbt, PublicKey, ScriptBuf, Sequence, TapLeafHash, TxIn, TxOut,
+    };
+    use vault::{
+        get_global_secp, BranchLayout, CoreError, CustodianOnly, Signing, SigningKeyMap,
+        TaprootInternalKey, UPCUnlockingParams, UPCUnlockingType, VaultManager, UPC,
+    };
+
+    use crate::common::*;
+
+    fn leaf_hash(psbt: &Psbt) -> TapLeafHash {
+        let (script, version) = psbt.inputs[0].tap_scripts.values().next().unwrap();
+        TapLeafHash::from_script(script, *version)
+    }
+
+    #[test]
+    fn test_finalize_smallest_or_forced_leaf() {
+        let keypairs = keypairs(1..=5);
+        let pubkeys = pubkeys(&keypairs);
+        let (user, protocol, custodians) = (&keypairs[0], &keypairs[1], &keypairs[2..]);
+
+        let script = <VaultManager as UPC>::locking_script(
+            &pubkeys[0],
+            &pubkeys[1],
+            &pubkeys[2..],
+            None,
+            2,
+            TaprootInternalKey::Nums,
+            None,
+        )
+        .unwrap()
+        .into_script();
+        let manager = manager();
+        let build = |typ| {
+            <VaultManager as UPC>::build_unlocking_psbt(
+                &manager,
+                &UPCUnlockingParams {
+                    inputs: vec![previous_outpoint(0, 100_000, &script)],
+                    output: TxOut {
+                        value: Amount::from_sat(90_000),
+                        script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+                    },
+                    user_pubkey: pubkeys[0],
+                    protocol_pubkey: pubkeys[1],
+                    custodian_pubkeys: pubkeys[2..].to_vec(),
+                    custodian_quorum: 2,
+                    custodian_weights: None,
+                    internal_key: TaprootInternalKey::Nums,
+                    branch_layout: BranchLayout::Legacy,
+                    emergency: None,
+                    rbf: false,
+                    fee_rate: 1,
+                    key_origins: None,
+                    sighash_type: None,
+                    typ,
+                },
+            )
+            .unwrap()
+        };
+
+        // An input that can be spent through the user-protocol and custodian-protocol leaves
+        let mut psbt = build(UPCUnlockingType::UserProtocol);
+        let custodian_psbt = build(UPCUnlockingType::CustodianProtocol);
+        let (user_leaf, custodian_leaf) = (leaf_hash(&psbt), leaf_hash(&custodian_psbt));
+        let other = custodian_psbt.inputs[0].clone();
+        let input = &mut psbt.inputs[0];
+        input.tap_scripts.extend(other.tap_scripts);
+        for (key, (leaves, source)) in other.tap_key_origins {
+            input
+                .tap_key_origins
+                .entry(key)
+                .or_insert((vec![], source))
+                .0
+                .extend(leaves);
+        }
+
+        sign(&mut psbt, protocol);
+        let status =
+            <VaultManager as Signing>::finalize_psbt(&mut psbt.clone(), &BTreeMap::new()).unwrap();
+        assert!(!status.ready && !status.inputs[0].finalized);
+        match <VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt.clone()) {
+            Err(CoreError::InputsNotFinalized(inputs)) => {
+                assert_eq!(inputs.len(), 1);
+                assert!(!inputs[0].missing.is_empty());
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Only the custodian leaf is satisfied
+        for custodian in &custodians[..2] {
+            sign(&mut psbt, custodian);
+        }
+        let status =
+            <VaultManager as Signing>::finalize_psbt(&mut psbt.clone(), &BTreeMap::new()).unwrap();
+        assert!(status.ready);
+        assert_eq!(status.inputs[0].leaf_hash, Some(custodian_leaf));
+
+        // Both are, the user-protocol leaf has the smallest witness unless the other is forced
+        sign(&mut psbt, user);
+        let mut finalized = psbt.clone();
+        let status =
+            <VaultManager as Signing>::finalize_psbt(&mut finalized, &BTreeMap::new()).unwrap();
+        assert_eq!(status.inputs[0].leaf_hash, Some(user_leaf));
+        assert_eq!(
+            finalized.inputs[0]
+                .final_script_witness
+                .as_ref()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let mut forced = psbt.clone();
+        let status = <VaultManager as Signing>::finalize_psbt(
+            &mut forced,
+            &BTreeMap::from([(0, custodian_leaf)]),
+        )
+        .unwrap();
+        assert!(status.ready);
+        assert_eq!(status.inputs[0].leaf_hash, Some(custodian_leaf));
+        let tx = finalize(&mut forced);
+        assert_eq!(tx.input[0].witness.len(), 6);
+
+        let unknown = ScriptBuf::new().tapscript_leaf_hash();
+        assert!(matches!(
+            <VaultManager as Signing>::finalize_psbt(&mut psbt, &BTreeMap::from([(0, unknown)])),
+            Err(CoreError::TapLeafNotFound(0, hash)) if hash == unknown
+        ));
+    }
+
+    #[test]
+    fn test_finalize_mixed_ecdsa_inputs() {
+        let secp = get_global_secp();
+        let keypairs = keypairs(1..=8);
+        let pubkeys = pubkeys(&keypairs);
+        let (custodians, users) = (&keypairs[..3], &pubkeys[3..]);
+
+        let script_pubkey = custodian_only_script(&pubkeys[..3], 2);
+        let params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &script_pubkey)],
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            }],
+            &pubkeys[..3],
+            2,
+        );
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        // User funded inputs: P2WPKH, P2SH-P2WPKH, 2-of-3 P2WSH and P2PKH
+        let wpkh = ScriptBuf::new_p2wpkh(&users[0].wpubkey_hash().unwrap());
+        let sh_wpkh = ScriptBuf::new_p2wpkh(&users[1].wpubkey_hash().unwrap());
+        let multisig = Builder::new()
+            .push_opcode(OP_PUSHNUM_2)
+            .push_key(&users[2])
+            .push_key(&users[3])
+            .push_key(&users[4])
+            .push_opcode(OP_PUSHNUM_3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script();
+        let funding = [
+            (wpkh.clone(), None, None),
+            (
+                ScriptBuf::new_p2sh(&sh_wpkh.script_hash()),
+                Some(sh_wpkh),
+                None,
+            ),
+            (
+                ScriptBuf::new_p2wsh(&multisig.wscript_hash()),
+                None,
+                Some(multisig),
+            ),
+            (ScriptBuf::new_p2pkh(&users[0].pubkey_hash()), None, None),
+        ];
+        for (vout, (script_pubkey, redeem_script, witness_script)) in
+            funding.into_iter().enumerate()
+        {
+            psbt.unsigned_tx.input.push(TxIn {
+                previous_output: outpoint(vout as u32 + 1),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            });
+            psbt.inputs.push(Input {
+                witness_utxo: Some(TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey,
+                }),
+                redeem_script,
+                witness_script,
+                ..Default::default()
+            });
+        }
+        psbt.unsigned_tx.output.push(TxOut {
+            value: Amount::from_sat(39_000),
+            script_pubkey: wpkh,
+        });
+
+        let tx = psbt.unsigned_tx.clone();
+        let mut cache = SighashCache::new(&tx);
+        let mut sign_ecdsa = |psbt: &mut Psbt, index: usize, key: &Keypair| {
+            let (msg, sighash_type) = psbt.sighash_ecdsa(index, &mut cache).unwrap();
+            let signature = ecdsa::Signature {
+                signature: secp.sign_ecdsa(&msg, &SecretKey::from_keypair(key)),
+                sighash_type,
+            };
+            psbt.inputs[index]
+                .partial_sigs
+                .insert(PublicKey::new(key.public_key()), signature);
+        };
+        for (index, key) in [(1, 3), (2, 4), (3, 5), (4, 3)] {
+            sign_ecdsa(&mut psbt, index, &keypairs[key]);
+        }
+        sign_all(&mut psbt, &custodians[..2]);
+
+        // One signature of the 2-of-3 P2WSH input is missing
+        let status =
+            <VaultManager as Signing>::finalize_psbt(&mut psbt.clone(), &BTreeMap::new()).unwrap();
+        let multisig_status = &status.inputs[3];
+        assert!(!status.ready && !multisig_status.finalized);
+        assert_eq!(
+            (multisig_status.quorum, multisig_status.weight),
+            (Some(2), 1)
+        );
+        assert_eq!(
+            multisig_status.missing,
+            vec![users[3].inner.into(), users[4].inner.into()]
+        );
+        assert!(status
+            .inputs
+            .iter()
+            .filter(|input| input.input != 3)
+            .all(|input| input.finalized));
+
+        sign_ecdsa(&mut psbt, 3, &keypairs[7]);
+        let tx = finalize(&mut psbt);
+
+        let (wpkh, sh_wpkh, wsh, pkh) = (&tx.input[1], &tx.input[2], &tx.input[3], &tx.input[4]);
+        assert!(wpkh.script_sig.is_empty() && wpkh.witness.len() == 2);
+        assert_eq!(
+            sh_wpkh.script_sig.as_bytes()[1..],
+            *ScriptBuf::new_p2wpkh(&users[1].wpubkey_hash().unwrap()).as_bytes()
+        );
+        assert_eq!(sh_wpkh.witness.len(), 2);
+        assert!(wsh.script_sig.is_empty());
+        assert_eq!(wsh.witness.len(), 4);
+        assert!(wsh.witness[0].is_empty());
+        assert_eq!(pkh.script_sig.instructions().count(), 2);
+        assert!(pkh.witness.is_empty());
+    }
+
+    #[test]
+    fn test_sign_ecdsa_inputs_by_xpriv() {
+        let secp = get_global_secp();
+        let keypairs = keypairs(1..=3);
+        let pubkeys = pubkeys(&keypairs);
+
+        let script_pubkey = custodian_only_script(&pubkeys, 2);
+        let params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &script_pubkey)],
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            }],
+            &pubkeys,
+            2,
+        );
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        // P2WPKH and P2SH-P2WPKH wallet inputs only known by their origin
+        let master = Xpriv::new_master(NetworkKind::Test, &[5u8; 32]).unwrap();
+        let fingerprint = master.fingerprint(secp);
+        for i in 0..2u32 {
+            let path = DerivationPath::from_str(&format!("m/84'/1'/0'/0/{}", i)).unwrap();
+            let pubkey = master
+                .derive_priv(secp, &path)
+                .unwrap()
+                .to_priv()
+                .public_key(secp);
+            let wpkh = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap());
+            let (script_pubkey, redeem_script) = match i {
+                0 => (wpkh, None),
+                _ => (ScriptBuf::new_p2sh(&wpkh.script_hash()), Some(wpkh)),
+            };
+
+            psbt.unsigned_tx.input.push(TxIn {
+                previous_output: outpoint(i + 1),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                ..Default::default()
+            });
+            let mut input = Input {
+                witness_utxo: Some(TxOut {
+                    value: Amount::from_sat(10_000),
+                    script_pubkey,
+                }),
+                redeem_script,
+                ..Default::default()
+            };
+            input
+                .bip32_derivation
+                .insert(pubkey.inner, (fingerprint, path));
+            psbt.inputs.push(input);
+        }
+
+        let key_map = SigningKeyMap::from_xpriv(secp, master, None, &[]).unwrap();
+        <VaultManager as Signing>::sign_psbt_by_multiple_keys(&mut psbt, &key_map, false).unwrap();
+        assert!(psbt.inputs[1..]
+            .iter()
+            .all(|input| input.partial_sigs.len() == 1));
+
+        sign_all(&mut psbt, &keypairs[..2]);
+        let tx = finalize(&mut psbt);
+        assert!(tx.input[1..].iter().all(|input| input.witness.len() == 2));
+    }
+}
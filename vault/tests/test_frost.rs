@@ -0,0 +1,198 @@
+mod common;
+
+#[cfg(test)]
+mod test_frost {
+    use std::collections::BTreeMap;
+
+    use bitcoin::{Amount, Psbt, ScriptBuf, TxOut, XOnlyPublicKey};
+    use vault::{
+        frost_dkg_finalize, frost_dkg_round1, frost_dkg_round2, frost_trusted_dealer_keygen,
+        CoreError, CustodianOnly, FrostDkgCommitment, FrostDkgShare, FrostIdentifier,
+        FrostKeyShare, FrostNonceCommitment, FrostPublicKeyPackage, FrostSignatureShare,
+        FrostSigning, TaprootInternalKey, VaultManager,
+    };
+
+    use crate::common::*;
+
+    fn frost_psbt(group_key: XOnlyPublicKey, n_inputs: u32) -> Psbt {
+        let custodian_pubkeys = pubkeys(&keypairs(1..=3));
+        let internal_key = TaprootInternalKey::FrostGroupKey(group_key);
+        let script_pubkey = <VaultManager as CustodianOnly>::locking_script(
+            &custodian_pubkeys,
+            None,
+            2,
+            internal_key,
+            None,
+        )
+        .unwrap()
+        .into_script();
+
+        let mut params = custodian_only_unlocking_params(
+            (0..n_inputs)
+                .map(|vout| previous_outpoint(vout, 100_000, &script_pubkey))
+                .collect(),
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            }],
+            &custodian_pubkeys,
+            2,
+        );
+        params.internal_key = internal_key;
+        <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap()
+    }
+
+    type Session = (
+        BTreeMap<FrostIdentifier, Vec<FrostNonceCommitment>>,
+        BTreeMap<FrostIdentifier, Vec<FrostSignatureShare>>,
+    );
+
+    fn sign_frost(psbt: &Psbt, signers: &[&FrostKeyShare]) -> Session {
+        let (mut sec_nonces, commitments): (BTreeMap<_, _>, BTreeMap<_, _>) = signers
+            .iter()
+            .map(|share| {
+                let (sec, public) =
+                    <VaultManager as FrostSigning>::generate_frost_nonces(psbt, share).unwrap();
+                ((share.identifier(), sec), (share.identifier(), public))
+            })
+            .unzip();
+
+        let shares = signers
+            .iter()
+            .map(|share| {
+                let sec = sec_nonces.remove(&share.identifier()).unwrap();
+                let shares = <VaultManager as FrostSigning>::sign_frost_share(
+                    psbt,
+                    share,
+                    sec,
+                    &commitments,
+                )
+                .unwrap();
+                (share.identifier(), shares)
+            })
+            .collect();
+        (commitments, shares)
+    }
+
+    #[test]
+    fn test_frost_trusted_dealer_key_path_spend() {
+        let key_shares = frost_trusted_dealer_keygen(2, 3).unwrap();
+        let package = key_shares[0].public_key_package().clone();
+        let mut psbt = frost_psbt(package.group_key(), 2);
+
+        let (commitments, shares) = sign_frost(&psbt, &[&key_shares[1], &key_shares[2]]);
+
+        // A share signed for another input is rejected, naming the signer
+        let mut bad_shares = shares.clone();
+        bad_shares.get_mut(&3).unwrap().swap(0, 1);
+        assert!(matches!(
+            <VaultManager as FrostSigning>::aggregate_frost_signature_shares(
+                &mut psbt.clone(),
+                &package,
+                &commitments,
+                &bad_shares,
+            ),
+            Err(CoreError::InvalidFrostSignatureShare(0, 3))
+        ));
+
+        <VaultManager as FrostSigning>::aggregate_frost_signature_shares(
+            &mut psbt,
+            &package,
+            &commitments,
+            &shares,
+        )
+        .unwrap();
+
+        // The custodian leaf stays available as fallback
+        assert!(psbt
+            .inputs
+            .iter()
+            .all(|input| !input.tap_scripts.is_empty()));
+
+        let tx = finalize(&mut psbt);
+        for input in &tx.input {
+            assert_eq!(input.witness.len(), 1);
+            assert_eq!(input.witness[0].len(), 64);
+        }
+    }
+
+    #[test]
+    fn test_frost_dkg_key_path_spend() {
+        let (secrets, commitments): (Vec<_>, Vec<FrostDkgCommitment>) = (1..=3)
+            .map(|identifier| frost_dkg_round1(identifier, 2, 3).unwrap())
+            .unzip();
+        let commitments: Vec<FrostDkgCommitment> = commitments
+            .iter()
+            .map(|c| FrostDkgCommitment::deserialize(c.identifier(), &c.serialize().unwrap()))
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let (secrets, sent): (Vec<_>, BTreeMap<FrostIdentifier, _>) = secrets
+            .into_iter()
+            .zip(&commitments)
+            .map(|(secret, commitment)| {
+                let (secret, shares) = frost_dkg_round2(secret, &commitments).unwrap();
+                (secret, (commitment.identifier(), shares))
+            })
+            .unzip();
+        let received = |identifier: FrostIdentifier| -> BTreeMap<FrostIdentifier, FrostDkgShare> {
+            sent.iter()
+                .filter_map(|(from, shares)| {
+                    shares.get(&identifier).map(|share| (*from, share.clone()))
+                })
+                .collect()
+        };
+
+        // A share meant for another participant is caught against the commitments of its
+        // sender
+        let mut tampered = received(1);
+        tampered.insert(2, sent[&2][&3].clone());
+        let (secret, _) = frost_dkg_round1(1, 2, 3).unwrap();
+        let (secret, _) = frost_dkg_round2(secret, &commitments).unwrap();
+        assert!(matches!(
+            frost_dkg_finalize(secret, &commitments, &tampered),
+            Err(CoreError::InvalidFrostDkgShare(2))
+        ));
+
+        let key_shares: Vec<FrostKeyShare> = secrets
+            .into_iter()
+            .zip(1..=3)
+            .map(|(secret, identifier)| {
+                frost_dkg_finalize(secret, &commitments, &received(identifier)).unwrap()
+            })
+            .collect();
+        let package = key_shares[0].public_key_package().clone();
+        assert!(key_shares
+            .iter()
+            .all(|share| *share.public_key_package() == package));
+
+        // The DKG result is stored and loaded back by each custodian
+        let package = FrostPublicKeyPackage::deserialize(&package.serialize().unwrap()).unwrap();
+        let key_shares: Vec<FrostKeyShare> = key_shares
+            .iter()
+            .map(|share| {
+                FrostKeyShare::deserialize(&share.serialize().unwrap(), package.clone()).unwrap()
+            })
+            .collect();
+        assert_eq!(*key_shares[0].public_key_package(), package);
+
+        // A share of another key is rejected
+        let other = frost_trusted_dealer_keygen(2, 3).unwrap();
+        assert!(
+            FrostKeyShare::deserialize(&other[0].serialize().unwrap(), package.clone()).is_err()
+        );
+
+        let mut psbt = frost_psbt(package.group_key(), 1);
+        let (commitments, shares) = sign_frost(&psbt, &[&key_shares[0], &key_shares[2]]);
+        <VaultManager as FrostSigning>::aggregate_frost_signature_shares(
+            &mut psbt,
+            &package,
+            &commitments,
+            &shares,
+        )
+        .unwrap();
+
+        let tx = finalize(&mut psbt);
+        assert_eq!(tx.input[0].witness.len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+mod common;
+
+#[cfg(test)]
+mod test_funding {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        bip32::{DerivationPath, Fingerprint},
+        secp256k1::Keypair,
+        PublicKey, ScriptBuf,
+    };
+    use vault::{
+        get_global_secp, BranchLayout, CoreError, CustodianOnly, CustodianOnlyLockingParams,
+        FundedLocking, FundedLockingParams, FundingUtxo, KeyOrigins, LockingOutput,
+        TaprootInternalKey, VaultManager, P2TR_BUFFER_SIZE, P2WPKH_INPUT_SIZE,
+    };
+
+    use crate::common::*;
+
+    fn locking_output(manager: &VaultManager, locking_amount: u64) -> LockingOutput {
+        <VaultManager as CustodianOnly>::build_locking_output(
+            manager,
+            &CustodianOnlyLockingParams {
+                locking_amount,
+                custodian_pubkeys: pubkeys(&keypairs(1..=3)),
+                custodian_quorum: 2,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
+                subsets: None,
+                destination_chain: [1u8; 8],
+                destination_token_address: [2u8; 20],
+                destination_recipient_address: [3u8; 20],
+            },
+        )
+        .unwrap()
+    }
+
+    fn funding_utxo(keypair: &Keypair, vout: u32, amount: u64, typ: &str) -> FundingUtxo {
+        let pubkey = PublicKey::new(keypair.public_key());
+        let p2wpkh = ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap());
+        let script_pubkey = match typ {
+            "p2wpkh" => p2wpkh,
+            "p2sh-p2wpkh" => ScriptBuf::new_p2sh(&p2wpkh.script_hash()),
+            _ => ScriptBuf::new_p2tr(get_global_secp(), keypair.x_only_public_key().0, None),
+        };
+        FundingUtxo {
+            input: previous_outpoint(vout, amount, &script_pubkey),
+            pubkey,
+        }
+    }
+
+    #[test]
+    fn test_build_funded_locking_psbt() {
+        let manager = manager();
+        let keypairs = keypairs(11..=13);
+        let fee_rate = 2;
+
+        let output = locking_output(&manager, 50_000);
+        let locking_outputs = output.clone().into_tx_outs();
+        let target = 50_000
+            + (P2TR_BUFFER_SIZE
+                + locking_outputs
+                    .iter()
+                    .map(|out| out.size() as u64)
+                    .sum::<u64>())
+                * fee_rate;
+
+        // The P2WPKH utxo covers the target with less excess than the cost of change
+        let exact = target + P2WPKH_INPUT_SIZE * fee_rate + 100;
+        let mut params = FundedLockingParams {
+            locking_output: output,
+            utxos: vec![
+                funding_utxo(&keypairs[0], 0, exact, "p2wpkh"),
+                funding_utxo(&keypairs[1], 1, 30_000, "p2sh-p2wpkh"),
+                funding_utxo(&keypairs[2], 2, 200_000, "p2tr"),
+            ],
+            change_script_pubkey: funding_utxo(&keypairs[0], 0, 0, "p2wpkh")
+                .input
+                .script_pubkey,
+            fee_rate,
+            rbf: true,
+            key_origins: None,
+        };
+
+        let mut psbt =
+            <VaultManager as FundedLocking>::build_funded_locking_psbt(&manager, &params).unwrap();
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.output, locking_outputs);
+        // No placeholder origin for keys the wallet did not describe
+        assert!(psbt.inputs[0].bip32_derivation.is_empty());
+
+        sign_all(&mut psbt, &keypairs);
+        let tx = finalize(&mut psbt);
+        assert_eq!(tx.input[0].witness.len(), 2);
+        let fee = exact - 50_000;
+        assert!(fee >= tx.vsize() as u64 * fee_rate);
+
+        // Without a close match, the P2TR and the P2SH-P2WPKH utxos are spent with change
+        let fingerprint = Fingerprint::from([7u8; 4]);
+        let path = DerivationPath::from_str("m/86'/1'/0'/0/2").unwrap();
+        let mut key_origins = KeyOrigins::new();
+        key_origins.insert_key(keypairs[2].x_only_public_key().0, fingerprint, path.clone());
+        params.key_origins = Some(key_origins);
+        params.locking_output = locking_output(&manager, 200_000);
+        let mut psbt =
+            <VaultManager as FundedLocking>::build_funded_locking_psbt(&manager, &params).unwrap();
+        let outpoints: Vec<u32> = psbt
+            .unsigned_tx
+            .input
+            .iter()
+            .map(|i| i.previous_output.vout)
+            .collect();
+        assert_eq!(outpoints, vec![1, 2]);
+        assert!(psbt.inputs[0].redeem_script.is_some());
+        assert!(psbt.inputs[0].bip32_derivation.is_empty());
+        assert_eq!(
+            psbt.inputs[1].tap_key_origins[&keypairs[2].x_only_public_key().0],
+            (vec![], (fingerprint, path))
+        );
+        assert_eq!(
+            psbt.inputs[1].tap_internal_key,
+            Some(keypairs[2].x_only_public_key().0)
+        );
+        let change = psbt.unsigned_tx.output.last().unwrap();
+        assert_eq!(change.script_pubkey, params.change_script_pubkey);
+
+        sign_all(&mut psbt, &keypairs);
+        let tx = finalize(&mut psbt);
+        // The script sig pushes the P2WPKH redeem script
+        assert_eq!(tx.input[0].script_sig.len(), 23);
+        assert_eq!(tx.input[1].witness.len(), 1);
+        let fee = 230_000 - tx.output.iter().map(|out| out.value.to_sat()).sum::<u64>();
+        assert!(fee >= tx.vsize() as u64 * fee_rate);
+
+        params.locking_output = locking_output(&manager, 1_000_000);
+        assert!(matches!(
+            <VaultManager as FundedLocking>::build_funded_locking_psbt(&manager, &params),
+            Err(CoreError::InsufficientUTXOs { .. })
+        ));
+
+        // A utxo must be locked by its own key
+        params.utxos[0].pubkey = params.utxos[1].pubkey;
+        assert!(matches!(
+            <VaultManager as FundedLocking>::build_funded_locking_psbt(&manager, &params),
+            Err(CoreError::InvalidParams(_))
+        ));
+    }
+}
@@ -0,0 +1,100 @@
+mod common;
+
+#[cfg(test)]
+mod test_htlc {
+    use bitcoin::{
+        absolute,
+        hashes::{sha256, Hash},
+        secp256k1::Keypair,
+        Psbt, PublicKey, ScriptBuf,
+    };
+    use vault::{CoreError, Htlc, HtlcUnlockingParams, HtlcUnlockingType, VaultManager};
+
+    use crate::common::*;
+
+    fn build_htlc_psbt(
+        recipient: &Keypair,
+        refund: &Keypair,
+        preimage: &[u8],
+        typ: HtlcUnlockingType,
+    ) -> Result<Psbt, CoreError> {
+        let (recipient_pubkey, refund_pubkey) = (
+            PublicKey::new(recipient.public_key()),
+            PublicKey::new(refund.public_key()),
+        );
+        let payment_hash = sha256::Hash::hash(preimage);
+        let lock_time = absolute::LockTime::from_height(900_000).unwrap();
+
+        let script = <VaultManager as Htlc>::locking_script(
+            &recipient_pubkey,
+            &refund_pubkey,
+            &payment_hash,
+            lock_time,
+        )
+        .unwrap()
+        .into_script();
+
+        <VaultManager as Htlc>::build_unlocking_psbt(
+            &manager(),
+            &HtlcUnlockingParams {
+                input: previous_outpoint(0, 100_000, &script),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+                recipient_pubkey,
+                refund_pubkey,
+                payment_hash,
+                lock_time,
+                preimage: Some(preimage.to_vec()),
+                fee_rate: 1,
+                key_origins: None,
+                sighash_type: None,
+                typ,
+            },
+        )
+    }
+
+    #[test]
+    fn test_htlc_claim_and_refund() {
+        let (recipient, refund) = (keypair(1), keypair(2));
+        let preimage = [42u8; 32];
+
+        let mut psbt =
+            build_htlc_psbt(&recipient, &refund, &preimage, HtlcUnlockingType::Claim).unwrap();
+        assert_eq!(psbt.unsigned_tx.lock_time, absolute::LockTime::ZERO);
+
+        sign(&mut psbt, &recipient);
+        let tx = finalize(&mut psbt);
+
+        // signature, preimage, hashlock leaf and control block
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert_eq!(witness.nth(0).unwrap().len(), 64);
+        assert_eq!(witness.nth(1).unwrap(), preimage);
+        // The fee is estimated from the exact size of the hashlock witness
+        let fee = 100_000 - tx.output[0].value.to_sat();
+        assert!((tx.vsize() as u64..=tx.vsize() as u64 + 1).contains(&fee));
+
+        let mut psbt =
+            build_htlc_psbt(&recipient, &refund, &preimage, HtlcUnlockingType::Refund).unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.lock_time,
+            absolute::LockTime::from_height(900_000).unwrap()
+        );
+
+        sign(&mut psbt, &refund);
+        let tx = finalize(&mut psbt);
+        assert_eq!(tx.input[0].witness.len(), 3);
+    }
+
+    #[test]
+    fn test_htlc_preimage_size() {
+        let (recipient, refund) = (keypair(1), keypair(2));
+
+        // The hashlock leaf only accepts 32 bytes preimages
+        for preimage in [&[42u8; 31][..], &[42u8; 33][..]] {
+            assert!(matches!(
+                build_htlc_psbt(&recipient, &refund, preimage, HtlcUnlockingType::Claim),
+                Err(CoreError::InvalidPreimage)
+            ));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+#[cfg(test)]
+mod test_inspection {
+    use bitcoin::{
+        absolute,
+        hashes::{sha256, Hash},
+        PublicKey, ScriptBuf,
+    };
+    use vault::{
+        inspect_signing_status, Htlc, HtlcUnlockingParams, HtlcUnlockingType, Signing, VaultManager,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_inspect_signing_status() {
+        let keypairs = keypairs(1..=3);
+        let custodians = x_only_pubkeys(&keypairs);
+        let mut psbt = custodian_only_psbt(&keypairs);
+
+        let status = inspect_signing_status(&psbt);
+        let input = &status.inputs[0];
+        assert!(!status.ready);
+        assert!(input.leaf_hash.is_some());
+        assert_eq!(input.quorum, Some(2));
+        assert_eq!(input.weight, 0);
+        let mut missing = input.missing.clone();
+        missing.sort();
+        let mut expected = custodians.clone();
+        expected.sort();
+        assert_eq!(missing, expected);
+
+        // One valid signature, and the same signature claimed by custodian 3
+        sign(&mut psbt, &keypairs[0]);
+        let leaf_hash = status.inputs[0].leaf_hash.unwrap();
+        let sig = psbt.inputs[0].tap_script_sigs[&(custodians[0], leaf_hash)];
+        psbt.inputs[0]
+            .tap_script_sigs
+            .insert((custodians[2], leaf_hash), sig);
+
+        let status = inspect_signing_status(&psbt);
+        let input = &status.inputs[0];
+        assert_eq!(input.signed, vec![custodians[0]]);
+        assert_eq!(input.invalid, vec![custodians[2]]);
+        assert_eq!(input.weight, 1);
+        assert_eq!(input.missing.len(), 2);
+        assert!(!input.missing.contains(&custodians[0]));
+        assert!(!status.ready);
+
+        psbt.inputs[0]
+            .tap_script_sigs
+            .remove(&(custodians[2], leaf_hash));
+        sign(&mut psbt, &keypairs[1]);
+        let status = inspect_signing_status(&psbt);
+        assert_eq!(status.inputs[0].weight, 2);
+        assert_eq!(status.inputs[0].missing, vec![custodians[2]]);
+        assert!(status.ready);
+
+        <VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).unwrap();
+        let status = inspect_signing_status(&psbt);
+        assert!(status.inputs[0].finalized && status.ready);
+    }
+
+    #[test]
+    fn test_inspect_hashlock_preimage() {
+        let (recipient, refund) = (keypair(1), keypair(2));
+        let (recipient_pubkey, refund_pubkey) = (
+            PublicKey::new(recipient.public_key()),
+            PublicKey::new(refund.public_key()),
+        );
+        let preimage = [42u8; 32];
+        let payment_hash = sha256::Hash::hash(&preimage);
+        let lock_time = absolute::LockTime::from_height(900_000).unwrap();
+        let script = <VaultManager as Htlc>::locking_script(
+            &recipient_pubkey,
+            &refund_pubkey,
+            &payment_hash,
+            lock_time,
+        )
+        .unwrap()
+        .into_script();
+
+        let mut psbt = <VaultManager as Htlc>::build_unlocking_psbt(
+            &manager(),
+            &HtlcUnlockingParams {
+                input: previous_outpoint(0, 100_000, &script),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+                recipient_pubkey,
+                refund_pubkey,
+                payment_hash,
+                lock_time,
+                preimage: Some(preimage.to_vec()),
+                fee_rate: 1,
+                key_origins: None,
+                sighash_type: None,
+                typ: HtlcUnlockingType::Claim,
+            },
+        )
+        .unwrap();
+        sign(&mut psbt, &recipient);
+
+        let status = inspect_signing_status(&psbt);
+        assert_eq!(status.inputs[0].missing_preimage, None);
+        assert!(status.ready);
+
+        // The recipient signature alone does not satisfy the hashlock leaf
+        psbt.inputs[0].sha256_preimages.clear();
+        let status = inspect_signing_status(&psbt);
+        assert_eq!(status.inputs[0].missing_preimage, Some(payment_hash));
+        assert!(!status.ready);
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+#[cfg(test)]
+mod test_key_origins {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        bip32::{DerivationPath, Xpriv, Xpub},
+        secp256k1::Parity,
+        Amount, NetworkKind, Psbt, PublicKey, ScriptBuf, TxOut, XOnlyPublicKey,
+    };
+    use vault::{get_global_secp, CustodianOnly, KeyOrigins, VaultManager};
+
+    use crate::common::*;
+
+    #[test]
+    fn test_key_origins_in_psbt() {
+        let secp = get_global_secp();
+        let master = Xpriv::new_master(NetworkKind::Test, &[7u8; 32]).unwrap();
+        let fingerprint = master.fingerprint(secp);
+        let account_path = DerivationPath::from_str("m/86'/1'/0'").unwrap();
+        let account = master.derive_priv(secp, &account_path).unwrap();
+        let xpub = Xpub::from_priv(secp, &account);
+
+        let mut key_origins = KeyOrigins::new();
+        key_origins.insert_xpub(xpub, fingerprint, account_path.clone());
+        let custodians: Vec<XOnlyPublicKey> = (0..3)
+            .map(|i| {
+                let child = DerivationPath::from_str(&format!("m/0/{}", i)).unwrap();
+                key_origins.derive_key(secp, &xpub, &child).unwrap()
+            })
+            .collect();
+        let custodian_pubkeys: Vec<PublicKey> = custodians
+            .iter()
+            .map(|key| key.public_key(Parity::Even).into())
+            .collect();
+
+        let locking_script = custodian_only_script(&custodian_pubkeys, 2);
+        let mut params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &locking_script)],
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_p2tr(secp, custodians[0], None),
+            }],
+            &custodian_pubkeys,
+            2,
+        );
+        params.key_origins = Some(key_origins);
+
+        let psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        // Origins survive the serialization hardware wallets receive
+        let psbt = Psbt::deserialize(&psbt.serialize()).unwrap();
+        assert_eq!(
+            psbt.xpub.get(&xpub),
+            Some(&(fingerprint, account_path.clone()))
+        );
+        for (i, custodian) in custodians.iter().enumerate() {
+            let (_, (key_fingerprint, path)) = &psbt.inputs[0].tap_key_origins[custodian];
+            assert_eq!(*key_fingerprint, fingerprint);
+            assert_eq!(
+                *path,
+                DerivationPath::from_str(&format!("m/86'/1'/0'/0/{}", i)).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,97 @@
+mod common;
+
+#[cfg(test)]
+mod test_musig {
+    use bitcoin::{
+        secp256k1::Message, sighash::SighashCache, Amount, ScriptBuf, TxOut, XOnlyPublicKey,
+    };
+    use musig2::PartialSignature;
+    use vault::{
+        get_global_secp, CustodianOnly, CustodianOnlyTree, Musig2Signing, TaprootInternalKey,
+        TaprootTree, Utils, VaultManager,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_musig2_custodian_key_path_spend() {
+        let secp = get_global_secp();
+        let keypairs = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&keypairs);
+
+        let tree = TaprootTree::<CustodianOnlyTree>::new_with_internal_key(
+            secp,
+            &x_only_pubkeys(&keypairs),
+            2,
+            TaprootInternalKey::CustodianMusig2,
+        )
+        .unwrap();
+        let script_pubkey = tree.into_script(secp);
+
+        let mut params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &script_pubkey)],
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            }],
+            &custodian_pubkeys,
+            2,
+        );
+        params.internal_key = TaprootInternalKey::CustodianMusig2;
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        let (sec_nonces, pub_nonces): (Vec<_>, Vec<_>) = keypairs
+            .iter()
+            .map(|kp| {
+                <VaultManager as Musig2Signing>::generate_musig2_nonces(
+                    &psbt,
+                    &custodian_pubkeys,
+                    &kp.secret_bytes(),
+                )
+                .unwrap()
+            })
+            .unzip();
+
+        let agg_nonces =
+            <VaultManager as Musig2Signing>::aggregate_musig2_nonces(&pub_nonces).unwrap();
+
+        let partial_sigs: Vec<Vec<PartialSignature>> = keypairs
+            .iter()
+            .zip(sec_nonces)
+            .map(|(kp, sec_nonces)| {
+                <VaultManager as Musig2Signing>::sign_musig2_partial(
+                    &psbt,
+                    &custodian_pubkeys,
+                    &kp.secret_bytes(),
+                    sec_nonces,
+                    &agg_nonces,
+                )
+                .unwrap()
+            })
+            .collect();
+
+        <VaultManager as Musig2Signing>::aggregate_musig2_partial_sigs(
+            &mut psbt,
+            &custodian_pubkeys,
+            &pub_nonces,
+            &partial_sigs,
+        )
+        .unwrap();
+
+        let sig = psbt.inputs[0].tap_key_sig.unwrap();
+        let (msg, _) = psbt
+            .sighash_taproot(0, &mut SighashCache::new(&psbt.unsigned_tx), None)
+            .unwrap();
+        let output_key = XOnlyPublicKey::from_slice(&script_pubkey.as_bytes()[2..]).unwrap();
+        secp.verify_schnorr(
+            &sig.signature,
+            &Message::from_digest(*msg.as_ref()),
+            &output_key,
+        )
+        .unwrap();
+
+        let tx = finalize(&mut psbt);
+        assert_eq!(tx.input[0].witness.len(), 1);
+    }
+}
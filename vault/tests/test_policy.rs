@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod test_policy {
+    use bitcoin::{relative, taproot::LeafVersion, Amount, ScriptBuf, Sequence, TxOut};
+    use vault::{
+        get_global_secp, CoreError, PolicyBranch, PolicyLeaf, PolicyTree, PolicyUnlockingParams,
+        PolicyVault, TaprootTree, VaultManager, VaultPolicy,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_policy_vault_unlocking() {
+        let secp = get_global_secp();
+        let keypairs = keypairs(1..=5);
+        let keys = x_only_pubkeys(&keypairs);
+
+        let policy = VaultPolicy::new(vec![
+            PolicyLeaf::new(
+                "user_protocol",
+                PolicyBranch::TwoParty {
+                    x: keys[0],
+                    y: keys[1],
+                },
+            )
+            .with_weight(10),
+            PolicyLeaf::new(
+                "custodians",
+                PolicyBranch::CustodianOnly {
+                    custodian_pubkeys: keys[2..].to_vec(),
+                    custodian_quorum: 2,
+                },
+            ),
+            PolicyLeaf::new(
+                "user_timeout",
+                PolicyBranch::PartyWithSequenceVerification {
+                    party: keys[0],
+                    sequence: relative::LockTime::from_height(144),
+                },
+            ),
+            PolicyLeaf::new(
+                "protocol_timeout",
+                PolicyBranch::PartyWithSequenceVerification {
+                    party: keys[1],
+                    sequence: relative::LockTime::from_512_second_intervals(1_000),
+                },
+            ),
+        ]);
+
+        let tree = TaprootTree::<PolicyTree>::new(secp, &policy).unwrap();
+        let control_block_len = |name: &str| {
+            let script = &tree.raw.leaf(name).unwrap().script;
+            tree.root
+                .control_block(&(script.clone(), LeafVersion::TapScript))
+                .unwrap()
+                .size()
+        };
+        assert!(control_block_len("user_protocol") < control_block_len("custodians"));
+
+        let script_pubkey = <VaultManager as PolicyVault>::locking_script(&policy)
+            .unwrap()
+            .into_script();
+
+        let manager = manager();
+        let build = |leaf_name: &str| {
+            <VaultManager as PolicyVault>::build_unlocking_psbt(
+                &manager,
+                &PolicyUnlockingParams {
+                    inputs: vec![previous_outpoint(0, 100_000, &script_pubkey)],
+                    outputs: vec![TxOut {
+                        value: Amount::from_sat(60_000),
+                        script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+                    }],
+                    policy: policy.clone(),
+                    leaf_name: leaf_name.to_string(),
+                    rbf: true,
+                    fee_rate: 1,
+                    key_origins: None,
+                    sighash_type: None,
+                },
+            )
+        };
+
+        let psbt = build("user_timeout").unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::from_height(144)
+        );
+        assert_eq!(psbt.unsigned_tx.output[1].value, Amount::from_sat(40_000));
+        let psbt = build("protocol_timeout").unwrap();
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::from_512_second_intervals(1_000)
+        );
+
+        assert!(matches!(
+            build("unknown"),
+            Err(CoreError::PolicyLeafNotFound(_))
+        ));
+
+        let mut psbt = build("custodians").unwrap();
+        sign_all(&mut psbt, &keypairs[2..4]);
+        let tx = finalize(&mut psbt);
+
+        // 3 signature slots (one empty), the leaf and its control block
+        assert_eq!(tx.input[0].witness.len(), 5);
+    }
+}
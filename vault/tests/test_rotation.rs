@@ -0,0 +1,94 @@
+mod common;
+
+#[cfg(test)]
+mod test_rotation {
+    use bitcoin::{secp256k1::Keypair, Amount};
+    use vault::{
+        types::{VaultReturnTxOutputType, VaultTransaction},
+        CoreError, CustodianGroup, CustodianOnly, CustodianRotation, CustodianRotationParams,
+        RotationVault, UnlockingFeeParams, VaultManager,
+    };
+
+    use crate::common::*;
+
+    fn custodian_group(
+        seeds: std::ops::RangeInclusive<u8>,
+        epoch: u64,
+    ) -> (Vec<Keypair>, CustodianGroup) {
+        let keypairs = keypairs(seeds);
+        let custodians = CustodianGroup::new(pubkeys(&keypairs), 2, epoch).unwrap();
+        (keypairs, custodians)
+    }
+
+    fn locking_script(custodians: &CustodianGroup) -> bitcoin::ScriptBuf {
+        <VaultManager as CustodianOnly>::locking_script(
+            custodians.custodian_pubkeys(),
+            None,
+            custodians.custodian_quorum(),
+            custodians.internal_key(),
+            None,
+        )
+        .unwrap()
+        .into_script()
+    }
+
+    #[test]
+    fn test_custodian_rotation() {
+        let (old_keypairs, old_custodians) = custodian_group(1..=3, 1);
+        let (_, new_custodians) = custodian_group(4..=7, 2);
+        let old_uid = *old_custodians.uid();
+        let new_uid = *new_custodians.uid();
+
+        let old_script = locking_script(&old_custodians);
+        let new_script = locking_script(&new_custodians);
+
+        let mut params = CustodianRotationParams {
+            inputs: (0..2)
+                .map(|vout| previous_outpoint(vout, 50_000, &old_script))
+                .collect(),
+            vault: RotationVault::CustodianOnly,
+            old_custodians,
+            new_custodians,
+            rbf: true,
+            fee_rate: 1,
+            key_origins: None,
+            sighash_type: None,
+        };
+
+        let manager = manager();
+        let mut psbt =
+            <VaultManager as CustodianRotation>::build_rotation_psbt(&manager, &params).unwrap();
+
+        let fee = manager.calculate_unlocking_fee(UnlockingFeeParams {
+            n_inputs: 2,
+            n_outputs: 2,
+            quorum: 2,
+            fee_rate: 1,
+            sighash_type: None,
+        });
+        assert_eq!(psbt.unsigned_tx.output[1].script_pubkey, new_script);
+        assert_eq!(
+            psbt.unsigned_tx.output[1].value,
+            Amount::from_sat(100_000) - fee
+        );
+
+        sign_all(&mut psbt, &old_keypairs[..2]);
+        let tx = finalize(&mut psbt);
+
+        let vault_tx = VaultTransaction::try_from(&tx).unwrap();
+        assert_eq!(
+            vault_tx.return_tx.transaction_type,
+            VaultReturnTxOutputType::Rotation
+        );
+        assert_eq!(vault_tx.return_tx.custodian_group_uid, old_uid);
+        assert_eq!(vault_tx.return_tx.new_custodian_group_uid, new_uid);
+        assert_eq!(vault_tx.lock_tx.unwrap().script_pubkey, new_script);
+
+        // Funds of another vault cannot be swept along
+        params.inputs[1].script_pubkey = new_script;
+        assert!(matches!(
+            <VaultManager as CustodianRotation>::build_rotation_psbt(&manager, &params),
+            Err(CoreError::InvalidParams(_))
+        ));
+    }
+}
@@ -1,11 +1,13 @@
 #[cfg(test)]
+// Predates the clippy gate, kept as is
+#[allow(clippy::needless_borrow)]
 mod test_script {
 
     use std::str::FromStr;
 
     use bitcoin::{hex::DisplayHex, PrivateKey, PublicKey};
     use lazy_static::lazy_static;
-    use vault::{get_global_secp, TestSuite, VaultManager, UPC};
+    use vault::{get_global_secp, TaprootInternalKey, TestSuite, VaultManager, UPC};
 
     lazy_static! {
         static ref TEST_SUITE: TestSuite = TestSuite::new_with_loaded_env("PEPE");
@@ -49,7 +51,10 @@ mod test_script {
             &user_pubkey,
             &protocol_pubkey,
             &TEST_SUITE.custodian_pubkeys(),
+            None,
             TEST_SUITE.env().custodian_quorum,
+            TaprootInternalKey::Nums,
+            None,
         )
         .unwrap();
 
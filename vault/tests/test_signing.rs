@@ -0,0 +1,229 @@
+mod common;
+
+#[cfg(test)]
+mod test_signing {
+    use std::str::FromStr;
+
+    use bitcoin::{
+        bip32::{DerivationPath, Xpriv, Xpub},
+        secp256k1::Parity,
+        Amount, NetworkKind, PrivateKey, Psbt, PublicKey, ScriptBuf, TapSighashType, TxIn, TxOut,
+        XOnlyPublicKey,
+    };
+    use bitcoincore_rpc::jsonrpc::serde_json;
+    use vault::{
+        get_global_secp, types::VaultTransaction, verify_tap_script_sigs, CoreError, CustodianOnly,
+        KeyOrigins, Signing, SigningKeyMap, TapScriptSigsMap, UnlockingFeeParams, VaultManager,
+    };
+
+    use crate::common::*;
+
+    /// Spends two 50_000 sats utxos of the 2-of-3 vault of `custodians`.
+    fn custodian_only_psbt(
+        custodians: &[XOnlyPublicKey],
+        key_origins: Option<KeyOrigins>,
+        sighash_type: Option<TapSighashType>,
+    ) -> Result<Psbt, CoreError> {
+        let custodian_pubkeys: Vec<PublicKey> = custodians
+            .iter()
+            .map(|key| key.public_key(Parity::Even).into())
+            .collect();
+        let locking_script = custodian_only_script(&custodian_pubkeys, 2);
+
+        let mut params = custodian_only_unlocking_params(
+            (0..2)
+                .map(|vout| previous_outpoint(vout, 50_000, &locking_script))
+                .collect(),
+            vec![TxOut {
+                value: Amount::from_sat(90_000),
+                script_pubkey: ScriptBuf::new_p2tr(get_global_secp(), custodians[0], None),
+            }],
+            &custodian_pubkeys,
+            2,
+        );
+        params.key_origins = key_origins;
+        params.sighash_type = sighash_type;
+        <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params)
+    }
+
+    fn privkeys() -> Vec<PrivateKey> {
+        (1..=3u8)
+            .map(|i| PrivateKey::from_slice(&[i; 32], NetworkKind::Test).unwrap())
+            .collect()
+    }
+
+    fn custodians(privkeys: &[PrivateKey]) -> Vec<XOnlyPublicKey> {
+        privkeys
+            .iter()
+            .map(|key| key.public_key(get_global_secp()).into())
+            .collect()
+    }
+
+    #[test]
+    fn test_sign_psbt_by_multiple_keys() {
+        let secp = get_global_secp();
+
+        // Raw keys, an operator running two of the three custodians
+        let privkeys = privkeys();
+        let custodians = custodians(&privkeys);
+        let wifs: Vec<String> = privkeys[..2].iter().map(|key| key.to_wif()).collect();
+        let wifs: Vec<&str> = wifs.iter().map(String::as_str).collect();
+        let key_map = SigningKeyMap::from_wifs(secp, &wifs).unwrap();
+
+        let mut psbt = custodian_only_psbt(&custodians, None, None).unwrap();
+        let (_, signed_keys) =
+            <VaultManager as Signing>::sign_psbt_by_multiple_keys(&mut psbt, &key_map, false)
+                .unwrap();
+        assert_eq!(signed_keys, custodians[..2].iter().copied().collect());
+        assert!(<VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).is_ok());
+
+        // Account xpriv, its keys are found from the PSBT key origins
+        let master = Xpriv::new_master(NetworkKind::Test, &[9u8; 32]).unwrap();
+        let fingerprint = master.fingerprint(secp);
+        let account_path = DerivationPath::from_str("m/86'/1'/0'").unwrap();
+        let account = master.derive_priv(secp, &account_path).unwrap();
+        let xpub = Xpub::from_priv(secp, &account);
+
+        let mut key_origins = KeyOrigins::new();
+        key_origins.insert_xpub(xpub, fingerprint, account_path.clone());
+        let custodians: Vec<XOnlyPublicKey> = (0..3)
+            .map(|i| {
+                let child = DerivationPath::from_str(&format!("m/0/{}", i)).unwrap();
+                key_origins.derive_key(secp, &xpub, &child).unwrap()
+            })
+            .collect();
+
+        let key_map =
+            SigningKeyMap::from_xpriv(secp, account, Some((fingerprint, account_path)), &[])
+                .unwrap();
+        let mut psbt = custodian_only_psbt(&custodians, Some(key_origins), None).unwrap();
+        let (_, signed_keys) =
+            <VaultManager as Signing>::sign_psbt_by_multiple_keys(&mut psbt, &key_map, true)
+                .unwrap();
+        assert_eq!(signed_keys, custodians.iter().copied().collect());
+    }
+
+    #[test]
+    fn test_anyone_can_pay_signatures() {
+        let privkeys = privkeys();
+        let custodians = custodians(&privkeys);
+
+        assert!(matches!(
+            custodian_only_psbt(&custodians, None, Some(TapSighashType::None)),
+            Err(CoreError::UnsupportedSighashType(TapSighashType::None))
+        ));
+
+        let unsigned =
+            custodian_only_psbt(&custodians, None, Some(TapSighashType::AllPlusAnyoneCanPay))
+                .unwrap();
+
+        // 65 bytes signatures survive the json sigs map of the coordinator
+        let collected: Vec<TapScriptSigsMap> = privkeys[..2]
+            .iter()
+            .map(|privkey| {
+                let sigs = <VaultManager as Signing>::sign_psbt_and_collect_tap_script_sigs(
+                    &mut unsigned.clone(),
+                    &privkey.inner.secret_bytes(),
+                    NetworkKind::Test,
+                )
+                .unwrap();
+                let sig = &sigs.get(0).unwrap()[0];
+                assert_eq!(sig.sig().sighash_type, TapSighashType::AllPlusAnyoneCanPay);
+                assert_eq!(sig.serialize().unwrap().signature.len(), 65);
+
+                let json = serde_json::to_string(&sigs).unwrap();
+                serde_json::from_str(&json).unwrap()
+            })
+            .collect();
+
+        let mut psbt = unsigned.clone();
+        for sigs in &collected {
+            <VaultManager as Signing>::aggregate_tap_script_sigs(&mut psbt, sigs).unwrap();
+        }
+
+        // A third party adds a fee input, the custodian signatures stay valid
+        let mut bumped = psbt.clone();
+        bumped.unsigned_tx.input.push(TxIn {
+            previous_output: outpoint(9),
+            ..Default::default()
+        });
+        bumped.inputs.push(bitcoin::psbt::Input {
+            witness_utxo: Some(TxOut {
+                value: Amount::from_sat(10_000),
+                script_pubkey: ScriptBuf::new_p2tr(get_global_secp(), custodians[2], None),
+            }),
+            ..Default::default()
+        });
+        for sigs in &collected {
+            assert!(verify_tap_script_sigs(&bumped, sigs).is_empty());
+        }
+
+        let tx_hex = <VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).unwrap();
+        let tx: bitcoin::Transaction = bitcoin::consensus::deserialize(&tx_hex).unwrap();
+        let witness_sigs: Vec<usize> = tx.input[0]
+            .witness
+            .iter()
+            .map(<[u8]>::len)
+            .filter(|len| *len == 65)
+            .collect();
+        assert_eq!(witness_sigs.len(), 2);
+    }
+
+    #[test]
+    fn test_single_anyone_can_pay_pairs_payouts() {
+        let keypairs = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&keypairs);
+        let script_pubkey = custodian_only_script(&custodian_pubkeys, 2);
+        let inputs: Vec<_> = (0..2)
+            .map(|vout| previous_outpoint(vout, 50_000, &script_pubkey))
+            .collect();
+        let payouts = vec![
+            p2tr_output(40_000, &keypairs[0]),
+            p2tr_output(40_000, &keypairs[1]),
+        ];
+
+        // Each input needs a payout of its own
+        let mut params = custodian_only_unlocking_params(
+            inputs.clone(),
+            payouts[..1].to_vec(),
+            &custodian_pubkeys,
+            2,
+        );
+        params.sighash_type = Some(TapSighashType::SinglePlusAnyoneCanPay);
+        assert!(matches!(
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params),
+            Err(CoreError::SighashSingleWithoutOutput(_, 1, 2))
+        ));
+
+        // The payouts come first, the vault data and the change follow them
+        params.outputs = payouts.clone();
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+        let outputs = &psbt.unsigned_tx.output;
+        assert_eq!(outputs.len(), 4);
+        assert_eq!(outputs[0].script_pubkey, payouts[0].script_pubkey);
+        assert_eq!(outputs[1].script_pubkey, payouts[1].script_pubkey);
+        assert!(outputs[2].script_pubkey.is_op_return());
+        assert_eq!(outputs[3].script_pubkey, script_pubkey);
+        assert!(VaultTransaction::try_from(&psbt.unsigned_tx).is_ok());
+
+        // The fee counts the sighash byte of every signature
+        let fee = |sighash_type| {
+            manager().calculate_unlocking_fee(UnlockingFeeParams {
+                n_inputs: 2,
+                n_outputs: 4,
+                quorum: 2,
+                fee_rate: 1,
+                sighash_type,
+            })
+        };
+        assert_eq!(
+            fee(params.sighash_type) - fee(None),
+            Amount::from_sat(2 * 2)
+        );
+
+        sign_all(&mut psbt, &keypairs[..2]);
+        let tx = finalize(&mut psbt);
+        assert!(tx.input[0].witness.iter().any(|item| item.len() == 65));
+    }
+}
@@ -0,0 +1,168 @@
+mod common;
+
+#[cfg(test)]
+mod test_time_gated {
+    use std::time::Duration;
+
+    use bitcoin::{relative, Amount, ScriptBuf, Sequence, TxOut};
+    use vault::{
+        convert_pubkey_to_x_only_key, parse_relative_lock_time, relative_lock_time_from_duration,
+        BranchLayout, CoreError, TimeGated, TimeGatedInput, TimeGatedUnlockingParams,
+        TimeGatedUnlockingType, UnlockingFeeParams, VaultBranch, VaultManager,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_time_based_relative_lock() {
+        let party = keypair(1);
+        let custodians = pubkeys(&keypairs(2..=4));
+        let party_pubkey = pubkeys(&[party])[0];
+
+        // one day, rounded up to 169 intervals of 512 seconds
+        let sequence = relative_lock_time_from_duration(Duration::from_secs(86_400)).unwrap();
+        assert_eq!(sequence, relative::LockTime::from_512_second_intervals(169));
+        assert_eq!(parse_relative_lock_time("1d").unwrap(), sequence);
+        assert_eq!(
+            parse_relative_lock_time("144 blocks").unwrap(),
+            relative::LockTime::from_height(144)
+        );
+        assert!(parse_relative_lock_time("400d").is_err());
+        assert!(parse_relative_lock_time("1y").is_err());
+
+        let script = <VaultManager as TimeGated>::locking_script(
+            &party_pubkey,
+            &custodians,
+            None,
+            2,
+            sequence,
+        )
+        .unwrap()
+        .into_script();
+
+        let manager = manager();
+        let mut params = TimeGatedUnlockingParams {
+            inputs: vec![TimeGatedInput {
+                input: previous_outpoint(0, 100_000, &script),
+                sequence,
+            }],
+            outputs: vec![],
+            change_script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+            party_pubkey,
+            custodian_pubkeys: custodians,
+            custodian_quorum: 2,
+            custodian_weights: None,
+            branch_layout: BranchLayout::Legacy,
+            fee_rate: 1,
+            key_origins: None,
+            sighash_type: None,
+            typ: TimeGatedUnlockingType::PartyTimeGated,
+        };
+        let mut psbt =
+            <VaultManager as TimeGated>::build_unlocking_psbt(&manager, &params).unwrap();
+
+        let tx_sequence = psbt.unsigned_tx.input[0].sequence;
+        assert_eq!(tx_sequence, Sequence::from_512_second_intervals(169));
+        assert!(tx_sequence.is_time_locked());
+
+        let (_, (leaf, _)) = psbt.inputs[0].tap_scripts.iter().next().unwrap();
+        assert_eq!(
+            VaultBranch::from_script(leaf).unwrap(),
+            VaultBranch::PartyWithSequenceVerification {
+                party: convert_pubkey_to_x_only_key(&party_pubkey),
+                sequence: sequence.to_consensus_u32().into(),
+                layout: BranchLayout::Legacy,
+            }
+        );
+
+        sign(&mut psbt, &party);
+        let tx = finalize(&mut psbt);
+        assert_eq!(tx.input[0].sequence, tx_sequence);
+        assert_eq!(tx.input[0].witness.len(), 3);
+
+        params.inputs[0].sequence = relative::LockTime::from_512_second_intervals(0);
+        assert!(matches!(
+            <VaultManager as TimeGated>::build_unlocking_psbt(&manager, &params),
+            Err(CoreError::InvalidRelativeLockTime(_))
+        ));
+    }
+
+    #[test]
+    fn test_time_gated_sweep_multiple_inputs() {
+        let party = keypair(1);
+        let custodians = pubkeys(&keypairs(2..=4));
+        let party_pubkey = pubkeys(&[party])[0];
+        let party_script = p2tr_output(0, &party).script_pubkey;
+
+        let sequences = [
+            relative::LockTime::from_height(144),
+            parse_relative_lock_time("1d").unwrap(),
+        ];
+        let inputs: Vec<TimeGatedInput> = sequences
+            .iter()
+            .enumerate()
+            .map(|(vout, sequence)| TimeGatedInput {
+                input: previous_outpoint(
+                    vout as u32,
+                    50_000,
+                    &<VaultManager as TimeGated>::locking_script(
+                        &party_pubkey,
+                        &custodians,
+                        None,
+                        2,
+                        *sequence,
+                    )
+                    .unwrap()
+                    .into_script(),
+                ),
+                sequence: *sequence,
+            })
+            .collect();
+        let payment = TxOut {
+            value: Amount::from_sat(30_000),
+            script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+        };
+
+        let manager = manager();
+        let params = TimeGatedUnlockingParams {
+            inputs,
+            outputs: vec![payment.clone()],
+            change_script_pubkey: party_script.clone(),
+            party_pubkey,
+            custodian_pubkeys: custodians,
+            custodian_quorum: 2,
+            custodian_weights: None,
+            branch_layout: BranchLayout::Legacy,
+            fee_rate: 2,
+            key_origins: None,
+            sighash_type: None,
+            typ: TimeGatedUnlockingType::PartyTimeGated,
+        };
+        let mut psbt =
+            <VaultManager as TimeGated>::build_unlocking_psbt(&manager, &params).unwrap();
+
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input[0].sequence, Sequence::from_height(144));
+        assert_eq!(
+            tx.input[1].sequence,
+            Sequence::from_512_second_intervals(169)
+        );
+
+        // The payment is untouched, the change pays the fee
+        let fee = manager.calculate_unlocking_fee(UnlockingFeeParams {
+            n_inputs: 2,
+            n_outputs: 2,
+            quorum: 1,
+            fee_rate: 2,
+            sighash_type: None,
+        });
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.output[0], payment);
+        assert_eq!(tx.output[1].script_pubkey, party_script);
+        assert_eq!(tx.output[1].value, Amount::from_sat(70_000) - fee);
+
+        sign(&mut psbt, &party);
+        let tx = finalize(&mut psbt);
+        assert!(tx.input.iter().all(|input| input.witness.len() == 3));
+    }
+}
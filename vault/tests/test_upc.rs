@@ -7,8 +7,9 @@ mod test_upc {
     use rust_mempool::MempoolClient;
     use vault::{
         get_approvable_utxos, get_fee_rate, get_global_secp, helper::log_tx_result, AccountEnv,
-        DestinationInfo, DestinationInfoEnv, PreviousOutpoint, SignByKeyMap, Signing, SuiteAccount,
-        TaprootTreeType, TestSuite, UPCUnlockingParams, UPCUnlockingType, VaultManager, UPC,
+        BranchLayout, DestinationInfo, DestinationInfoEnv, PreviousOutpoint, SignByKeyMap, Signing,
+        SuiteAccount, TaprootInternalKey, TaprootTreeType, TestSuite, UPCUnlockingParams,
+        UPCUnlockingType, VaultManager, UPC,
     };
 
     use lazy_static::lazy_static;
@@ -23,7 +24,7 @@ mod test_upc {
 
     #[test]
     fn test_staking() {
-        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), 2000).unwrap();
+        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, TEST_ACCOUNT.address(), 2000).unwrap();
         let staking_tx = TEST_SUITE
             .prepare_staking_tx(
                 2000,
@@ -38,7 +39,7 @@ mod test_upc {
 
     #[test]
     fn test_user_protocol() {
-        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), 2000).unwrap();
+        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, TEST_ACCOUNT.address(), 2000).unwrap();
         let staking_tx = TEST_SUITE
             .prepare_staking_tx(
                 2000,
@@ -90,7 +91,7 @@ mod test_upc {
 
     #[test]
     fn test_custodian_user() {
-        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), 3000).unwrap();
+        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, TEST_ACCOUNT.address(), 3000).unwrap();
         let staking_tx = TEST_SUITE
             .prepare_staking_tx(
                 2000,
@@ -133,7 +134,7 @@ mod test_upc {
         }
 
         // // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         // // Extract and send
         match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
@@ -152,7 +153,7 @@ mod test_upc {
     // cargo test --package bitcoin-vault --test test_upc -- test_upc::test_custodian_protocol --exact --show-output
     #[test]
     fn test_custodian_protocol() {
-        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), 2000).unwrap();
+        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, TEST_ACCOUNT.address(), 2000).unwrap();
         let staking_tx = TEST_SUITE
             .prepare_staking_tx(
                 2000,
@@ -195,7 +196,7 @@ mod test_upc {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         // Extract and send
         match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
@@ -216,7 +217,7 @@ mod test_upc {
         use std::sync::mpsc;
         use std::thread;
 
-        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, &TEST_ACCOUNT.address(), 2000).unwrap();
+        let utxos = get_approvable_utxos(&TEST_SUITE.rpc, TEST_ACCOUNT.address(), 2000).unwrap();
         let staking_tx = TEST_SUITE
             .prepare_staking_tx(
                 2000,
@@ -254,7 +255,7 @@ mod test_upc {
         println!("psbt_hex: {:?}\n", hex::encode(original_psbt.serialize()));
 
         // Spawn a thread for each signing key
-        for (_, privkey) in signing_privkeys.iter().enumerate() {
+        for privkey in signing_privkeys.iter() {
             let mut psbt_clone = original_psbt.clone();
             let privkey = privkey.clone();
             let tx = tx.clone();
@@ -299,7 +300,7 @@ mod test_upc {
         println!("\npsbt_hex: {}", psbt_hex);
 
         // Finalize and send
-        <Psbt as SignByKeyMap<All>>::finalize(&mut final_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut final_psbt, &Default::default()).unwrap();
         match TEST_SUITE.send_psbt_by_rpc(final_psbt) {
             Ok(Some(result)) => {
                 log_tx_result(&result);
@@ -322,7 +323,7 @@ mod test_upc {
         let secp = get_global_secp();
 
         for priv_key in TEST_SUITE.env().custodian_private_keys.iter() {
-            let priv_key = PrivateKey::from_wif(&priv_key).unwrap();
+            let priv_key = PrivateKey::from_wif(priv_key).unwrap();
             // println!("priv_key: {:?}", priv_key.to_bytes().to_lower_hex_string());
             println!(
                 "pub_key: {:?}",
@@ -363,14 +364,17 @@ mod test_upc {
             &user_pubkey,
             &protocol_pubkey,
             &TEST_SUITE.custodian_pubkeys(),
+            None,
             TEST_SUITE.env().custodian_quorum,
+            TaprootInternalKey::Nums,
+            None,
         )
         .unwrap();
 
         let address =
             Address::from_script(&script.clone().into_script(), Network::Testnet4).unwrap();
 
-        let mempool_client = MempoolClient::new(Network::Testnet4);
+        let _mempool_client = MempoolClient::new(Network::Testnet4);
 
         println!("address: {:?}", address);
 
@@ -399,7 +403,7 @@ mod test_upc {
         .unwrap();
 
         let mut unstaked_psbt = <VaultManager as UPC>::build_unlocking_psbt(
-            &TEST_SUITE.manager(),
+            TEST_SUITE.manager(),
             &UPCUnlockingParams {
                 inputs: vec![PreviousOutpoint {
                     outpoint: OutPoint::new(
@@ -407,7 +411,7 @@ mod test_upc {
                             "3e1f9009397aedfdad2456ac1295e746b166878f8f38e31c9a4c6d2050b8fa6d",
                         )
                         .unwrap(),
-                        1 as u32,
+                        1_u32,
                     ),
                     amount_in_sats: Amount::from_sat(20_000_000),
                     script_pubkey: script.clone().into_script(),
@@ -416,11 +420,17 @@ mod test_upc {
                     value: Amount::from_sat(20_000_000),
                     script_pubkey: user_script_pub_key,
                 },
-                user_pubkey: user_pubkey,
-                protocol_pubkey: protocol_pubkey,
+                user_pubkey,
+                protocol_pubkey,
                 custodian_pubkeys: TEST_SUITE.custodian_pubkeys(),
                 custodian_quorum: 3,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
+                emergency: None,
                 fee_rate: get_fee_rate() * 5,
+                key_origins: None,
+                sighash_type: None,
                 rbf: true,
                 typ: UPCUnlockingType::CustodianUser,
             },
@@ -459,7 +469,7 @@ mod test_upc {
         }
 
         // Finalize the PSBT
-        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt);
+        <Psbt as SignByKeyMap<All>>::finalize(&mut unstaked_psbt, &Default::default()).unwrap();
 
         // Extract and send
         // match TEST_SUITE.send_psbt_by_rpc(unstaked_psbt) {
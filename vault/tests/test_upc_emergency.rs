@@ -0,0 +1,142 @@
+mod common;
+
+#[cfg(test)]
+mod test_upc_emergency {
+    use std::collections::BTreeMap;
+
+    use bitcoin::{
+        absolute,
+        opcodes::all::{
+            OP_CHECKSIG, OP_CHECKSIGADD, OP_DROP, OP_EQUALVERIFY, OP_NUMEQUAL, OP_SHA256,
+        },
+        script::Builder,
+        Amount, ScriptBuf, Sequence, TxOut,
+    };
+    use vault::{
+        AliasPushBytes, BranchLayout, CoreError, TaprootInternalKey, UPCEmergencyParams,
+        UPCUnlockingParams, UPCUnlockingType, Utils, VaultManager, UPC,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_upc_emergency_unlocking() {
+        let keypairs = keypairs(1..=5);
+        let pubkeys = pubkeys(&keypairs);
+        let (user_pubkey, protocol_pubkey, custodian_pubkeys) =
+            (pubkeys[0], pubkeys[1], pubkeys[2..].to_vec());
+
+        let emergency = UPCEmergencyParams {
+            custodian_quorum: 3,
+            lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+        };
+
+        let script = <VaultManager as UPC>::locking_script(
+            &user_pubkey,
+            &protocol_pubkey,
+            &custodian_pubkeys,
+            None,
+            2,
+            TaprootInternalKey::Nums,
+            Some(emergency),
+        )
+        .unwrap()
+        .into_script();
+
+        let mut psbt = <VaultManager as UPC>::build_unlocking_psbt(
+            &manager(),
+            &UPCUnlockingParams {
+                inputs: vec![previous_outpoint(0, 100_000, &script)],
+                output: TxOut {
+                    value: Amount::from_sat(90_000),
+                    script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+                },
+                user_pubkey,
+                protocol_pubkey,
+                custodian_pubkeys,
+                custodian_quorum: 2,
+                custodian_weights: None,
+                internal_key: TaprootInternalKey::Nums,
+                branch_layout: BranchLayout::Legacy,
+                emergency: Some(emergency),
+                rbf: false,
+                fee_rate: 1,
+                key_origins: None,
+                sighash_type: None,
+                typ: UPCUnlockingType::Emergency,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(psbt.unsigned_tx.lock_time, emergency.lock_time);
+        assert_eq!(
+            psbt.unsigned_tx.input[0].sequence,
+            Sequence::ENABLE_LOCKTIME_NO_RBF
+        );
+
+        sign_all(&mut psbt, &keypairs[2..]);
+        let tx = finalize(&mut psbt);
+
+        // 3 signatures, the emergency leaf and its control block
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 5);
+        assert!(witness.iter().take(3).all(|sig| sig.len() == 64));
+    }
+
+    #[test]
+    fn test_weighted_emergency_quorum() {
+        let pubkeys = pubkeys(&keypairs(1..=5));
+        let locking_script = |custodian_quorum| {
+            <VaultManager as UPC>::locking_script(
+                &pubkeys[0],
+                &pubkeys[1],
+                &pubkeys[2..],
+                Some(&[2, 2, 1]),
+                3,
+                TaprootInternalKey::Nums,
+                Some(UPCEmergencyParams {
+                    custodian_quorum,
+                    lock_time: absolute::LockTime::from_height(900_000).unwrap(),
+                }),
+            )
+        };
+
+        // A weight of 3 may take 2 custodians, the emergency leaf must ask for more
+        assert!(matches!(
+            locking_script(2),
+            Err(CoreError::InvalidEmergencyQuorum(2))
+        ));
+        assert!(locking_script(3).is_ok());
+    }
+
+    #[test]
+    fn test_witness_slots_follow_opcodes() {
+        let keys = x_only_pubkeys(&keypairs(1..=2));
+        let (payment_hash, data) = ([3u8; 32], [4u8; 32]);
+        let script = Builder::new()
+            .push_slice(data)
+            .push_opcode(OP_DROP)
+            .push_opcode(OP_SHA256)
+            .push_slice(payment_hash)
+            .push_opcode(OP_EQUALVERIFY)
+            .push_x_only_key(&keys[0])
+            .push_opcode(OP_CHECKSIG)
+            .push_x_only_key(&keys[1])
+            .push_opcode(OP_CHECKSIGADD)
+            .push_int(2)
+            .push_opcode(OP_NUMEQUAL)
+            .into_script();
+
+        // The 32 bytes push that is dropped is neither a key nor a hash
+        let psbt = custodian_only_psbt(&keypairs(1..=3));
+        let positions = psbt.calculate_push_bytes_positions(&script);
+        assert_eq!(
+            positions,
+            BTreeMap::from([
+                (AliasPushBytes::from(payment_hash.to_vec()), 2),
+                (AliasPushBytes::from(keys[0].serialize().to_vec()), 1),
+                (AliasPushBytes::from(keys[1].serialize().to_vec()), 0),
+            ])
+        );
+    }
+}
@@ -0,0 +1,101 @@
+mod common;
+
+#[cfg(test)]
+mod test_validation {
+    use bitcoin::{
+        absolute, transaction::Version, Amount, ScriptBuf, TapSighashType, Transaction, TxOut,
+    };
+    use vault::{
+        validate_custodians, validate_distinct_keys, validate_locking_amount, validate_outputs,
+        validate_sighash_type, validate_tapscript_witness, CoreError, CustodianScriptBuilder,
+        MAX_STANDARD_TX_WEIGHT, MAX_TAPSCRIPT_STACK_SIZE,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_validation_errors() {
+        let custodians = x_only_pubkeys(&keypairs(1..=3));
+
+        assert!(validate_custodians(&custodians, 2, None).is_ok());
+        assert!(matches!(
+            validate_custodians(&[], 1, None),
+            Err(CoreError::EmptyCustodianSet)
+        ));
+        assert!(matches!(
+            validate_custodians(&custodians, 0, None),
+            Err(CoreError::InvalidCustodianQuorum { quorum: 0, .. })
+        ));
+        assert!(matches!(
+            validate_custodians(&custodians, 4, None),
+            Err(CoreError::InvalidCustodianQuorum { quorum: 4, .. })
+        ));
+        assert!(validate_custodians(&custodians, 4, Some(&[2, 1, 1])).is_ok());
+        assert!(matches!(
+            validate_custodians(&[custodians[0], custodians[0]], 1, None),
+            Err(CoreError::DuplicateCustodianKeys)
+        ));
+        assert!(matches!(
+            validate_distinct_keys(&[custodians[0], custodians[1], custodians[0]]),
+            Err(CoreError::OverlappingKeys(key)) if key == custodians[0]
+        ));
+
+        assert!(matches!(
+            validate_locking_amount(0),
+            Err(CoreError::ZeroAmount)
+        ));
+        assert!(matches!(
+            validate_locking_amount(329),
+            Err(CoreError::AmountBelowDust { amount: 329, .. })
+        ));
+        assert!(validate_outputs(&[TxOut {
+            value: Amount::ZERO,
+            script_pubkey: ScriptBuf::new_op_return([1u8; 4]),
+        }])
+        .is_ok());
+
+        // The script and the control block count as stack elements
+        let script = CustodianScriptBuilder::build(&custodians, 2, None).unwrap();
+        assert!(validate_tapscript_witness(&[65, 65, 65], &script).is_ok());
+        assert!(matches!(
+            validate_tapscript_witness(&[65; MAX_TAPSCRIPT_STACK_SIZE - 1], &script),
+            Err(CoreError::TapscriptStackLimitExceeded(1001))
+        ));
+        // The 80 bytes element limit of P2WSH does not apply to tapscript
+        assert!(validate_tapscript_witness(&[65, 520], &script).is_ok());
+        assert!(matches!(
+            validate_tapscript_witness(&[65], &ScriptBuf::from(vec![0x51; MAX_STANDARD_TX_WEIGHT])),
+            Err(CoreError::TapscriptWitnessTooLarge(_))
+        ));
+
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![Default::default(); 2],
+            output: vec![TxOut::NULL],
+        };
+        assert!(validate_sighash_type(TapSighashType::AllPlusAnyoneCanPay, &tx).is_ok());
+        assert!(matches!(
+            validate_sighash_type(TapSighashType::SinglePlusAnyoneCanPay, &tx),
+            Err(CoreError::SighashSingleWithoutOutput(_, 1, 2))
+        ));
+        let tx = Transaction {
+            output: vec![
+                TxOut {
+                    value: Amount::ZERO,
+                    script_pubkey: ScriptBuf::new_op_return([0u8; 32]),
+                },
+                TxOut::NULL,
+            ],
+            ..tx
+        };
+        assert!(matches!(
+            validate_sighash_type(TapSighashType::SinglePlusAnyoneCanPay, &tx),
+            Err(CoreError::SighashSingleWithDataOutput(_, 0))
+        ));
+        assert!(matches!(
+            validate_sighash_type(TapSighashType::Single, &tx),
+            Err(CoreError::UnsupportedSighashType(_))
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+mod common;
+
+#[cfg(test)]
+mod test_verification {
+    use bitcoin::{hashes::Hash, NetworkKind, TapLeafHash};
+    use vault::{
+        verify_tap_script_sigs, CoreError, Signing, TapScriptSig, TapScriptSigRejection,
+        TapScriptSigsMap, VaultManager,
+    };
+
+    use crate::common::*;
+
+    #[test]
+    fn test_verify_tap_script_sigs() {
+        let keypairs = keypairs(1..=4);
+        let custodians = x_only_pubkeys(&keypairs[..3]);
+        let unsigned = custodian_only_psbt(&keypairs[..3]);
+
+        let collect = |index: usize| {
+            let mut psbt = unsigned.clone();
+            let sigs = <VaultManager as Signing>::sign_psbt_and_collect_tap_script_sigs(
+                &mut psbt,
+                &keypairs[index].secret_bytes(),
+                NetworkKind::Test,
+            )
+            .unwrap();
+            sigs.get(0).unwrap()[0].clone()
+        };
+        let sig_1 = collect(0);
+        let sig_2 = collect(1);
+        let leaf_hash = *sig_1.leaf_hash();
+
+        // The signature of custodian 1 submitted as custodian 3's
+        let forged = TapScriptSig::new((custodians[2], leaf_hash), *sig_1.sig());
+        // A key outside of the custodian set, not in the leaf
+        let outsider = keypairs[3].x_only_public_key().0;
+        let not_in_leaf = TapScriptSig::new((outsider, leaf_hash), *sig_1.sig());
+        // A leaf that is not spent by this psbt
+        let unknown_leaf = TapScriptSig::new(
+            (custodians[1], TapLeafHash::from_byte_array([1u8; 32])),
+            *sig_2.sig(),
+        );
+
+        let mut submitted = TapScriptSigsMap::default();
+        submitted.insert(
+            0,
+            vec![
+                sig_1.clone(),
+                forged,
+                not_in_leaf,
+                unknown_leaf,
+                sig_2.clone(),
+            ],
+        );
+        let rejected: Vec<_> = verify_tap_script_sigs(&unsigned, &submitted)
+            .into_iter()
+            .map(|r| (r.key, r.reason))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (custodians[2], TapScriptSigRejection::InvalidSignature),
+                (outsider, TapScriptSigRejection::KeyNotInLeaf),
+                (custodians[1], TapScriptSigRejection::LeafNotFound),
+            ]
+        );
+
+        let mut psbt = unsigned.clone();
+        assert!(matches!(
+            <VaultManager as Signing>::aggregate_tap_script_sigs(&mut psbt, &submitted),
+            Err(CoreError::RejectedTapScriptSigs(rejected)) if rejected.len() == 3
+        ));
+        assert!(psbt.inputs[0].tap_script_sigs.is_empty());
+
+        let mut valid = TapScriptSigsMap::default();
+        valid.insert(0, vec![sig_1, sig_2]);
+        <VaultManager as Signing>::aggregate_tap_script_sigs(&mut psbt, &valid).unwrap();
+        assert!(<VaultManager as Signing>::finalize_psbt_and_extract_tx(&mut psbt).is_ok());
+    }
+}
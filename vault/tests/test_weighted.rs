@@ -0,0 +1,61 @@
+mod common;
+
+#[cfg(test)]
+mod test_weighted {
+    use vault::{custodian_signature_count, CustodianOnly, TaprootInternalKey, VaultManager};
+
+    use crate::common::*;
+
+    #[test]
+    fn test_weighted_custodian_only_unlocking() {
+        let keypairs = keypairs(1..=3);
+        let custodian_pubkeys = pubkeys(&keypairs);
+        let weights = vec![3, 1, 1];
+
+        // the two light custodians are one vote short, so the worst case is all three signing
+        assert_eq!(custodian_signature_count(Some(&weights), 3), 3);
+        assert_eq!(custodian_signature_count(Some(&weights), 2), 2);
+        assert_eq!(custodian_signature_count(None, 2), 2);
+
+        let script_pubkey = <VaultManager as CustodianOnly>::locking_script(
+            &custodian_pubkeys,
+            Some(&weights),
+            3,
+            TaprootInternalKey::Nums,
+            None,
+        )
+        .unwrap()
+        .into_script();
+        assert_ne!(script_pubkey, custodian_only_script(&custodian_pubkeys, 3));
+
+        let mut params = custodian_only_unlocking_params(
+            vec![previous_outpoint(0, 100_000, &script_pubkey)],
+            vec![p2tr_output(90_000, &keypairs[0])],
+            &custodian_pubkeys,
+            3,
+        );
+        params.custodian_weights = Some(weights);
+        let mut psbt =
+            <VaultManager as CustodianOnly>::build_unlocking_psbt(&manager(), &params).unwrap();
+
+        // the heaviest custodian reaches the quorum alone
+        sign(&mut psbt, &keypairs[0]);
+        let tx = finalize(&mut psbt);
+
+        // 3 signature slots, the leaf and its control block
+        let witness = &tx.input[0].witness;
+        assert_eq!(witness.len(), 5);
+
+        // the first key of the script consumes the last signature slot
+        let mut sorted_keys = x_only_pubkeys(&keypairs);
+        sorted_keys.sort();
+        let heavy_position = sorted_keys
+            .iter()
+            .position(|key| *key == keypairs[0].x_only_public_key().0)
+            .unwrap();
+        for slot in 0..3 {
+            let expected_len = if slot == 2 - heavy_position { 64 } else { 0 };
+            assert_eq!(witness.nth(slot).unwrap().len(), expected_len);
+        }
+    }
+}
@@ -18,7 +18,7 @@ impl Decoder {
     }
     pub fn decode_33bytes_pubkey_list(input: &[u8]) -> Result<Vec<PublicKey>, VaultABIError> {
         let key_len = PUBLIC_KEY_SIZE;
-        if input.is_empty() || input.len() % key_len != 0 {
+        if input.is_empty() || !input.len().is_multiple_of(key_len) {
             return Err(VaultABIError::InvalidInputData);
         }
         let number_of_pubkeys = input.len() / key_len;
@@ -2,11 +2,13 @@ use std::convert::{TryFrom, TryInto};
 
 use crate::errors::VaultABIError;
 use crate::{decoder::Decoder, encoder::Encoder};
-use bitcoin::{Amount, NetworkKind, OutPoint, PublicKey, TxOut};
+use bitcoin::{relative, Amount, NetworkKind, OutPoint, PublicKey, TxOut};
 use vault::{
-    CustodianOnly, CustodianOnlyLockingParams, DestinationChain, DestinationRecipientAddress,
-    DestinationTokenAddress, PreviousOutpoint, Signing, UPCLockingParams, UPCUnlockingParams,
-    UPCUnlockingType, VaultManager, UPC,
+    inspect_signing_status, BranchLayout, CustodianOnly, CustodianOnlyLockingParams,
+    DestinationChain, DestinationRecipientAddress, DestinationTokenAddress, PreviousOutpoint,
+    Signing, TaprootInternalKey, TimeGated, TimeGatedInput, TimeGatedUnlockingParams,
+    TimeGatedUnlockingType, UPCLockingParams, UPCUnlockingParams, UPCUnlockingType, VaultManager,
+    UPC,
 };
 
 use wasm_bindgen::prelude::*;
@@ -35,6 +37,36 @@ impl TryFrom<UnlockingTypeWasm> for UPCUnlockingType {
     }
 }
 
+#[wasm_bindgen]
+pub enum TimeGatedUnlockingTypeWasm {
+    CustodianOnly,
+    PartyTimeGated,
+}
+
+impl From<TimeGatedUnlockingTypeWasm> for TimeGatedUnlockingType {
+    fn from(value: TimeGatedUnlockingTypeWasm) -> Self {
+        match value {
+            TimeGatedUnlockingTypeWasm::CustodianOnly => TimeGatedUnlockingType::CustodianOnly,
+            TimeGatedUnlockingTypeWasm::PartyTimeGated => TimeGatedUnlockingType::PartyTimeGated,
+        }
+    }
+}
+
+/// BIP-68 relative lock time unit.
+#[wasm_bindgen]
+pub enum RelativeLockTimeTypeWasm {
+    Blocks,
+    /// 512-second intervals
+    Seconds,
+}
+
+fn relative_lock_time(typ: RelativeLockTimeTypeWasm, value: u16) -> relative::LockTime {
+    match typ {
+        RelativeLockTimeTypeWasm::Blocks => relative::LockTime::from_height(value),
+        RelativeLockTimeTypeWasm::Seconds => relative::LockTime::from_512_second_intervals(value),
+    }
+}
+
 #[wasm_bindgen]
 pub struct TxOutWasm {
     script_pubkey: Vec<u8>,
@@ -142,6 +174,10 @@ impl TryFrom<UpcLockingParamsWasm> for UPCLockingParams {
             protocol_pubkey,
             custodian_pubkeys,
             custodian_quorum: params.custodian_quorum,
+            custodian_weights: None,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Legacy,
+            emergency: None,
             destination_chain,
             destination_token_address,
             destination_recipient_address,
@@ -171,11 +207,11 @@ impl TryFrom<UpcUnlockingParamsWasm> for UPCUnlockingParams {
             &params.custodian_pubkeys,
         )?;
 
-        let inputs: Vec<PreviousOutpoint> = params
+        let inputs = params
             .inputs
             .into_iter()
-            .map(|input| input.try_into().unwrap())
-            .collect();
+            .map(PreviousOutpoint::try_from)
+            .collect::<Result<_, _>>()?;
 
         Ok(UPCUnlockingParams {
             inputs,
@@ -184,13 +220,120 @@ impl TryFrom<UpcUnlockingParamsWasm> for UPCUnlockingParams {
             protocol_pubkey,
             custodian_pubkeys,
             custodian_quorum: params.custodian_quorum,
+            custodian_weights: None,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Legacy,
+            emergency: None,
             rbf: params.rbf,
             fee_rate: params.fee_rate,
+            key_origins: None,
+            sighash_type: None,
             typ: UPCUnlockingType::try_from(params.unlocking_type)?,
         })
     }
 }
 
+#[wasm_bindgen]
+pub struct TimeGatedInputWasm {
+    input: PreviousOutpointWasm,
+    sequence_type: RelativeLockTimeTypeWasm,
+    sequence: u16,
+}
+
+#[wasm_bindgen]
+impl TimeGatedInputWasm {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        input: PreviousOutpointWasm,
+        sequence_type: RelativeLockTimeTypeWasm,
+        sequence: u16,
+    ) -> Self {
+        Self {
+            input,
+            sequence_type,
+            sequence,
+        }
+    }
+}
+
+impl TryFrom<TimeGatedInputWasm> for TimeGatedInput {
+    type Error = VaultABIError;
+
+    fn try_from(input: TimeGatedInputWasm) -> Result<Self, Self::Error> {
+        Ok(TimeGatedInput {
+            input: input.input.try_into()?,
+            sequence: relative_lock_time(input.sequence_type, input.sequence),
+        })
+    }
+}
+
+#[wasm_bindgen]
+pub struct TimeGatedUnlockingParamsWasm {
+    inputs: Vec<TimeGatedInputWasm>,
+    outputs: Vec<TxOutWasm>,
+    change_script_pubkey: Vec<u8>,
+    party_pubkey: Vec<u8>,
+    custodian_pubkeys: Vec<u8>,
+    custodian_quorum: u8,
+    fee_rate: u64,
+    unlocking_type: TimeGatedUnlockingTypeWasm,
+}
+
+#[wasm_bindgen]
+impl TimeGatedUnlockingParamsWasm {
+    #[allow(clippy::too_many_arguments)]
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        inputs: Vec<TimeGatedInputWasm>,
+        outputs: Vec<TxOutWasm>,
+        change_script_pubkey: Vec<u8>,
+        party_pubkey: Vec<u8>,
+        custodian_pubkeys: Vec<u8>,
+        custodian_quorum: u8,
+        fee_rate: u64,
+        unlocking_type: TimeGatedUnlockingTypeWasm,
+    ) -> Self {
+        Self {
+            inputs,
+            outputs,
+            change_script_pubkey,
+            party_pubkey,
+            custodian_pubkeys,
+            custodian_quorum,
+            fee_rate,
+            unlocking_type,
+        }
+    }
+}
+
+impl TryFrom<TimeGatedUnlockingParamsWasm> for TimeGatedUnlockingParams {
+    type Error = JsValue;
+    fn try_from(params: TimeGatedUnlockingParamsWasm) -> Result<Self, Self::Error> {
+        Ok(TimeGatedUnlockingParams {
+            inputs: params
+                .inputs
+                .into_iter()
+                .map(TimeGatedInput::try_from)
+                .collect::<Result<_, _>>()?,
+            outputs: params
+                .outputs
+                .into_iter()
+                .map(TxOut::try_from)
+                .collect::<Result<_, _>>()?,
+            change_script_pubkey: Decoder::decode_script_pubkey(&params.change_script_pubkey),
+            party_pubkey: Decoder::decode_33bytes_pubkey(&params.party_pubkey)?,
+            custodian_pubkeys: Decoder::decode_33bytes_pubkey_list(&params.custodian_pubkeys)?,
+            custodian_quorum: params.custodian_quorum,
+            custodian_weights: None,
+            branch_layout: BranchLayout::Legacy,
+            fee_rate: params.fee_rate,
+            key_origins: None,
+            sighash_type: None,
+            typ: params.unlocking_type.into(),
+        })
+    }
+}
+
 #[wasm_bindgen]
 pub struct VaultWasm {
     manager: VaultManager,
@@ -232,14 +375,84 @@ impl VaultWasm {
         ))
     }
 
+    fn custodian_only_locking_params(
+        amount: u64,
+        //33 bytes pubkey
+        custodial_pubkeys: &[u8],
+        custodian_quorum: u8,
+        destination_chain: &[u8],
+        destination_token_address: &[u8],
+        destination_recipient_address: &[u8],
+    ) -> Result<CustodianOnlyLockingParams, JsValue> {
+        let (destination_chain, destination_token_address, destination_recipient_address) =
+            VaultWasm::parse_destination_params(
+                destination_chain,
+                destination_token_address,
+                destination_recipient_address,
+            )?;
+
+        Ok(CustodianOnlyLockingParams {
+            locking_amount: amount,
+            custodian_pubkeys: Decoder::decode_33bytes_pubkey_list(custodial_pubkeys)?,
+            custodian_quorum,
+            custodian_weights: None,
+            internal_key: TaprootInternalKey::Nums,
+            branch_layout: BranchLayout::Legacy,
+            destination_chain,
+            destination_token_address,
+            destination_recipient_address,
+            subsets: None,
+        })
+    }
+
+    fn custodian_only_locking_script_with_internal_key(
+        custodian_pubkeys: &[u8],
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+    ) -> Result<Vec<u8>, JsValue> {
+        let custodian_pubkeys = Decoder::decode_33bytes_pubkey_list(custodian_pubkeys)?;
+
+        let script = <VaultManager as CustodianOnly>::locking_script(
+            &custodian_pubkeys,
+            None,
+            custodian_quorum,
+            internal_key,
+            None,
+        )
+        .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(script.into_script().to_bytes())
+    }
+
+    fn upc_locking_script_with_internal_key(
+        user_pubkey: &[u8],
+        protocol_pubkey: &[u8],
+        custodian_pubkeys: &[u8],
+        custodian_quorum: u8,
+        internal_key: TaprootInternalKey,
+    ) -> Result<Vec<u8>, JsValue> {
+        let user_pubkey = Decoder::decode_33bytes_pubkey(user_pubkey)?;
+        let protocol_pubkey = Decoder::decode_33bytes_pubkey(protocol_pubkey)?;
+        let custodian_pubkeys = Decoder::decode_33bytes_pubkey_list(custodian_pubkeys)?;
+        let script = <VaultManager as UPC>::locking_script(
+            &user_pubkey,
+            &protocol_pubkey,
+            &custodian_pubkeys,
+            None,
+            custodian_quorum,
+            internal_key,
+            None,
+        )
+        .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(script.into_script().to_bytes())
+    }
+
+    /// Failures are thrown as a JS error with the message of the error. They used to be
+    /// returned as an empty buffer, which callers could mistake for an output.
     fn handle_serialize_result<T>(
-        result: Result<T, impl std::fmt::Debug>,
+        result: Result<T, impl std::fmt::Display>,
         f: impl FnOnce(T) -> Vec<u8>,
     ) -> Result<Vec<u8>, JsValue> {
-        match result {
-            Ok(output) => Ok(f(output)),
-            Err(_) => Ok(vec![]),
-        }
+        result.map(f).map_err(|e| JsValue::from(e.to_string()))
     }
 }
 
@@ -291,6 +504,14 @@ impl VaultWasm {
                 .map_err(|e| VaultABIError::DecodingError(format!("{}", e)))?;
         Ok(signed_psbt)
     }
+
+    /// Json report of the valid and missing signatures of every input of the psbt
+    #[wasm_bindgen]
+    pub fn inspect_psbt_signing_status(&self, psbt: &[u8]) -> Result<String, JsValue> {
+        let psbt = Decoder::decode_psbt(psbt)?;
+        serde_json::to_string(&inspect_signing_status(&psbt))
+            .map_err(|e| VaultABIError::DecodingError(e.to_string()).into())
+    }
 }
 
 #[wasm_bindgen]
@@ -306,20 +527,45 @@ impl VaultWasm {
         destination_token_address: &[u8],
         destination_recipient_address: &[u8],
     ) -> Result<Vec<u8>, JsValue> {
-        let (destination_chain, destination_token_address, destination_recipient_address) =
-            VaultWasm::parse_destination_params(
-                destination_chain,
-                destination_token_address,
-                destination_recipient_address,
-            )?;
-
-        let params = CustodianOnlyLockingParams {
-            locking_amount: amount,
-            custodian_pubkeys: Decoder::decode_33bytes_pubkey_list(custodial_pubkeys)?,
+        let params = Self::custodian_only_locking_params(
+            amount,
+            custodial_pubkeys,
             custodian_quorum,
             destination_chain,
             destination_token_address,
             destination_recipient_address,
+        )?;
+
+        Self::handle_serialize_result(
+            <VaultManager as CustodianOnly>::build_locking_output(&self.manager, &params),
+            |output| Encoder::serialize_tx_outs(&output.into_tx_outs()),
+        )
+    }
+
+    /// Same as `build_custodian_only_locking`, all custodians together can also spend through
+    /// the key path with their MuSig2 aggregated key.
+    #[wasm_bindgen]
+    pub fn build_custodian_only_musig_locking(
+        &self,
+        amount: u64,
+        //33 bytes pubkey
+        custodial_pubkeys: &[u8],
+        custodian_quorum: u8,
+        destination_chain: &[u8],
+        destination_token_address: &[u8],
+        destination_recipient_address: &[u8],
+    ) -> Result<Vec<u8>, JsValue> {
+        let params = CustodianOnlyLockingParams {
+            internal_key: TaprootInternalKey::CustodianMusig2,
+            branch_layout: BranchLayout::Legacy,
+            ..Self::custodian_only_locking_params(
+                amount,
+                custodial_pubkeys,
+                custodian_quorum,
+                destination_chain,
+                destination_token_address,
+                destination_recipient_address,
+            )?
         };
 
         Self::handle_serialize_result(
@@ -334,12 +580,24 @@ impl VaultWasm {
         custodian_pubkeys: &[u8],
         custodian_quorum: u8,
     ) -> Result<Vec<u8>, JsValue> {
-        let custodian_pubkeys = Decoder::decode_33bytes_pubkey_list(custodian_pubkeys)?;
+        Self::custodian_only_locking_script_with_internal_key(
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::Nums,
+        )
+    }
 
-        let script =
-            <VaultManager as CustodianOnly>::locking_script(&custodian_pubkeys, custodian_quorum)
-                .map_err(|e| JsValue::from(format!("{:?}", e)))?;
-        Ok(script.into_script().to_bytes())
+    #[wasm_bindgen]
+    pub fn custodian_only_musig_locking_script(
+        &self,
+        custodian_pubkeys: &[u8],
+        custodian_quorum: u8,
+    ) -> Result<Vec<u8>, JsValue> {
+        Self::custodian_only_locking_script_with_internal_key(
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::CustodianMusig2,
+        )
     }
 
     #[wasm_bindgen]
@@ -350,16 +608,62 @@ impl VaultWasm {
         custodian_pubkeys: &[u8],
         custodian_quorum: u8,
     ) -> Result<Vec<u8>, JsValue> {
-        let user_pubkey = Decoder::decode_33bytes_pubkey(user_pubkey)?;
-        let protocol_pubkey = Decoder::decode_33bytes_pubkey(protocol_pubkey)?;
+        Self::upc_locking_script_with_internal_key(
+            user_pubkey,
+            protocol_pubkey,
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::Nums,
+        )
+    }
+
+    #[wasm_bindgen]
+    pub fn upc_musig_locking_script(
+        &self,
+        user_pubkey: &[u8],
+        protocol_pubkey: &[u8],
+        custodian_pubkeys: &[u8],
+        custodian_quorum: u8,
+    ) -> Result<Vec<u8>, JsValue> {
+        Self::upc_locking_script_with_internal_key(
+            user_pubkey,
+            protocol_pubkey,
+            custodian_pubkeys,
+            custodian_quorum,
+            TaprootInternalKey::CustodianMusig2,
+        )
+    }
+
+    #[wasm_bindgen]
+    pub fn time_gated_locking_script(
+        &self,
+        party_pubkey: &[u8],
+        custodian_pubkeys: &[u8],
+        custodian_quorum: u8,
+        sequence_type: RelativeLockTimeTypeWasm,
+        sequence: u16,
+    ) -> Result<Vec<u8>, JsValue> {
+        let party_pubkey = Decoder::decode_33bytes_pubkey(party_pubkey)?;
         let custodian_pubkeys = Decoder::decode_33bytes_pubkey_list(custodian_pubkeys)?;
-        let script = <VaultManager as UPC>::locking_script(
-            &user_pubkey,
-            &protocol_pubkey,
+        let script = <VaultManager as TimeGated>::locking_script(
+            &party_pubkey,
             &custodian_pubkeys,
+            None,
             custodian_quorum,
+            relative_lock_time(sequence_type, sequence),
         )
-        .map_err(|e| JsValue::from(format!("{:?}", e)))?;
+        .map_err(|e| JsValue::from(e.to_string()))?;
         Ok(script.into_script().to_bytes())
     }
+
+    #[wasm_bindgen]
+    pub fn build_time_gated_unlocking(
+        &self,
+        params: TimeGatedUnlockingParamsWasm,
+    ) -> Result<Vec<u8>, JsValue> {
+        Self::handle_serialize_result(
+            <VaultManager as TimeGated>::build_unlocking_psbt(&self.manager, &params.try_into()?),
+            |psbt| psbt.serialize(),
+        )
+    }
 }